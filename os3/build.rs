@@ -6,10 +6,39 @@ use std::io::{Result, Write};
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
+    println!("cargo:rerun-if-changed={}", ELF_PATH);
     insert_app_data().unwrap();
 }
 
 static TARGET_PATH: &str = "../user/build/bin/";
+/// where the ELF files of the apps are, to take their notes from
+static ELF_PATH: &str = "../user/build/elf/";
+/// section of the app metadata notes, see `src/appmeta.rs`
+static NOTE_SECTION: &str = ".note.app";
+
+/// get `(offset, size)` of section `name` in ELF64 file `path`, if it has one
+fn find_section(path: &str, name: &str) -> Option<(u64, u64)> {
+    let elf = std::fs::read(path).ok()?;
+    if elf.get(..5)? != b"\x7fELF\x02" {
+        return None;
+    }
+    let read = |offset: u64, len: usize| -> Option<u64> {
+        let bytes = elf.get(offset as usize..offset as usize + len)?;
+        Some(bytes.iter().rev().fold(0, |v, &b| v << 8 | b as u64))
+    };
+    let shoff = read(0x28, 8)?;
+    let shentsize = read(0x3a, 2)?;
+    let shnum = read(0x3c, 2)?;
+    let strtab = read(shoff + read(0x3e, 2)? * shentsize + 0x18, 8)?;
+    (0..shnum).map(|i| shoff + i * shentsize).find_map(|sh| {
+        let name_start = (strtab + read(sh, 4)?) as usize;
+        let name_len = elf.get(name_start..)?.iter().position(|&b| b == 0)?;
+        if &elf[name_start..name_start + name_len] != name.as_bytes() {
+            return None;
+        }
+        Some((read(sh + 0x18, 8)?, read(sh + 0x20, 8)?))
+    })
+}
 
 /// get app data and build linker
 fn insert_app_data() -> Result<()> {
@@ -41,6 +70,41 @@ _num_app:
     }
     writeln!(f, r#"    .quad app_{}_end"#, apps.len() - 1)?;
 
+    writeln!(
+        f,
+        r#"
+    .global _app_names
+_app_names:"#
+    )?;
+    for app in apps.iter() {
+        writeln!(f, r#"    .string "{}""#, app)?;
+    }
+
+    writeln!(
+        f,
+        r#"
+    .align 3
+    .global _app_notes
+_app_notes:"#
+    )?;
+    for i in 0..apps.len() {
+        writeln!(f, r#"    .quad app_{0}_note_start, app_{0}_note_end"#, i)?;
+    }
+    for (idx, app) in apps.iter().enumerate() {
+        let elf = format!("{}{}.elf", ELF_PATH, app);
+        writeln!(
+            f,
+            r#"
+    .align 2
+app_{}_note_start:"#,
+            idx
+        )?;
+        if let Some((offset, size)) = find_section(&elf, NOTE_SECTION) {
+            writeln!(f, r#"    .incbin "{}", {}, {}"#, elf, offset, size)?;
+        }
+        writeln!(f, r#"app_{}_note_end:"#, idx)?;
+    }
+
     for (idx, app) in apps.iter().enumerate() {
         println!("app_{}: {}", idx, app);
         writeln!(
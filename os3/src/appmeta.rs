@@ -0,0 +1,157 @@
+//! Per-app metadata
+//!
+//! An app may embed an ELF note saying what it asks of the kernel, with
+//! `app_meta!` of the user library: a nice value to start with, the bytes of
+//! stack it needs, the syscalls it can't do without, and what it does. The
+//! note is kept out of the image the app is loaded from, so the build script
+//! links it in next to it, and [`init`] parses it. A kernel built without
+//! the notes linked in, as by the CI build script, has no metadata for any
+//! app.
+//!
+//! The loader refuses an app whose stack or syscalls the kernel can't
+//! provide, and the task of the app starts with its nice value and with its
+//! stack size as the soft `RLIMIT_STACK`, so that an app carries its own
+//! configuration rather than needing it on the command line.
+
+use crate::config::USER_STACK_SIZE;
+use crate::loader::{get_app_name, get_num_app};
+use crate::sync::LazyInit;
+use crate::syscall::syscall_supported;
+use crate::task::{NICE_MAX, NICE_MIN};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// owner of the note, NUL included
+const NOTE_NAME: &[u8] = b"rCore\0";
+/// note type of the app metadata
+const NT_APP_META: u32 = 1;
+/// `nice` of metadata that doesn't ask for one
+const NICE_DEFAULT: i32 = i32::MIN;
+const MAX_REQUIRED_SYSCALLS: usize = 16;
+const MAX_DESCRIPTION_LEN: usize = 64;
+/// bytes of the note description: nice, stack size, syscalls, description
+const DESC_SIZE: usize = 4 + 4 + 2 * MAX_REQUIRED_SYSCALLS + MAX_DESCRIPTION_LEN;
+
+/// What an app asks of the kernel
+pub struct AppMeta {
+    /// nice value to start with
+    pub nice: Option<isize>,
+    /// bytes of user stack needed
+    pub stack_size: Option<usize>,
+    /// ids of the syscalls the app can't do without
+    pub syscalls: Vec<usize>,
+    pub description: &'static str,
+}
+
+impl AppMeta {
+    /// Parse the description of the metadata note.
+    fn parse(desc: &'static [u8]) -> Option<Self> {
+        let nice = u32_at(desc, 0) as i32;
+        let stack_size = u32_at(desc, 4) as usize;
+        let syscalls = desc[8..8 + 2 * MAX_REQUIRED_SYSCALLS]
+            .chunks(2)
+            .map(|id| u16::from_le_bytes([id[0], id[1]]) as usize)
+            .take_while(|&id| id != 0)
+            .collect();
+        let description = &desc[8 + 2 * MAX_REQUIRED_SYSCALLS..DESC_SIZE];
+        let len = description
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MAX_DESCRIPTION_LEN);
+        Some(Self {
+            nice: (nice != NICE_DEFAULT).then(|| (nice as isize).clamp(NICE_MIN, NICE_MAX)),
+            stack_size: (stack_size != 0).then(|| stack_size),
+            syscalls,
+            description: core::str::from_utf8(&description[..len]).ok()?,
+        })
+    }
+
+    /// Why the kernel can't run the app, if it can't.
+    pub fn unmet(&self) -> Option<String> {
+        if let Some(size) = self.stack_size.filter(|&size| size > USER_STACK_SIZE) {
+            return Some(format!(
+                "needs {} bytes of stack, over USER_STACK_SIZE = {}",
+                size, USER_STACK_SIZE
+            ));
+        }
+        let id = self.syscalls.iter().find(|&&id| !syscall_supported(id))?;
+        Some(format!("needs syscall {}, which is not supported", id))
+    }
+}
+
+/// Read the little-endian `u32` at `offset` of `bytes`.
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    let b = &bytes[offset..offset + 4];
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+/// Find the metadata note among `notes`.
+fn find_note(mut notes: &'static [u8]) -> Option<&'static [u8]> {
+    let align = |n: usize| (n + 3) & !3;
+    while notes.len() >= 12 {
+        let namesz = u32_at(notes, 0) as usize;
+        let descsz = u32_at(notes, 4) as usize;
+        let ntype = u32_at(notes, 8);
+        let desc = 12 + align(namesz);
+        let next = desc + align(descsz);
+        if next > notes.len() {
+            return None;
+        }
+        if &notes[12..12 + namesz] == NOTE_NAME && ntype == NT_APP_META && descsz >= DESC_SIZE {
+            return Some(&notes[desc..desc + descsz]);
+        }
+        notes = &notes[next..];
+    }
+    None
+}
+
+// The address of the table of notes, 0 if `linker.ld` provides it for lack
+// of one; read from memory, as taking it directly the compiler could assume
+// it is never 0.
+core::arch::global_asm!(
+    "
+    .section .rodata
+    .align 3
+    .global _app_notes_addr
+_app_notes_addr:
+    .quad _app_notes"
+);
+
+/// metadata of each app, set up by [`init`]
+static APP_META: LazyInit<Vec<Option<AppMeta>>> = LazyInit::new("APP_META");
+
+/// Parse the metadata notes linked in with the apps.
+pub fn init() {
+    extern "C" {
+        static _app_notes_addr: usize;
+    }
+    let notes_addr = unsafe { _app_notes_addr };
+    let mut metas = Vec::new();
+    // not linked in by a build script that doesn't know about metadata
+    if notes_addr == 0 {
+        metas.resize_with(get_num_app(), || None);
+        APP_META.init(metas);
+        return;
+    }
+    let bounds =
+        unsafe { core::slice::from_raw_parts(notes_addr as *const (usize, usize), get_num_app()) };
+    for (app_id, &(start, end)) in bounds.iter().enumerate() {
+        let notes = unsafe { core::slice::from_raw_parts(start as *const u8, end - start) };
+        let meta = find_note(notes).and_then(AppMeta::parse);
+        if meta.is_none() && !notes.is_empty() {
+            println!(
+                "[kernel] app {} ({}) has malformed metadata, ignored",
+                app_id,
+                get_app_name(app_id)
+            );
+        }
+        metas.push(meta);
+    }
+    APP_META.init(metas);
+}
+
+/// Get the metadata of app `app_id`, if it has any.
+pub fn get_app_meta(app_id: usize) -> Option<&'static AppMeta> {
+    APP_META[app_id].as_ref()
+}
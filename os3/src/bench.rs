@@ -0,0 +1,146 @@
+//! Boot microbenchmarks
+//!
+//! With the `bench` boot option, [`run`] times the paths every task goes
+//! through all the time, right before the first app runs, printing the mean
+//! and 99th percentile in cycles of each:
+//!
+//! - `switch`: `__switch` from one kernel context to another
+//! - `syscall`: a `getpid` from user mode, from its `ecall` to back in user
+//!   mode, through the trap entry, [`crate::trap`] and `__restore`
+//! - `trap`: the same round trip for a syscall id there is no handler for,
+//!   which is about all trap entry and exit
+//!
+//! The user mode side is `__bench_user` of `bench.S`, which times each
+//! `ecall` itself. It traps in place of the first task, before that runs,
+//! so the accounting of the task is cleared afterwards; with no apps, the
+//! round trips are skipped.
+//!
+//! Nothing else runs meanwhile, interrupts are masked at boot, and the timer
+//! interrupt also in user mode, so the numbers vary with the paths
+//! themselves only.
+
+use crate::config::KERNEL_STACK_SIZE;
+use crate::loader::get_num_app;
+use crate::syscall::SYSCALL_GETPID;
+use crate::task::{clear_accounting, get_current_task, TaskContext, __switch};
+use crate::timer::get_cycle;
+use crate::trap::TrapContext;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use riscv::register::sie;
+
+core::arch::global_asm!(include_str!("bench.S"));
+
+extern "C" {
+    fn __bench_user(samples: *mut usize, runs: usize, syscall_id: usize);
+    fn __bench_user_done();
+}
+
+/// runs of each benchmark
+const ITERATIONS: usize = 4096;
+/// bytes of stack for the other end of the switches
+const PARTNER_STACK_SIZE: usize = 4096;
+/// a syscall id with no handler
+const NO_SYSCALL: usize = usize::MAX;
+
+/// contexts of the benchmark and of the other end it switches to and back,
+/// the partner or the trap ending the user round trips
+static mut CONTEXTS: [TaskContext; 2] = [TaskContext::zero_init(); 2];
+
+/// Switch straight back to the benchmark for as long as it switches here.
+extern "C" fn partner() -> ! {
+    loop {
+        unsafe {
+            __switch(&mut CONTEXTS[1], &CONTEXTS[0]);
+        }
+    }
+}
+
+/// Time `f` over [`ITERATIONS`] runs, returning the cycles of each.
+fn measure(mut f: impl FnMut()) -> Vec<usize> {
+    (0..ITERATIONS)
+        .map(|_| {
+            let start = get_cycle();
+            f();
+            get_cycle().wrapping_sub(start)
+        })
+        .collect()
+}
+
+/// Time [`ITERATIONS`] round trips from user mode of syscall `syscall_id`,
+/// returning the cycles of each.
+fn measure_user(syscall_id: usize) -> Vec<usize> {
+    let mut samples = vec![0; ITERATIONS];
+    let stack = vec![0u128; KERNEL_STACK_SIZE / 16];
+    let top = stack.as_ptr_range().end as usize;
+    // `__restore` returns to user mode with this, and traps come back to
+    // the stack above it; the round trips don't use a user stack
+    let mut cx = TrapContext::app_init_context(__bench_user as usize, 0);
+    cx.x[10] = samples.as_mut_ptr() as usize;
+    cx.x[11] = ITERATIONS;
+    cx.x[12] = syscall_id;
+    let cx_ptr = (top - size_of::<TrapContext>()) as *mut TrapContext;
+    let user = TaskContext::goto_restore(cx_ptr as usize);
+    let timer = sie::read().stimer();
+    unsafe {
+        cx_ptr.write(cx);
+        // interrupts are always taken in user mode, and a tick would
+        // preempt the task the round trips stand in for
+        sie::clear_stimer();
+        __switch(&mut CONTEXTS[0], &user);
+        if timer {
+            sie::set_stimer();
+        }
+    }
+    drop(stack);
+    samples
+}
+
+/// Switch back to the benchmark if the breakpoint of a trap at `cx.sepc` is
+/// the one ending the user round trips, so never returning then. Returns
+/// `false` for any other breakpoint.
+pub fn leave_user(cx: &TrapContext) -> bool {
+    if cx.sepc != __bench_user_done as usize {
+        return false;
+    }
+    // the trap is never returned from, its frames go with the stack
+    unsafe {
+        __switch(&mut CONTEXTS[1], &CONTEXTS[0]);
+    }
+    unreachable!()
+}
+
+/// Print the mean and 99th percentile of `samples`, each `runs` runs of
+/// benchmark `name`.
+fn report(name: &str, mut samples: Vec<usize>, runs: usize) {
+    samples.sort_unstable();
+    let mean = samples.iter().sum::<usize>() / samples.len() / runs;
+    let p99 = samples[samples.len() * 99 / 100] / runs;
+    println!(
+        "[kernel] bench: {:<8} mean {:>6} p99 {:>6} cycles ({} runs)",
+        name,
+        mean,
+        p99,
+        samples.len()
+    );
+}
+
+/// Run all benchmarks.
+pub fn run() {
+    let stack = vec![0u128; PARTNER_STACK_SIZE / 16];
+    let top = stack.as_ptr_range().end as usize;
+    unsafe {
+        CONTEXTS[1] = TaskContext::goto_entry(partner as usize, top);
+    }
+    // a round trip is two switches
+    let switch = measure(|| unsafe { __switch(&mut CONTEXTS[0], &CONTEXTS[1]) });
+    report("switch", switch, 2);
+    drop(stack);
+    if get_num_app() == 0 {
+        return;
+    }
+    report("syscall", measure_user(SYSCALL_GETPID), 1);
+    report("trap", measure_user(NO_SYSCALL), 1);
+    clear_accounting(get_current_task());
+}
@@ -0,0 +1,72 @@
+//! Staged kernel initialization
+//!
+//! [`crate::rust_main`] runs its init calls as [`Step`]s through [`run`],
+//! grouped into [`Stage`]s that must come in order. Each step is logged at
+//! `trace` level as it starts, so a hang points at it, and its duration at
+//! `debug` level; a panic during boot reports the step it happened in.
+
+use crate::timer::get_time_us;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Stages of the boot, in the order they run
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// what runs on the early console, see [`crate::console`]
+    Earlycon,
+    /// the kernel heap and what needs it to be set up
+    Heap,
+    /// the memory map and the memory added to the heap
+    Memory,
+    /// devices and device-like facilities
+    Drivers,
+    /// registration of tunables, then the boot options setting them
+    Options,
+    /// trap entry and the timer interrupt
+    Traps,
+    /// loading the apps and creating their tasks
+    Tasks,
+}
+
+/// An init call, given the address of the device tree
+pub struct Step {
+    pub stage: Stage,
+    pub name: &'static str,
+    pub init: fn(usize),
+}
+
+/// index into the steps being run of the one running, `usize::MAX` once
+/// the boot is over
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+/// the steps being run
+static mut STEPS: &[Step] = &[];
+
+/// Run `steps` in order, panicking if their stages are not.
+pub fn run(steps: &'static [Step], dtb_addr: usize) {
+    if let Some(w) = steps.windows(2).find(|w| w[0].stage > w[1].stage) {
+        panic!(
+            "boot step {} ({:?}) is after {} ({:?})",
+            w[1].name, w[1].stage, w[0].name, w[0].stage
+        );
+    }
+    unsafe {
+        STEPS = steps;
+    }
+    for (i, step) in steps.iter().enumerate() {
+        CURRENT.store(i, Ordering::Relaxed);
+        trace!("[kernel] boot: {:?}/{}", step.stage, step.name);
+        let start = get_time_us();
+        (step.init)(dtb_addr);
+        debug!(
+            "[kernel] boot: {:?}/{} took {} us",
+            step.stage,
+            step.name,
+            get_time_us() - start
+        );
+    }
+    CURRENT.store(usize::MAX, Ordering::Relaxed);
+}
+
+/// Get the step being run, if the boot is not over.
+pub fn current_step() -> Option<&'static Step> {
+    unsafe { STEPS.get(CURRENT.load(Ordering::Relaxed)) }
+}
@@ -0,0 +1,204 @@
+//! Kernel command line
+//!
+//! Options are taken from `/chosen/bootargs` of the device tree, as
+//! whitespace separated `key=value` pairs. If it has none, as QEMU gives
+//! none to a kernel loaded with `-device loader`, they are taken from the
+//! `BOOTARGS` build variable instead:
+//!
+//! - `log=<level>`: maximum log level, overriding the `LOG` build variable
+//! - `sched=rr|stride|mlfq`: scheduling policy, see [`crate::task::Policy`]
+//! - `tick_hz=<n>`: timer interrupts per second
+//! - `init=<app>`: name of the app to run first
+//! - `apps=<app>,<app>,...`: names of the apps to load, all of them if left
+//!   out
+//! - `privileged=<app>,<app>,...`: names of the apps allowed the privileged
+//!   syscalls, the first app to run if left out
+//! - `end=shutdown|idle|panic`: what to do once all apps have exited
+//! - `trap=direct|vectored`: `stvec` mode for traps from user space
+//! - `sched_record`: log the scheduling decisions, and print them as a
+//!   `sched_replay=` option at the end
+//! - `sched_replay=<id>,<id>,...`: switch to these tasks in this order, see
+//!   [`crate::task::replay`]
+//! - `selftest`: check the kernel is set up right before running the apps,
+//!   see [`crate::selftest`]
+//! - `bench`: time context switches, syscalls and traps before running the
+//!   apps, see [`crate::bench`]
+//!
+//! Any other `name=<n>` sets the [`crate::sysctl`] tunable `name`. Unknown or
+//! malformed options are reported and ignored.
+
+use crate::config::MAX_APP_NUM;
+use crate::fdt::Fdt;
+use crate::loader::{get_app_name, get_num_app};
+use crate::logging;
+use crate::sync::UPSafeCell;
+use crate::sysctl;
+use crate::task::{replay, Policy, END_ACTION_IDLE, END_ACTION_PANIC, END_ACTION_SHUTDOWN};
+use crate::timer;
+use crate::trap;
+use lazy_static::*;
+
+/// options that are applied later than [`init`]
+struct BootOptions {
+    /// id of the app to run first
+    init_app: Option<usize>,
+    /// which apps to load, if chosen with `apps=`
+    apps: Option<[bool; MAX_APP_NUM]>,
+    /// which apps are privileged, if chosen with `privileged=`
+    privileged: Option<[bool; MAX_APP_NUM]>,
+    /// whether to run the boot self-check
+    selftest: bool,
+    /// whether to run the microbenchmarks
+    bench: bool,
+    /// scheduling policy chosen with `sched=`
+    sched: Policy,
+}
+
+lazy_static! {
+    static ref BOOT_OPTIONS: UPSafeCell<BootOptions> = unsafe {
+        UPSafeCell::new(BootOptions {
+            init_app: None,
+            apps: None,
+            privileged: None,
+            selftest: false,
+            bench: false,
+            sched: Policy::RoundRobin,
+        })
+    };
+}
+
+/// Find the app named `name`.
+fn find_app(name: &str) -> Option<usize> {
+    (0..get_num_app()).find(|&i| get_app_name(i) == name)
+}
+
+/// Get the set of apps in the comma separated `names` of option `key`,
+/// reporting the ones that don't exist.
+fn parse_app_list(key: &str, names: &str) -> [bool; MAX_APP_NUM] {
+    let mut apps = [false; MAX_APP_NUM];
+    for name in names.split(',').filter(|name| !name.is_empty()) {
+        match find_app(name) {
+            Some(app_id) => apps[app_id] = true,
+            None => {
+                println!("[kernel] {}: no app named {}", key, name);
+            }
+        }
+    }
+    apps
+}
+
+/// Parse the command line in the device tree at `dtb_addr` and apply it.
+pub fn init(dtb_addr: usize) {
+    let bootargs = Fdt::from_addr(dtb_addr)
+        .and_then(|fdt| fdt.find_property("/chosen", "bootargs"))
+        .and_then(|value| core::str::from_utf8(value).ok())
+        .map(|s| s.trim_end_matches('\0'))
+        .filter(|s| !s.is_empty())
+        .or(option_env!("BOOTARGS"))
+        .unwrap_or("");
+    if !bootargs.is_empty() {
+        println!("[kernel] command line: {}", bootargs);
+    }
+    for option in bootargs.split_whitespace() {
+        let (key, value) = option.split_once('=').unwrap_or((option, ""));
+        let ok = match key {
+            "log" => logging::set_level(value),
+            "sched" => match Policy::from_name(value) {
+                Some(policy) => {
+                    BOOT_OPTIONS.exclusive_access().sched = policy;
+                    true
+                }
+                None => false,
+            },
+            "tick_hz" => value.parse().map_or(false, timer::set_tick_hz),
+            "end" => match value {
+                "shutdown" => sysctl::set("task.end_action", END_ACTION_SHUTDOWN),
+                "idle" => sysctl::set("task.end_action", END_ACTION_IDLE),
+                "panic" => sysctl::set("task.end_action", END_ACTION_PANIC),
+                _ => false,
+            },
+            "apps" => {
+                BOOT_OPTIONS.exclusive_access().apps = Some(parse_app_list(key, value));
+                true
+            }
+            "privileged" => {
+                BOOT_OPTIONS.exclusive_access().privileged = Some(parse_app_list(key, value));
+                true
+            }
+            "sched_record" if value.is_empty() => {
+                replay::start_recording();
+                true
+            }
+            "sched_replay" => replay::start_replay(value),
+            "selftest" if value.is_empty() => {
+                BOOT_OPTIONS.exclusive_access().selftest = true;
+                true
+            }
+            "bench" if value.is_empty() => {
+                BOOT_OPTIONS.exclusive_access().bench = true;
+                true
+            }
+            "trap" => match value {
+                "direct" => {
+                    trap::set_vectored(false);
+                    true
+                }
+                "vectored" => {
+                    trap::set_vectored(true);
+                    true
+                }
+                _ => false,
+            },
+            "init" => match find_app(value) {
+                Some(app_id) => {
+                    BOOT_OPTIONS.exclusive_access().init_app = Some(app_id);
+                    true
+                }
+                None => false,
+            },
+            _ => value.parse().map_or(false, |v| sysctl::set(key, v)),
+        };
+        if !ok {
+            println!("[kernel] ignoring boot option {}", option);
+        }
+    }
+    sysctl::for_each(|name, value| debug!("[kernel] {} = {}", name, value));
+}
+
+/// Get the scheduling policy chosen with `sched=`.
+pub fn sched_policy() -> Policy {
+    BOOT_OPTIONS.exclusive_access().sched
+}
+
+/// Whether the boot self-check was asked for.
+pub fn selftest() -> bool {
+    BOOT_OPTIONS.exclusive_access().selftest
+}
+
+/// Whether the microbenchmarks were asked for.
+pub fn bench() -> bool {
+    BOOT_OPTIONS.exclusive_access().bench
+}
+
+/// Get the id of the app chosen with `init=`, if any.
+pub fn init_app() -> Option<usize> {
+    BOOT_OPTIONS.exclusive_access().init_app
+}
+
+/// Whether app `app_id` may make the privileged syscalls, see
+/// `privileged=`.
+pub fn app_privileged(app_id: usize) -> bool {
+    let options = BOOT_OPTIONS.exclusive_access();
+    match options.privileged {
+        Some(privileged) => privileged[app_id],
+        None => app_id == options.init_app.unwrap_or(0),
+    }
+}
+
+/// Whether app `app_id` is to be loaded, see `apps=`.
+pub fn app_selected(app_id: usize) -> bool {
+    BOOT_OPTIONS
+        .exclusive_access()
+        .apps
+        .map_or(true, |apps| apps[app_id])
+}
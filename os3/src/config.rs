@@ -1,10 +1,23 @@
 //! Constants used in rCore
 
+pub const PAGE_SIZE: usize = 4096;
 pub const USER_STACK_SIZE: usize = 4096;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 20;
 pub const KERNEL_HEAP_SIZE: usize = 0x20000;
-pub const MAX_APP_NUM: usize = 16;
+pub const MAX_APP_NUM: usize = 32;
+pub const MAX_HARTS: usize = 8;
 pub const APP_BASE_ADDRESS: usize = 0x80400000;
 pub const APP_SIZE_LIMIT: usize = 0x20000;
 pub const CLOCK_FREQ: usize = 12500000;
 pub const MAX_SYSCALL_NUM: usize = 500;
+pub const RTC_BASE_ADDRESS: usize = 0x101000;
+pub const MEMORY_END: usize = 0x88000000;
+pub const VIRTIO_MMIO_BASE: usize = 0x10001000;
+pub const VIRTIO_MMIO_STRIDE: usize = 0x1000;
+pub const VIRTIO_MMIO_SLOTS: usize = 8;
+
+/// device register windows, as `(base, len)`
+pub const MMIO: &[(usize, usize)] = &[
+    (0x0010_1000, 0x00_1000), // goldfish RTC in virt machine
+    (0x1000_1000, 0x00_8000), // virtio-mmio slots in virt machine
+];
@@ -1,21 +1,317 @@
-//! SBI console driver, for text output
+//! SBI console driver
+//!
+//! Output is collected in a kernel-side buffer and handed to the SBI in one
+//! go on newline, when the buffer fills up, on task switch and on panic,
+//! instead of one SBI call per character. Use [`flush()`] to force it out.
+//! Where the SBI has the debug console extension, the whole buffer takes a
+//! single call; otherwise it goes out a character at a time.
+//!
+//! Until [`init()`], everything is written straight to the SBI instead, one
+//! character at a time, touching neither `lazy_static` nor the heap, so what
+//! the kernel prints while setting those up is not lost if it fails. Use
+//! [`early_println!`] where the early path is needed for sure.
+//!
+//! Input is taken from the SBI on every timer interrupt with
+//! [`poll_console()`], as well as when read, so that a paste doesn't
+//! overflow the UART while nobody reads. It is collected in one of two
+//! buffers while the reader drains the other, and bytes arriving with the
+//! buffer full are dropped and counted, see the `console.rx_dropped`
+//! tunable.
+//!
+//! It then goes through a line discipline: typed characters are echoed and
+//! can be edited with backspace and Ctrl-U, and only complete lines are
+//! handed to [`read_line()`]. Ctrl-D hands over the line as it is, or end of
+//! file on an empty line. Characters that don't fit in the line are dropped
+//! and counted as well.
 
-use crate::sbi::console_putchar;
+use crate::sbi::{console_getchar, console_putchar, console_write, has_debug_console};
+use crate::sync::UPSafeCell;
+use crate::trap::without_interrupts;
+use crate::{sysctl, teardown};
 use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use lazy_static::*;
+
+const CONSOLE_BUFFER_SIZE: usize = 256;
+
+/// bytes waiting to be sent to the SBI console
+struct ConsoleBuffer {
+    buf: [u8; CONSOLE_BUFFER_SIZE],
+    len: usize,
+}
+
+impl ConsoleBuffer {
+    fn push(&mut self, c: u8) {
+        self.buf[self.len] = c;
+        self.len += 1;
+        if c == b'\n' || self.len == CONSOLE_BUFFER_SIZE {
+            self.flush();
+        }
+    }
+    fn flush(&mut self) {
+        let mut rest = &self.buf[..self.len];
+        if *HAS_DEBUG_CONSOLE {
+            while let Ok(n @ 1..) = console_write(rest) {
+                rest = &rest[n.min(rest.len())..];
+                if rest.is_empty() {
+                    break;
+                }
+            }
+        }
+        for &c in rest {
+            console_putchar(c as usize);
+        }
+        self.len = 0;
+    }
+}
+
+lazy_static! {
+    /// whether the buffer can be written in one SBI call
+    static ref HAS_DEBUG_CONSOLE: bool = has_debug_console();
+    static ref CONSOLE_BUFFER: UPSafeCell<ConsoleBuffer> = unsafe {
+        UPSafeCell::new(ConsoleBuffer {
+            buf: [0; CONSOLE_BUFFER_SIZE],
+            len: 0,
+        })
+    };
+}
+
+const RX_BUFFER_SIZE: usize = 1024;
+
+/// input taken from the SBI but not yet through the line discipline
+struct RxBuffer {
+    buf: [u8; RX_BUFFER_SIZE],
+    len: usize,
+}
+
+lazy_static! {
+    /// the buffer `RX_FILLING` is collected in, and the one drained
+    static ref RX_BUFFERS: [UPSafeCell<RxBuffer>; 2] = unsafe {
+        [(); 2].map(|_| {
+            UPSafeCell::new(RxBuffer {
+                buf: [0; RX_BUFFER_SIZE],
+                len: 0,
+            })
+        })
+    };
+}
+
+/// index of the RX buffer input is collected in
+static RX_FILLING: AtomicUsize = AtomicUsize::new(0);
+/// bytes taken from the SBI
+static RX_BYTES: AtomicUsize = AtomicUsize::new(0);
+/// bytes dropped for lack of room
+static RX_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Take what was typed from the SBI into the RX buffer being filled.
+///
+/// Called with interrupts masked, from the timer interrupt and before
+/// reading.
+pub fn poll_console() {
+    if EARLY.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut rx = RX_BUFFERS[RX_FILLING.load(Ordering::Relaxed)].exclusive_access();
+    loop {
+        // the legacy SBI call returns -1 if nothing was typed; a 0 is a
+        // NUL byte typed with Ctrl-@
+        let c = match console_getchar() {
+            usize::MAX => break,
+            c => c as u8,
+        };
+        RX_BYTES.fetch_add(1, Ordering::Relaxed);
+        if rx.len == RX_BUFFER_SIZE {
+            RX_DROPPED.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        let len = rx.len;
+        rx.buf[len] = c;
+        rx.len += 1;
+    }
+}
+
+/// most bytes of input [`read_line()`] can hand over at once
+pub const LINE_BUFFER_SIZE: usize = 256;
+
+/// console input, `buf[..ready]` being complete and `buf[ready..len]` the
+/// line still being edited
+struct LineBuffer {
+    buf: [u8; LINE_BUFFER_SIZE],
+    len: usize,
+    ready: usize,
+    /// Ctrl-D on an empty line, to be read as end of file
+    eof: bool,
+}
+
+impl LineBuffer {
+    /// Handle typed character `c`, echoing it.
+    fn input(&mut self, c: u8) {
+        match c {
+            b'\r' | b'\n' if self.len < LINE_BUFFER_SIZE => {
+                self.buf[self.len] = b'\n';
+                self.len += 1;
+                self.ready = self.len;
+                echo("\n");
+            }
+            // Ctrl-D
+            0x04 => {
+                if self.len == self.ready {
+                    self.eof = true;
+                }
+                self.ready = self.len;
+            }
+            // backspace, DEL
+            0x08 | 0x7f => self.erase(1),
+            // Ctrl-U
+            0x15 => self.erase(self.len - self.ready),
+            // one byte is kept for the newline
+            b' '..=b'~' if self.len + 1 < LINE_BUFFER_SIZE => {
+                self.buf[self.len] = c;
+                self.len += 1;
+                Stdout.write_char(c as char).unwrap();
+            }
+            // no room left in the line
+            b'\r' | b'\n' | b' '..=b'~' => {
+                RX_DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+    /// Erase up to `n` characters of the line being edited.
+    fn erase(&mut self, n: usize) {
+        for _ in 0..n.min(self.len - self.ready) {
+            self.len -= 1;
+            echo("\x08 \x08");
+        }
+    }
+    /// Move complete input to `buf`, returning how many bytes were moved.
+    fn take(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.ready.min(buf.len());
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.copy_within(n..self.len, 0);
+        self.len -= n;
+        self.ready -= n;
+        n
+    }
+}
+
+lazy_static! {
+    static ref LINE_BUFFER: UPSafeCell<LineBuffer> = unsafe {
+        UPSafeCell::new(LineBuffer {
+            buf: [0; LINE_BUFFER_SIZE],
+            len: 0,
+            ready: 0,
+            eof: false,
+        })
+    };
+}
+
+/// Read complete console input into `buf`, handling whatever was typed so
+/// far. Returns the number of bytes read, 0 at end of file, or `None` if no
+/// line is complete yet.
+pub fn read_line(buf: &mut [u8]) -> Option<usize> {
+    let mut line = LINE_BUFFER.exclusive_access();
+    poll_input(&mut line);
+    if line.ready > 0 {
+        Some(line.take(buf))
+    } else if line.eof {
+        line.eof = false;
+        Some(0)
+    } else {
+        None
+    }
+}
+
+/// Whether [`read_line()`] would return now, handling whatever was typed
+/// so far.
+pub fn input_ready() -> bool {
+    let mut line = LINE_BUFFER.exclusive_access();
+    poll_input(&mut line);
+    line.ready > 0 || line.eof
+}
+
+/// Feed what was typed so far into `line`.
+fn poll_input(line: &mut LineBuffer) {
+    // switched with interrupts masked, so that the timer interrupt fills
+    // the other buffer from then on and this one is left to drain
+    let drained = without_interrupts(|| {
+        poll_console();
+        RX_FILLING.fetch_xor(1, Ordering::Relaxed)
+    });
+    let mut rx = RX_BUFFERS[drained].exclusive_access();
+    for &c in &rx.buf[..rx.len] {
+        line.input(c);
+    }
+    rx.len = 0;
+    flush();
+}
+
+/// whether [`init()`] has not run yet
+static EARLY: AtomicBool = AtomicBool::new(true);
+
+/// the SBI console without any state of the kernel's, see [`early_print`]
+struct EarlyConsole;
+
+impl Write for EarlyConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|c| console_putchar(c as usize));
+        Ok(())
+    }
+}
+
+/// print straight to the SBI console
+pub fn early_print(args: fmt::Arguments) {
+    EarlyConsole.write_fmt(args).unwrap();
+}
+
+/// Switch from the early console to the buffered one, once the heap is up.
+pub fn init() {
+    EARLY.store(false, Ordering::Relaxed);
+    teardown::register("console", |_| flush());
+    sysctl::register(
+        "console.rx_bytes",
+        || RX_BYTES.load(Ordering::Relaxed),
+        |v| sysctl::reset_counter(&RX_BYTES, v),
+    );
+    sysctl::register(
+        "console.rx_dropped",
+        || RX_DROPPED.load(Ordering::Relaxed),
+        |v| sysctl::reset_counter(&RX_DROPPED, v),
+    );
+}
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        match CONSOLE_BUFFER.try_exclusive_access() {
+            Some(mut buffer) => s.bytes().for_each(|c| buffer.push(c)),
+            // printing while printing, e.g. panicking in the middle of a
+            // print: bypass the buffer rather than panic again
+            None => s.bytes().for_each(|c| console_putchar(c as usize)),
         }
         Ok(())
     }
 }
 
+/// echo console input back
+fn echo(s: &str) {
+    Stdout.write_str(s).unwrap();
+}
+
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    if EARLY.load(Ordering::Relaxed) {
+        early_print(args);
+    } else {
+        Stdout.write_fmt(args).unwrap();
+    }
+}
+
+/// send all buffered output to the console now
+pub fn flush() {
+    if let Some(mut buffer) = CONSOLE_BUFFER.try_exclusive_access() {
+        buffer.flush();
+    }
 }
 
 #[macro_export]
@@ -26,6 +322,14 @@ macro_rules! print {
     }
 }
 
+#[macro_export]
+/// println string macro, printing straight to the SBI console
+macro_rules! early_println {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::early_print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
+    }
+}
+
 #[macro_export]
 /// println string macro
 macro_rules! println {
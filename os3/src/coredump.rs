@@ -0,0 +1,133 @@
+//! Core dumps of tasks killed by a fault
+//!
+//! With the `coredump.enable` tunable set, a task killed by a fault has its
+//! registers and user memory written to the console, base64 encoded between
+//! marker lines, as there is no block device to write a file to. The dump
+//! is a sequence of little-endian `u64`s:
+//!
+//! - `CORE_MAGIC`, task id, `scause`, `stval`, `sepc`, `sstatus`, `x0`..`x31`
+//! - for each region, its start address and length, then its bytes
+//!
+//! The regions are the load region of the app and its user stack.
+
+use crate::loader::{get_app_range, get_user_stack_range};
+use crate::sysctl;
+use crate::trap::TrapContext;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// `b"OS3CORE\0"`, little endian
+const CORE_MAGIC: u64 = u64::from_le_bytes(*b"OS3CORE\0");
+/// base64 characters per output line
+const LINE_LEN: usize = 76;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// whether to dump tasks killed by a fault
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Register the `coredump.enable` tunable.
+pub fn init() {
+    sysctl::register(
+        "coredump.enable",
+        || ENABLED.load(Ordering::Relaxed) as usize,
+        |on| {
+            if on > 1 {
+                return false;
+            }
+            ENABLED.store(on == 1, Ordering::Relaxed);
+            true
+        },
+    );
+}
+
+/// base64 encoder printing full lines as it goes
+struct Base64Writer {
+    /// bytes not encoded yet, `len` of them
+    pending: [u8; 3],
+    len: usize,
+    line: [u8; LINE_LEN],
+    line_len: usize,
+}
+
+impl Base64Writer {
+    fn new() -> Self {
+        Self {
+            pending: [0; 3],
+            len: 0,
+            line: [0; LINE_LEN],
+            line_len: 0,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.pending[self.len] = b;
+            self.len += 1;
+            if self.len == 3 {
+                self.encode();
+            }
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Encode the pending bytes, padding them if there are less than 3.
+    fn encode(&mut self) {
+        let [a, b, c] = self.pending;
+        let n = (a as usize) << 16 | (b as usize) << 8 | c as usize;
+        for i in 0..4 {
+            self.line[self.line_len] = if i <= self.len {
+                BASE64[(n >> (18 - 6 * i)) & 0x3f]
+            } else {
+                b'='
+            };
+            self.line_len += 1;
+        }
+        self.pending = [0; 3];
+        self.len = 0;
+        if self.line_len == LINE_LEN {
+            self.flush_line();
+        }
+    }
+
+    fn flush_line(&mut self) {
+        if self.line_len > 0 {
+            // only base64 characters in there
+            let line = core::str::from_utf8(&self.line[..self.line_len]).unwrap();
+            println!("{}", line);
+            self.line_len = 0;
+        }
+    }
+
+    fn finish(mut self) {
+        if self.len > 0 {
+            self.encode();
+        }
+        self.flush_line();
+    }
+}
+
+/// Dump task `task_id`, killed by a fault in context `cx`, if enabled.
+pub fn dump(task_id: usize, cx: &TrapContext, scause: usize, stval: usize) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    println!("[kernel] core dump of task {} begin", task_id);
+    let mut w = Base64Writer::new();
+    for value in [CORE_MAGIC, task_id as u64, scause as u64, stval as u64] {
+        w.write_u64(value);
+    }
+    w.write_u64(cx.sepc as u64);
+    w.write_u64(cx.sstatus.bits() as u64);
+    for &x in cx.x.iter() {
+        w.write_u64(x as u64);
+    }
+    for (start, end) in [get_app_range(task_id), get_user_stack_range(task_id)] {
+        w.write_u64(start as u64);
+        w.write_u64((end - start) as u64);
+        w.write(unsafe { core::slice::from_raw_parts(start as *const u8, end - start) });
+    }
+    w.finish();
+    println!("[kernel] core dump of task {} end", task_id);
+}
@@ -0,0 +1,154 @@
+//! Minimal flattened device tree reader
+//!
+//! Only what the kernel needs at boot: looking up a property by node path in
+//! the blob the SBI passes in `a1`, possibly for all children of a node.
+//! Nodes are not validated beyond the header.
+
+use alloc::vec::Vec;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// a device tree blob in memory
+pub struct Fdt {
+    base: usize,
+    size: usize,
+    struct_off: usize,
+    strings_off: usize,
+}
+
+/// read a big-endian `u32` at `addr`
+fn read_be32(addr: usize) -> u32 {
+    u32::from_be(unsafe { (addr as *const u32).read_volatile() })
+}
+
+/// read the NUL-terminated string at `addr`
+fn read_str(addr: usize) -> &'static str {
+    unsafe {
+        let mut end = addr as *const u8;
+        while end.read_volatile() != 0 {
+            end = end.add(1);
+        }
+        let bytes = core::slice::from_raw_parts(addr as *const u8, end as usize - addr);
+        core::str::from_utf8(bytes).unwrap_or("")
+    }
+}
+
+/// round `x` up to the 4-byte token alignment
+fn align4(x: usize) -> usize {
+    (x + 3) & !3
+}
+
+/// Whether node `name` matches the path component `comp`; the unit address
+/// may be left out of `comp`.
+fn node_matches(name: &str, comp: &str) -> bool {
+    name == comp || (!comp.contains('@') && name.split('@').next() == Some(comp))
+}
+
+impl Fdt {
+    /// Get the device tree at `addr`, if there is a valid header there.
+    pub fn from_addr(addr: usize) -> Option<Self> {
+        if addr == 0 || addr % 4 != 0 || read_be32(addr) != FDT_MAGIC {
+            return None;
+        }
+        Some(Self {
+            base: addr,
+            size: read_be32(addr + 4) as usize,
+            struct_off: read_be32(addr + 8) as usize,
+            strings_off: read_be32(addr + 12) as usize,
+        })
+    }
+
+    /// Get the address range `[start, end)` of the blob.
+    pub fn range(&self) -> (usize, usize) {
+        (self.base, self.base + self.size)
+    }
+
+    /// Get the value of property `prop` of the node at `path`, e.g.
+    /// `find_property("/chosen", "bootargs")`.
+    pub fn find_property(&self, path: &str, prop: &str) -> Option<&'static [u8]> {
+        let mut value = None;
+        self.walk(path, false, |prop_name, v| {
+            if prop_name == prop {
+                value = Some(v);
+                false
+            } else {
+                true
+            }
+        });
+        value
+    }
+
+    /// Get the values of property `prop` of all children of the node at
+    /// `path` that have it, e.g. `child_properties("/reserved-memory", "reg")`.
+    pub fn child_properties(&self, path: &str, prop: &str) -> Vec<&'static [u8]> {
+        let mut values = Vec::new();
+        self.walk(path, true, |prop_name, v| {
+            if prop_name == prop {
+                values.push(v);
+            }
+            true
+        });
+        values
+    }
+
+    /// Call `f` with the name and value of each property of the node at
+    /// `path`, or of its children if `children`, until it returns `false`.
+    fn walk(&self, path: &str, children: bool, mut f: impl FnMut(&str, &'static [u8]) -> bool) {
+        let comps = path.split('/').filter(|c| !c.is_empty());
+        let target_depth = comps.clone().count() + 1;
+        // `depth` counts the root node as 1, `matched` is how deep the
+        // current node path still agrees with `path`
+        let (mut depth, mut matched) = (0, 0);
+        let mut pos = self.base + self.struct_off;
+        loop {
+            let token = read_be32(pos);
+            pos += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = read_str(pos);
+                    pos = align4(pos + name.len() + 1);
+                    depth += 1;
+                    if matched == depth - 1 {
+                        if depth == 1 {
+                            matched = 1;
+                        } else if let Some(comp) = comps.clone().nth(depth - 2) {
+                            if node_matches(name, comp) {
+                                matched = depth;
+                            }
+                        }
+                    }
+                }
+                FDT_END_NODE => {
+                    if matched == depth {
+                        if matched == target_depth {
+                            return;
+                        }
+                        matched -= 1;
+                    }
+                    depth -= 1;
+                }
+                FDT_PROP => {
+                    let len = read_be32(pos) as usize;
+                    let name_off = read_be32(pos + 4) as usize;
+                    let value = pos + 8;
+                    pos = align4(value + len);
+                    let prop_depth = target_depth + children as usize;
+                    if matched == target_depth && depth == prop_depth {
+                        let name = read_str(self.base + self.strings_off + name_off);
+                        let value = unsafe { core::slice::from_raw_parts(value as *const u8, len) };
+                        if !f(name, value) {
+                            return;
+                        }
+                    }
+                }
+                FDT_NOP => {}
+                // FDT_END, or a corrupted blob
+                _ => return,
+            }
+        }
+    }
+}
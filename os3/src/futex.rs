@@ -0,0 +1,81 @@
+//! Futexes, for user-level locks that block instead of spinning
+//!
+//! A futex is any aligned `u32` of user memory. With one address space, the
+//! physical address is the key, so tasks sharing memory through it (there
+//! is no other way in ch3) share the futex.
+
+use crate::sync::{UPSafeCell, WaitQueue};
+use crate::task::{block_current_and_run_next, get_current_task, signal_pending};
+use alloc::collections::BTreeMap;
+use lazy_static::*;
+
+lazy_static! {
+    /// waiters by futex address, removed once nobody waits
+    static ref FUTEXES: UPSafeCell<BTreeMap<usize, WaitQueue>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Why waiting on a futex ended other than by [`wake`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum FutexError {
+    /// the futex didn't hold the value waited on
+    Again,
+    /// a signal came, before or while waiting
+    Interrupted,
+}
+
+/// Block the current task on the futex at `addr` if it still holds `val`,
+/// until woken by [`wake`] or a signal.
+pub fn wait(addr: usize, val: u32) -> Result<(), FutexError> {
+    let current = get_current_task();
+    enqueue(addr, val, current)?;
+    block_current_and_run_next();
+    dequeue(addr, current)
+}
+
+/// Queue task `task_id` on the futex at `addr`, unless it doesn't hold
+/// `val` or the task has a signal pending.
+fn enqueue(addr: usize, val: u32, task_id: usize) -> Result<(), FutexError> {
+    let mut futexes = FUTEXES.exclusive_access();
+    // checked with the futexes held, so no wake can slip in between
+    if unsafe { (addr as *const u32).read_volatile() } != val {
+        return Err(FutexError::Again);
+    }
+    if signal_pending(task_id) {
+        return Err(FutexError::Interrupted);
+    }
+    futexes.entry(addr).or_default().push(task_id);
+    Ok(())
+}
+
+/// Take task `task_id`, woken up, out of the queue of the futex at `addr`
+/// if it is still in, as it was a signal rather than [`wake`] that woke it.
+fn dequeue(addr: usize, task_id: usize) -> Result<(), FutexError> {
+    let mut futexes = FUTEXES.exclusive_access();
+    let queue = match futexes.get_mut(&addr) {
+        Some(queue) => queue,
+        None => return Ok(()),
+    };
+    if !queue.remove(task_id) {
+        return Ok(());
+    }
+    if queue.is_empty() {
+        futexes.remove(&addr);
+    }
+    Err(FutexError::Interrupted)
+}
+
+/// Wake up to `n` tasks waiting on the futex at `addr`, returning how many
+/// were woken.
+pub fn wake(addr: usize, n: usize) -> usize {
+    let mut futexes = FUTEXES.exclusive_access();
+    let queue = match futexes.get_mut(&addr) {
+        Some(queue) => queue,
+        None => return 0,
+    };
+    let woken = (0..n).take_while(|_| queue.wake_one()).count();
+    if queue.is_empty() {
+        futexes.remove(&addr);
+    }
+    woken
+}
@@ -0,0 +1,24 @@
+//! Hart identification
+//!
+//! Only the boot hart runs the kernel for now, but task affinity masks are
+//! already checked against the harts that are online.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// id of the hart that booted the kernel
+static BOOT_HART: AtomicUsize = AtomicUsize::new(0);
+
+/// record the id of the boot hart
+pub fn init(hart_id: usize) {
+    BOOT_HART.store(hart_id, Ordering::Relaxed);
+}
+
+/// get the id of the hart we are running on
+pub fn hart_id() -> usize {
+    BOOT_HART.load(Ordering::Relaxed)
+}
+
+/// get the set of harts running the kernel, bit `n` standing for hart `n`
+pub fn online_harts() -> usize {
+    1 << hart_id()
+}
@@ -1,24 +1,233 @@
 //! The global allocator
+//!
+//! The heap starts out as a static array in `.bss`. Spare memory can be
+//! donated to it later with [`add_region`].
+//!
+//! Small allocations are served from an arena per size class, each with a
+//! lock of its own, which takes memory from the shared heap a chunk at a
+//! time and keeps it. Everything else goes to the shared heap directly.
+//!
+//! Debug builds also track the live allocations by call site and fence
+//! them in with redzones, panicking on overruns and double frees, see
+//! [`crate::heap_sites`].
 
-use crate::config::KERNEL_HEAP_SIZE;
+use crate::config::{KERNEL_HEAP_SIZE, MEMORY_END};
+use crate::fdt::Fdt;
+use crate::memmap::reserved_ranges;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::convert::TryInto;
+use lazy_static::*;
+
+/// most regions that can be added to the heap
+const MAX_HEAP_REGIONS: usize = 4;
+
+/// largest size and alignment of each arena, in increasing order
+const SIZE_CLASSES: [usize; 2] = [64, 512];
+/// bytes an arena takes from the shared heap at once
+const ARENA_CHUNK: usize = 4096;
+
+/// the shared heap and the arenas in front of it
+struct KernelHeap {
+    arenas: [LockedHeap; SIZE_CLASSES.len()],
+    shared: LockedHeap,
+}
+
+impl KernelHeap {
+    /// Get the arena serving `layout`, if it is small enough for one.
+    fn arena(&self, layout: &Layout) -> Option<&LockedHeap> {
+        let size = layout.size().max(layout.align());
+        SIZE_CLASSES
+            .iter()
+            .position(|&class| size <= class)
+            .map(|i| &self.arenas[i])
+    }
+
+    /// Get the bytes free in the arenas.
+    fn arena_free(&self) -> usize {
+        self.arenas
+            .iter()
+            .map(|arena| {
+                let arena = arena.lock();
+                arena.stats_total_bytes() - arena.stats_alloc_actual()
+            })
+            .sum()
+    }
+}
+
+impl KernelHeap {
+    unsafe fn alloc_untracked(&self, layout: Layout) -> *mut u8 {
+        let arena = match self.arena(&layout) {
+            Some(arena) => arena,
+            None => return self.shared.alloc(layout),
+        };
+        loop {
+            let ptr = arena.alloc(layout);
+            if !ptr.is_null() {
+                return ptr;
+            }
+            let chunk = Layout::from_size_align_unchecked(ARENA_CHUNK, ARENA_CHUNK);
+            let start = self.shared.alloc(chunk) as usize;
+            if start == 0 {
+                return core::ptr::null_mut();
+            }
+            arena.lock().add_to_heap(start, start + ARENA_CHUNK);
+        }
+    }
+
+    unsafe fn dealloc_untracked(&self, ptr: *mut u8, layout: Layout) {
+        match self.arena(&layout) {
+            Some(arena) => arena.dealloc(ptr, layout),
+            None => self.shared.dealloc(ptr, layout),
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    #[cfg(not(debug_assertions))]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.alloc_untracked(layout)
+    }
+
+    #[cfg(debug_assertions)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        crate::heap_sites::alloc(layout, |layout| self.alloc_untracked(layout))
+    }
+
+    #[cfg(not(debug_assertions))]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.dealloc_untracked(ptr, layout)
+    }
+
+    #[cfg(debug_assertions)]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        crate::heap_sites::dealloc(ptr, layout, |ptr, layout| {
+            self.dealloc_untracked(ptr, layout)
+        })
+    }
+}
 
 #[global_allocator]
 /// heap allocator instance
-static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
+static HEAP_ALLOCATOR: KernelHeap = KernelHeap {
+    arenas: [LockedHeap::empty(), LockedHeap::empty()],
+    shared: LockedHeap::empty(),
+};
 
 /// heap space ([u8; KERNEL_HEAP_SIZE])
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
+lazy_static! {
+    /// regions added with [`add_region`]
+    static ref HEAP_REGIONS: UPSafeCell<[Option<(usize, usize)>; MAX_HEAP_REGIONS]> =
+        unsafe { UPSafeCell::new([None; MAX_HEAP_REGIONS]) };
+}
+
 /// initiate heap allocator
 pub fn init_heap() {
+    let (start, end) = heap_range();
+    early_println!("[kernel] heap: [{:#x}, {:#x})", start, end);
     unsafe {
-        HEAP_ALLOCATOR
-            .lock()
-            .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
+        HEAP_ALLOCATOR.shared.lock().init(start, KERNEL_HEAP_SIZE);
+    }
+}
+
+/// Give the memory `[start, end)` to the heap for good. Fails if too many
+/// regions have been added already.
+///
+/// The memory must not be used by anything else.
+pub fn add_region(start: usize, end: usize) -> bool {
+    let mut regions = HEAP_REGIONS.exclusive_access();
+    match regions.iter_mut().find(|r| r.is_none()) {
+        Some(slot) if start < end => {
+            unsafe {
+                HEAP_ALLOCATOR.shared.lock().add_to_heap(start, end);
+            }
+            *slot = Some((start, end));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Add the memory the device tree at `dtb_addr` reports past `MEMORY_END`
+/// to the heap, except for the blob itself and the reserved regions.
+///
+/// The kernel assumes nothing beyond `MEMORY_END`, so a board with more
+/// memory than that would leave it unused otherwise.
+pub fn add_spare_memory(dtb_addr: usize) {
+    let fdt = match Fdt::from_addr(dtb_addr) {
+        Some(fdt) => fdt,
+        None => return,
+    };
+    let reg = match fdt.find_property("/memory", "reg") {
+        Some(reg) => reg,
+        None => return,
+    };
+    // keep clear of the reserved regions and the blob itself, which may well
+    // be up there
+    let mut holes = reserved_ranges();
+    holes.push(fdt.range());
+    holes.sort_unstable();
+    // `(base, size)` pairs of two cells each, as on qemu virt
+    let read_be64 = |bytes: &[u8]| u64::from_be_bytes(bytes.try_into().unwrap()) as usize;
+    for pair in reg.chunks_exact(16) {
+        let (base, size) = (read_be64(&pair[..8]), read_be64(&pair[8..]));
+        let (start, end) = (base.max(MEMORY_END), base + size);
+        for (start, end) in without_holes(start, end, &holes) {
+            if add_region(start, end) {
+                println!("[kernel] heap: added [{:#x}, {:#x})", start, end);
+            } else {
+                println!("[kernel] heap: no room for [{:#x}, {:#x})", start, end);
+            }
+        }
     }
 }
 
+/// Split `[start, end)` into the parts outside of `holes`, which are sorted
+/// by start.
+fn without_holes(mut start: usize, end: usize, holes: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut parts = Vec::new();
+    for &(hole_start, hole_end) in holes {
+        if hole_start.min(end) > start {
+            parts.push((start, hole_start.min(end)));
+        }
+        start = start.max(hole_end);
+    }
+    if start < end {
+        parts.push((start, end));
+    }
+    parts
+}
+
+/// get the address range `[start, end)` of the static kernel heap
+pub fn heap_range() -> (usize, usize) {
+    let start = unsafe { HEAP_SPACE.as_ptr() as usize };
+    (start, start + KERNEL_HEAP_SIZE)
+}
+
+/// get the address ranges `[start, end)` of the kernel heap, the static one
+/// first
+pub fn heap_regions() -> Vec<(usize, usize)> {
+    let regions = HEAP_REGIONS.exclusive_access();
+    core::iter::once(heap_range())
+        .chain(regions.iter().flatten().copied())
+        .collect()
+}
+
+/// get the number of bytes `(total, in use)` in the kernel heap, the
+/// memory left in the arenas counting as free
+pub fn heap_usage() -> (usize, usize) {
+    let arena_free = HEAP_ALLOCATOR.arena_free();
+    let heap = HEAP_ALLOCATOR.shared.lock();
+    (
+        heap.stats_total_bytes(),
+        heap.stats_alloc_actual() - arena_free,
+    )
+}
+
 #[alloc_error_handler]
 /// panic when heap allocation error occurs
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
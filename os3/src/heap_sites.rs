@@ -0,0 +1,218 @@
+//! Heap allocations by call site, in debug builds
+//!
+//! Every allocation is tagged with the return addresses of the
+//! [`SITE_FRAMES`] frames above `alloc::alloc::alloc`, found by walking the
+//! frame pointers, and each distinct site counts the blocks and bytes it has
+//! live. [`dump`] prints the sites holding the most, for `addr2line -f -e os`
+//! to turn into function names; the kernel code asking is in there, past
+//! whatever `Box` or `Vec` layers there are.
+//!
+//! The site of a block is kept in a word below it, so that freeing it can be
+//! charged back to the site. Each block is also fenced in to catch what
+//! would otherwise corrupt the heap unnoticed: the word right below it holds
+//! a magic number, changed to another when it is freed, and a redzone of
+//! [`REDZONE_SIZE`] bytes of a pattern follows it. Freeing a block whose
+//! magic or redzone was overwritten, or freeing it twice, panics with its
+//! size and site.
+
+use crate::config::MEMORY_END;
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use riscv::register::sstatus;
+
+/// return addresses identifying a site
+pub const SITE_FRAMES: usize = 4;
+/// return addresses into the allocator to skip: into the tracking itself,
+/// `GlobalAlloc::alloc`, the `__rg_alloc` shim and `alloc::alloc::alloc`
+const SKIP_FRAMES: usize = 4;
+/// sites that can be told apart, the rest are counted together
+const MAX_SITES: usize = 128;
+/// sites [`dump`] prints
+const TOP_SITES: usize = 10;
+/// index of the site counting the allocations [`SITES`] had no room for,
+/// printed with no frames
+const OTHER: usize = MAX_SITES;
+
+/// bytes of pattern after every block
+pub const REDZONE_SIZE: usize = 16;
+/// what the redzone is filled with
+const REDZONE_BYTE: u8 = 0xfd;
+/// magic number below a live block
+const MAGIC_LIVE: usize = 0xa110_c8ed_b10c_4a11;
+/// magic number below a freed block
+const MAGIC_FREED: usize = 0xdead_f4ee_b10c_4a11;
+
+#[derive(Copy, Clone)]
+struct Site {
+    frames: [usize; SITE_FRAMES],
+    blocks: usize,
+    bytes: usize,
+}
+
+struct Sites(UnsafeCell<[Option<Site>; MAX_SITES + 1]>);
+
+unsafe impl Sync for Sites {}
+
+static SITES: Sites = Sites(UnsafeCell::new([None; MAX_SITES + 1]));
+
+/// Run `f` on the site table with interrupts disabled, so that an
+/// allocation in an interrupt handler can't get in between.
+fn with_sites<T>(f: impl FnOnce(&mut [Option<Site>; MAX_SITES + 1]) -> T) -> T {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    let ret = f(unsafe { &mut *SITES.0.get() });
+    if enabled {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+    ret
+}
+
+/// Get the return addresses of the frames above `alloc::alloc::alloc`,
+/// 0 past the last one that could be found.
+#[inline(never)]
+fn return_addresses() -> [usize; SITE_FRAMES] {
+    extern "C" {
+        fn skernel();
+    }
+    let mut frames = [0; SITE_FRAMES];
+    let mut fp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, s0", out(reg) fp);
+    }
+    for i in 0..SKIP_FRAMES + SITE_FRAMES {
+        // stop at anything that is not a kernel stack frame further up
+        if fp % 8 != 0 || fp < skernel as usize + 16 || fp > MEMORY_END {
+            break;
+        }
+        let (ra, next) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if i >= SKIP_FRAMES {
+            frames[i - SKIP_FRAMES] = ra;
+        }
+        if next <= fp {
+            break;
+        }
+        fp = next;
+    }
+    frames
+}
+
+/// Get the room kept in front of a block of `layout` for its site and
+/// magic number, and the layout taking them and the redzone in.
+fn padded(layout: Layout) -> (usize, Layout) {
+    let pad = layout.align().max(2 * core::mem::size_of::<usize>());
+    (pad, unsafe {
+        Layout::from_size_align_unchecked(layout.size() + pad + REDZONE_SIZE, pad)
+    })
+}
+
+/// Allocate a block of `layout` with `alloc`, charging it to the site it is
+/// allocated from.
+pub unsafe fn alloc(layout: Layout, alloc: impl FnOnce(Layout) -> *mut u8) -> *mut u8 {
+    let frames = return_addresses();
+    let (pad, padded) = padded(layout);
+    let base = alloc(padded);
+    if base.is_null() {
+        return base;
+    }
+    let site = with_sites(|sites| {
+        let index = sites[..MAX_SITES]
+            .iter()
+            .position(|s| s.map_or(true, |s| s.frames == frames))
+            .unwrap_or(OTHER);
+        let site = sites[index].get_or_insert(Site {
+            frames: if index == OTHER {
+                [0; SITE_FRAMES]
+            } else {
+                frames
+            },
+            blocks: 0,
+            bytes: 0,
+        });
+        site.blocks += 1;
+        site.bytes += layout.size();
+        index
+    });
+    let ptr = base.add(pad);
+    (ptr as *mut usize).sub(2).write(site);
+    (ptr as *mut usize).sub(1).write(MAGIC_LIVE);
+    core::ptr::write_bytes(ptr.add(layout.size()), REDZONE_BYTE, REDZONE_SIZE);
+    ptr
+}
+
+/// Get the frames of site `site`, or none if that is no site, as when the
+/// word holding it was overwritten.
+fn site_frames(site: usize) -> [usize; SITE_FRAMES] {
+    with_sites(|sites| {
+        sites
+            .get(site)
+            .copied()
+            .flatten()
+            .map_or([0; SITE_FRAMES], |s| s.frames)
+    })
+}
+
+/// Free a block of `layout` allocated by [`alloc`] with `dealloc`, panicking
+/// if it was freed before or written out of bounds.
+pub unsafe fn dealloc(ptr: *mut u8, layout: Layout, dealloc: impl FnOnce(*mut u8, Layout)) {
+    let site = (ptr as *const usize).sub(2).read();
+    let magic = (ptr as *mut usize).sub(1);
+    match magic.read() {
+        MAGIC_LIVE => {}
+        MAGIC_FREED => panic!(
+            "heap: double free of {} bytes at {:p} from {:#x?}",
+            layout.size(),
+            ptr,
+            site_frames(site)
+        ),
+        _ => panic!(
+            "heap: underrun of {} bytes at {:p}, from {:#x?}",
+            layout.size(),
+            ptr,
+            site_frames(site)
+        ),
+    }
+    let redzone = core::slice::from_raw_parts(ptr.add(layout.size()), REDZONE_SIZE);
+    if let Some(offset) = redzone.iter().position(|&b| b != REDZONE_BYTE) {
+        panic!(
+            "heap: overrun of {} bytes at {:p} by {} bytes at least, from {:#x?}",
+            layout.size(),
+            ptr,
+            offset + 1,
+            site_frames(site)
+        );
+    }
+    magic.write(MAGIC_FREED);
+    with_sites(|sites| {
+        if let Some(site) = sites[site].as_mut() {
+            site.blocks -= 1;
+            site.bytes -= layout.size();
+        }
+    });
+    let (pad, padded) = padded(layout);
+    dealloc(ptr.sub(pad), padded);
+}
+
+/// Print the sites with the most bytes live, returning the number of sites
+/// with any.
+pub fn dump() -> usize {
+    let mut sites = with_sites(|sites| *sites);
+    let live = sites.iter().flatten().filter(|s| s.blocks > 0).count();
+    sites.sort_unstable_by_key(|s| core::cmp::Reverse(s.map_or(0, |s| s.bytes)));
+    println!("[kernel] heap sites: {} with blocks live", live);
+    let top = sites
+        .iter()
+        .flatten()
+        .filter(|s| s.blocks > 0)
+        .take(TOP_SITES);
+    for site in top {
+        println!(
+            "[kernel]   {} bytes in {} blocks from {:#x?}",
+            site.bytes, site.blocks, site.frames
+        );
+    }
+    live
+}
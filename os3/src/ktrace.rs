@@ -0,0 +1,147 @@
+//! Kernel event trace
+//!
+//! Trace points across the kernel [`record`] an [`Event`] and an argument,
+//! with a timestamp, into a ring of the last [`TRACE_LEN`] records of the
+//! hart they run on. Recording takes no lock, so it is safe in the trap
+//! paths; it can be turned off with the `trace.enable` tunable.
+//!
+//! `sys_trace_dump` prints the rings out, one record per line as
+//!
+//! ```text
+//! trace <hart> <time in us> <event> <arg>
+//! ```
+//!
+//! oldest first, for a host-side script to turn into a timeline. They are
+//! also dumped when the kernel shuts down on a failure, see
+//! [`crate::teardown`].
+
+use crate::config::MAX_HARTS;
+use crate::hart::hart_id;
+use crate::sysctl;
+use crate::teardown;
+use crate::timer::get_time_us;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// records kept per hart
+pub const TRACE_LEN: usize = 256;
+
+/// What a trace point saw
+#[repr(usize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// switched to the task in the argument
+    Switch = 1,
+    /// entered the kernel from user space with the `scause` in the argument
+    TrapEnter,
+    /// returning to the task in the argument
+    TrapExit,
+    /// dispatching the syscall in the argument
+    Syscall,
+    /// allocated a kernel stack, with its bottom in the argument
+    StackAlloc,
+    /// freed a kernel stack, with its bottom in the argument
+    StackFree,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Switch => "switch",
+            Event::TrapEnter => "trap_enter",
+            Event::TrapExit => "trap_exit",
+            Event::Syscall => "syscall",
+            Event::StackAlloc => "stack_alloc",
+            Event::StackFree => "stack_free",
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Record {
+    time: usize,
+    event: Event,
+    arg: usize,
+}
+
+/// the trace of one hart
+struct Ring {
+    /// slot of the next record, counting up from 0 forever
+    next: AtomicUsize,
+    records: UnsafeCell<[Option<Record>; TRACE_LEN]>,
+}
+
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    const fn new() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            records: UnsafeCell::new([None; TRACE_LEN]),
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_RING: Ring = Ring::new();
+static RINGS: [Ring; MAX_HARTS] = [EMPTY_RING; MAX_HARTS];
+
+/// Register the `trace.enable` tunable.
+pub fn init() {
+    sysctl::register(
+        "trace.enable",
+        || ENABLED.load(Ordering::Relaxed) as usize,
+        |on| {
+            if on > 1 {
+                return false;
+            }
+            ENABLED.store(on == 1, Ordering::Relaxed);
+            true
+        },
+    );
+    // what led up to a failure
+    teardown::register("trace ring", |failure| {
+        if failure && ENABLED.load(Ordering::Relaxed) {
+            dump();
+        }
+    });
+}
+
+/// Record `event` with `arg` in the trace of this hart.
+pub fn record(event: Event, arg: usize) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let ring = match RINGS.get(hart_id()) {
+        Some(ring) => ring,
+        None => return,
+    };
+    // a trace point interrupting this one takes the next slot
+    let slot = ring.next.fetch_add(1, Ordering::Relaxed) % TRACE_LEN;
+    let record = Record {
+        time: get_time_us(),
+        event,
+        arg,
+    };
+    unsafe {
+        (*ring.records.get())[slot] = Some(record);
+    }
+}
+
+/// Print the traces of all harts, returning the number of records.
+pub fn dump() -> usize {
+    let mut count = 0;
+    for (hart, ring) in RINGS.iter().enumerate() {
+        let next = ring.next.load(Ordering::Relaxed);
+        let records = unsafe { &*ring.records.get() };
+        for i in next.saturating_sub(TRACE_LEN)..next {
+            if let Some(r) = records[i % TRACE_LEN] {
+                println!("trace {} {} {} {:#x}", hart, r.time, r.event.name(), r.arg);
+                count += 1;
+            }
+        }
+    }
+    count
+}
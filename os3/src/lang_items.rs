@@ -1,11 +1,20 @@
 //! The panic handler
 
-use crate::sbi::shutdown;
+use crate::boot;
+use crate::console;
+use crate::loader::get_trap_cx;
+use crate::task::running_task_id;
+use crate::teardown;
+use crate::trap::dump_task;
 use core::panic::PanicInfo;
 
 #[panic_handler]
 /// panic handler
 fn panic(info: &PanicInfo) -> ! {
+    // locks are left held, don't report the orders we take them in now
+    #[cfg(debug_assertions)]
+    crate::sync::lockdep::disable();
+    console::flush();
     if let Some(location) = info.location() {
         println!(
             "Panicked at {}:{} {}",
@@ -16,5 +25,13 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         println!("[kernel] Panicked: {}", info.message().unwrap());
     }
-    shutdown()
+    if let Some(step) = boot::current_step() {
+        println!("[kernel] during boot step {:?}/{}", step.stage, step.name);
+    }
+    if let Some(task_id) = running_task_id() {
+        if let Some(cx) = get_trap_cx(task_id) {
+            dump_task(task_id, cx);
+        }
+    }
+    teardown::power_off(true)
 }
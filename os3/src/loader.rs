@@ -3,16 +3,127 @@
 //! For chapter 3, user applications are simply part of the data included in the
 //! kernel binary, so we only need to copy them to the space allocated for each
 //! app to load them. We also allocate fixed spaces for each task's
-//! [`KernelStack`] and [`UserStack`].
+//! [`UserStack`].
+//!
+//! Kernel stacks are allocated when a task is created, from the memory
+//! between the last app and `MEMORY_END`, and freed once it has exited, see
+//! [`free_kernel_stack`]. The lowest word of each holds a canary, which
+//! [`kernel_stacks_intact`] checks to tell whether a stack overflowed.
 
+use crate::appmeta::get_app_meta;
+use crate::cmdline::app_selected;
 use crate::config::*;
-use crate::trap::TrapContext;
+use crate::ktrace::{self, Event};
+use crate::sync::{LazyInit, UPSafeCell};
+use crate::trap::{with_interrupts_enabled, TrapContext};
+use alloc::vec::Vec;
+use lazy_static::*;
 
-#[repr(align(4096))]
-#[derive(Copy, Clone)]
-/// kernel stack structure
-struct KernelStack {
-    data: [u8; KERNEL_STACK_SIZE],
+/// value at the bottom of every kernel stack in use
+const STACK_CANARY: usize = 0x5afe_57ac_c0de_cafe;
+
+/// first-fit allocator of page-aligned kernel stacks
+struct StackPool {
+    /// free ranges `[start, end)`, sorted and never adjacent
+    free: Vec<(usize, usize)>,
+    /// `[bottom, top)` of the kernel stack of each app
+    stacks: [Option<(usize, usize)>; MAX_APP_NUM],
+}
+
+impl StackPool {
+    fn alloc(&mut self, size: usize) -> Option<usize> {
+        let size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let i = self
+            .free
+            .iter()
+            .position(|(start, end)| end - start >= size)?;
+        let bottom = self.free[i].0;
+        self.free[i].0 += size;
+        if self.free[i].0 == self.free[i].1 {
+            self.free.remove(i);
+        }
+        Some(bottom)
+    }
+    fn dealloc(&mut self, start: usize, end: usize) {
+        let i = self.free.partition_point(|r| r.0 < start);
+        self.free.insert(i, (start, end));
+        // merge with the following, then with the preceding range
+        if i + 1 < self.free.len() && self.free[i].1 == self.free[i + 1].0 {
+            self.free[i].1 = self.free.remove(i + 1).1;
+        }
+        if i > 0 && self.free[i - 1].1 == self.free[i].0 {
+            self.free[i - 1].1 = self.free.remove(i).1;
+        }
+    }
+}
+
+lazy_static! {
+    static ref STACK_POOL: UPSafeCell<StackPool> = unsafe {
+        let (start, end) = stack_pool_range();
+        let mut free = Vec::new();
+        if start < end {
+            free.push((start, end));
+        }
+        UPSafeCell::new(StackPool {
+            free,
+            stacks: [None; MAX_APP_NUM],
+        })
+    };
+}
+
+/// Get the memory `[start, end)` kernel stacks are allocated from.
+pub fn stack_pool_range() -> (usize, usize) {
+    extern "C" {
+        fn ekernel();
+    }
+    let start = (ekernel as usize).max(get_base_i(get_num_app()));
+    ((start + PAGE_SIZE - 1) & !(PAGE_SIZE - 1), MEMORY_END)
+}
+
+/// Allocate a kernel stack of `size` bytes for app `app_id`, returning its top.
+fn alloc_kernel_stack(app_id: usize, size: usize) -> Option<usize> {
+    let mut pool = STACK_POOL.exclusive_access();
+    assert!(pool.stacks[app_id].is_none());
+    let bottom = pool.alloc(size)?;
+    let top = bottom + ((size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1));
+    pool.stacks[app_id] = Some((bottom, top));
+    unsafe {
+        (bottom as *mut usize).write_volatile(STACK_CANARY);
+    }
+    ktrace::record(Event::StackAlloc, bottom);
+    Some(top)
+}
+
+/// Get the number of pages left in the kernel stack pool.
+pub fn free_stack_frames() -> usize {
+    STACK_POOL
+        .exclusive_access()
+        .free
+        .iter()
+        .map(|(start, end)| (end - start) / PAGE_SIZE)
+        .sum()
+}
+
+/// Whether the canaries of all kernel stacks in use are intact.
+pub fn kernel_stacks_intact() -> bool {
+    STACK_POOL
+        .exclusive_access()
+        .stacks
+        .iter()
+        .flatten()
+        .all(|&(bottom, _)| unsafe { (bottom as *const usize).read_volatile() } == STACK_CANARY)
+}
+
+/// Free the kernel stack of app `app_id`, if it still has one.
+///
+/// The stack must not be in use anymore, so this can't be called by the
+/// task itself.
+pub fn free_kernel_stack(app_id: usize) {
+    let mut pool = STACK_POOL.exclusive_access();
+    if let Some((bottom, top)) = pool.stacks[app_id].take() {
+        pool.dealloc(bottom, top);
+        ktrace::record(Event::StackFree, bottom);
+    }
 }
 
 #[repr(align(4096))]
@@ -22,29 +133,11 @@ struct UserStack {
     data: [u8; USER_STACK_SIZE],
 }
 
-/// kernel stack instance
-static KERNEL_STACK: [KernelStack; MAX_APP_NUM] = [KernelStack {
-    data: [0; KERNEL_STACK_SIZE],
-}; MAX_APP_NUM];
-
 /// user stack instance
 static USER_STACK: [UserStack; MAX_APP_NUM] = [UserStack {
     data: [0; USER_STACK_SIZE],
 }; MAX_APP_NUM];
 
-impl KernelStack {
-    fn get_sp(&self) -> usize {
-        self.data.as_ptr() as usize + KERNEL_STACK_SIZE
-    }
-    pub fn push_context(&self, trap_cx: TrapContext) -> usize {
-        let trap_cx_ptr = (self.get_sp() - core::mem::size_of::<TrapContext>()) as *mut TrapContext;
-        unsafe {
-            *trap_cx_ptr = trap_cx;
-        }
-        trap_cx_ptr as usize
-    }
-}
-
 impl UserStack {
     fn get_sp(&self) -> usize {
         self.data.as_ptr() as usize + USER_STACK_SIZE
@@ -56,46 +149,168 @@ fn get_base_i(app_id: usize) -> usize {
     APP_BASE_ADDRESS + app_id * APP_SIZE_LIMIT
 }
 
+/// Get the load region `[start, end)` of app `app_id`.
+pub fn get_app_range(app_id: usize) -> (usize, usize) {
+    let base = get_base_i(app_id);
+    (base, base + APP_SIZE_LIMIT)
+}
+
+/// Get the user stack `[bottom, top)` of app `app_id`.
+pub fn get_user_stack_range(app_id: usize) -> (usize, usize) {
+    let top = USER_STACK[app_id].get_sp();
+    (top - USER_STACK_SIZE, top)
+}
+
 /// Get the total number of applications.
+///
+/// Apps linked beyond `MAX_APP_NUM` are not counted.
 pub fn get_num_app() -> usize {
+    linked_app_count().min(MAX_APP_NUM)
+}
+
+/// get the number of apps linked into the kernel
+fn linked_app_count() -> usize {
     extern "C" {
         fn _num_app();
     }
     unsafe { (_num_app as usize as *const usize).read_volatile() }
 }
 
+/// names of the apps, in the order they are linked, set up by [`init`]
+static APP_NAMES: LazyInit<Vec<&'static str>> = LazyInit::new("APP_NAMES");
+
+/// Read the names of the apps linked into the kernel.
+pub fn init() {
+    extern "C" {
+        fn _app_names();
+    }
+    let mut start = _app_names as usize as *const u8;
+    let mut v = Vec::new();
+    unsafe {
+        for _ in 0..get_num_app() {
+            let mut end = start;
+            while end.read_volatile() != b'\0' {
+                end = end.add(1);
+            }
+            let slice = core::slice::from_raw_parts(start, end as usize - start as usize);
+            v.push(core::str::from_utf8(slice).unwrap());
+            start = end.add(1);
+        }
+    }
+    APP_NAMES.init(v);
+}
+
+/// Get the name of app `app_id`.
+pub fn get_app_name(app_id: usize) -> &'static str {
+    APP_NAMES[app_id]
+}
+
+lazy_static! {
+    /// whether each app passed the checks of [`load_apps`] and was loaded
+    static ref APP_LOADED: UPSafeCell<[bool; MAX_APP_NUM]> =
+        unsafe { UPSafeCell::new([false; MAX_APP_NUM]) };
+}
+
+/// Whether app `app_id` was loaded, see [`load_apps`].
+pub fn app_loaded(app_id: usize) -> bool {
+    APP_LOADED.exclusive_access()[app_id]
+}
+
 /// Load nth user app at
 /// [APP_BASE_ADDRESS + n * APP_SIZE_LIMIT, APP_BASE_ADDRESS + (n+1) * APP_SIZE_LIMIT).
+///
+/// Apps bigger than `APP_SIZE_LIMIT` or whose region overlaps the kernel are
+/// reported and left out, as are apps beyond `MAX_APP_NUM` and apps not
+/// chosen with the `apps=` boot option.
 pub fn load_apps() {
     extern "C" {
         fn _num_app();
+        fn skernel();
+        fn ekernel();
     }
     let num_app_ptr = _num_app as usize as *const usize;
     let num_app = get_num_app();
+    for i in num_app..linked_app_count() {
+        println!(
+            "[kernel] app {} is beyond MAX_APP_NUM = {}, not loaded",
+            i, MAX_APP_NUM
+        );
+    }
     let app_start = unsafe { core::slice::from_raw_parts(num_app_ptr.add(1), num_app + 1) };
     // clear i-cache first
     unsafe {
         core::arch::asm!("fence.i");
     }
+    let mut loaded = APP_LOADED.exclusive_access();
     // load apps
     for i in 0..num_app {
+        if !app_selected(i) {
+            continue;
+        }
         let base_i = get_base_i(i);
-        // clear region
-        (base_i..base_i + APP_SIZE_LIMIT)
-            .for_each(|addr| unsafe { (addr as *mut u8).write_volatile(0) });
-        // load app from data section to memory
-        let src = unsafe {
-            core::slice::from_raw_parts(app_start[i] as *const u8, app_start[i + 1] - app_start[i])
-        };
-        let dst = unsafe { core::slice::from_raw_parts_mut(base_i as *mut u8, src.len()) };
-        dst.copy_from_slice(src);
+        let size = app_start[i + 1] - app_start[i];
+        if size > APP_SIZE_LIMIT {
+            println!(
+                "[kernel] app {} ({}) is {} bytes, over APP_SIZE_LIMIT = {}, not loaded",
+                i,
+                get_app_name(i),
+                size,
+                APP_SIZE_LIMIT
+            );
+            continue;
+        }
+        let meta = get_app_meta(i);
+        if let Some(unmet) = meta.and_then(|meta| meta.unmet()) {
+            println!(
+                "[kernel] app {} ({}) {}, not loaded",
+                i,
+                get_app_name(i),
+                unmet
+            );
+            continue;
+        }
+        if base_i < ekernel as usize && (skernel as usize) < base_i + APP_SIZE_LIMIT {
+            println!(
+                "[kernel] app {} ({}) region {:#x} overlaps the kernel, not loaded",
+                i,
+                get_app_name(i),
+                base_i
+            );
+            continue;
+        }
+        with_interrupts_enabled(|| {
+            // clear region
+            (base_i..base_i + APP_SIZE_LIMIT)
+                .for_each(|addr| unsafe { (addr as *mut u8).write_volatile(0) });
+            // load app from data section to memory
+            let src = unsafe { core::slice::from_raw_parts(app_start[i] as *const u8, size) };
+            let dst = unsafe { core::slice::from_raw_parts_mut(base_i as *mut u8, src.len()) };
+            dst.copy_from_slice(src);
+        });
+        if let Some(meta) = meta.filter(|meta| !meta.description.is_empty()) {
+            info!("[kernel] app {} ({}): {}", i, get_app_name(i), meta.description);
+        }
+        loaded[i] = true;
     }
 }
 
-/// get app info with entry and sp and save `TrapContext` in kernel stack
+/// Get the `TrapContext` of app `app_id`, saved at the top of its kernel
+/// stack, unless the stack has been freed.
+pub fn get_trap_cx(app_id: usize) -> Option<&'static TrapContext> {
+    let (_, top) = STACK_POOL.exclusive_access().stacks[app_id]?;
+    let ptr = top - core::mem::size_of::<TrapContext>();
+    Some(unsafe { &*(ptr as *const TrapContext) })
+}
+
+/// allocate the kernel stack of app `app_id` and save the `TrapContext` to
+/// enter it on that stack
 pub fn init_app_cx(app_id: usize) -> usize {
-    KERNEL_STACK[app_id].push_context(TrapContext::app_init_context(
-        get_base_i(app_id),
-        USER_STACK[app_id].get_sp(),
-    ))
+    let top =
+        alloc_kernel_stack(app_id, KERNEL_STACK_SIZE).expect("out of memory for kernel stacks");
+    let trap_cx_ptr = (top - core::mem::size_of::<TrapContext>()) as *mut TrapContext;
+    unsafe {
+        *trap_cx_ptr =
+            TrapContext::app_init_context(get_base_i(app_id), USER_STACK[app_id].get_sp());
+    }
+    trap_cx_ptr as usize
 }
@@ -0,0 +1,87 @@
+//! Secondary UART for kernel logs
+//!
+//! If the device tree names a second serial port, `serial1` in `/aliases`,
+//! the logger writes to it instead of the console, leaving UART0 to the
+//! output of the apps. The port is a polled 16550 as set up by the firmware,
+//! no interrupts or baud rate.
+
+use crate::fdt::Fdt;
+use core::convert::TryInto;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// transmit holding register
+const THR: usize = 0;
+/// line status register
+const LSR: usize = 5;
+/// LSR bit: THR can take another byte
+const LSR_THRE: u8 = 1 << 5;
+
+/// registers of the log UART, 0 without one
+static BASE: AtomicUsize = AtomicUsize::new(0);
+/// bits registers are shifted by, `reg-shift` in the device tree
+static REG_SHIFT: AtomicUsize = AtomicUsize::new(0);
+
+/// Look for the log UART in the device tree at `dtb_addr`.
+pub fn init(dtb_addr: usize) {
+    let fdt = match Fdt::from_addr(dtb_addr) {
+        Some(fdt) => fdt,
+        None => return,
+    };
+    let path = match fdt
+        .find_property("/aliases", "serial1")
+        .and_then(|value| core::str::from_utf8(value).ok())
+    {
+        Some(path) => path.trim_end_matches('\0'),
+        None => return,
+    };
+    // two address cells, as on qemu virt
+    let base = match fdt.find_property(path, "reg") {
+        Some(reg) if reg.len() >= 8 => u64::from_be_bytes(reg[..8].try_into().unwrap()) as usize,
+        _ => return,
+    };
+    let shift = fdt
+        .find_property(path, "reg-shift")
+        .filter(|value| value.len() == 4)
+        .map_or(0, |value| {
+            u32::from_be_bytes(value.try_into().unwrap()) as usize
+        });
+    REG_SHIFT.store(shift, Ordering::Relaxed);
+    BASE.store(base, Ordering::Relaxed);
+    println!("[kernel] logging to UART {} at {:#x}", path, base);
+}
+
+/// Get whether there is a log UART.
+pub fn present() -> bool {
+    BASE.load(Ordering::Relaxed) != 0
+}
+
+/// the log UART, only to be written to if [`present()`]
+pub struct LogUart;
+
+impl LogUart {
+    fn reg(&self, off: usize) -> *mut u8 {
+        (BASE.load(Ordering::Relaxed) + (off << REG_SHIFT.load(Ordering::Relaxed))) as *mut u8
+    }
+
+    fn putchar(&self, c: u8) {
+        unsafe {
+            while self.reg(LSR).read_volatile() & LSR_THRE == 0 {
+                core::hint::spin_loop();
+            }
+            self.reg(THR).write_volatile(c);
+        }
+    }
+}
+
+impl Write for LogUart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.bytes() {
+            if c == b'\n' {
+                self.putchar(b'\r');
+            }
+            self.putchar(c);
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,29 @@
 //! Global logger
+//!
+//! Every line is prefixed with the milliseconds since boot, the level and the
+//! id of the running task (`-` while booting), e.g. `[   1234 INFO  3]`.
+//!
+//! Lines go to the [`crate::log_uart`] if there is one, else the console.
 
+use crate::log_uart::{self, LogUart};
+use crate::sysctl;
+use crate::task::running_task_id;
+use crate::timer::get_time_us;
+use core::fmt::{self, Write};
 use log::{self, Level, LevelFilter, Log, Metadata, Record};
 
 /// a simple logger
 struct SimpleLogger;
 
+/// Write one line of log.
+fn emit(args: fmt::Arguments) {
+    if log_uart::present() {
+        LogUart.write_fmt(args).unwrap();
+    } else {
+        crate::console::print(args);
+    }
+}
+
 impl Log for SimpleLogger {
     fn enabled(&self, _metadata: &Metadata) -> bool {
         true
@@ -20,26 +39,60 @@ impl Log for SimpleLogger {
             Level::Debug => 32, // Green
             Level::Trace => 90, // BrightBlack
         };
-        println!(
-            "\u{1B}[{}m[{:>5}] {}\u{1B}[0m",
-            color,
-            record.level(),
-            record.args(),
-        );
+        let ms = get_time_us() / 1000;
+        match running_task_id() {
+            Some(id) => {
+                emit(format_args!(
+                    "\u{1B}[{}m[{:>7} {:>5} {:>2}] {}\u{1B}[0m\n",
+                    color,
+                    ms,
+                    record.level(),
+                    id,
+                    record.args(),
+                ));
+            }
+            None => {
+                emit(format_args!(
+                    "\u{1B}[{}m[{:>7} {:>5}  -] {}\u{1B}[0m\n",
+                    color,
+                    ms,
+                    record.level(),
+                    record.args(),
+                ));
+            }
+        }
+    }
+    fn flush(&self) {
+        crate::console::flush();
     }
-    fn flush(&self) {}
 }
 
 /// initiate logger
 pub fn init() {
     static LOGGER: SimpleLogger = SimpleLogger;
     log::set_logger(&LOGGER).unwrap();
-    log::set_max_level(match option_env!("LOG") {
-        Some("ERROR") => LevelFilter::Error,
-        Some("WARN") => LevelFilter::Warn,
-        Some("INFO") => LevelFilter::Info,
-        Some("DEBUG") => LevelFilter::Debug,
-        Some("TRACE") => LevelFilter::Trace,
-        _ => LevelFilter::Off,
-    });
+    sysctl::register(
+        "log.level",
+        || log::max_level() as usize,
+        |v| {
+            // 0 is `Off`, up to 5 for `Trace`
+            LevelFilter::iter().nth(v).map(log::set_max_level).is_some()
+        },
+    );
+    log::set_max_level(
+        option_env!("LOG")
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(LevelFilter::Off),
+    );
+}
+
+/// Change the maximum log level by name, e.g. `debug` or `OFF`.
+pub fn set_level(level: &str) -> bool {
+    match level.parse() {
+        Ok(filter) => {
+            log::set_max_level(filter);
+            true
+        }
+        Err(_) => false,
+    }
 }
@@ -9,8 +9,8 @@
 //!
 //! The operating system also starts in this module. Kernel code starts
 //! executing from `entry.asm`, after which [`rust_main()`] is called to
-//! initialize various pieces of functionality. (See its source code for
-//! details.)
+//! initialize various pieces of functionality, in the stages of [`BOOT`].
+//! (See [`boot`] for details.)
 //!
 //! We then call [`task::run_first_task()`] and for the first time go to
 //! userspace.
@@ -20,6 +20,8 @@
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
 
+#[macro_use]
+extern crate bitflags;
 #[macro_use]
 extern crate log;
 
@@ -27,17 +29,42 @@ extern crate alloc;
 
 #[macro_use]
 mod console;
+mod appmeta;
+mod bench;
+mod boot;
+mod cmdline;
 mod config;
+mod coredump;
+mod fdt;
+mod futex;
+mod hart;
 mod heap_alloc;
+#[cfg(debug_assertions)]
+mod heap_sites;
+mod ktrace;
 mod lang_items;
 mod loader;
+mod log_uart;
 mod logging;
+mod memmap;
+mod mq;
+mod mutex;
+mod perf;
+mod random;
 mod sbi;
+mod selftest;
 mod sync;
 pub mod syscall;
+mod sysctl;
 pub mod task;
+mod teardown;
 mod timer;
 pub mod trap;
+mod uaccess;
+mod virtio_rng;
+mod workqueue;
+
+use boot::{Stage, Step};
 
 core::arch::global_asm!(include_str!("entry.asm"));
 core::arch::global_asm!(include_str!("link_app.S"));
@@ -54,17 +81,145 @@ fn clear_bss() {
     }
 }
 
+/// the init calls of the boot, see [`boot`]
+static BOOT: &[Step] = &[
+    Step {
+        stage: Stage::Earlycon,
+        name: "logging",
+        init: |_| logging::init(),
+    },
+    Step {
+        stage: Stage::Earlycon,
+        name: "hello",
+        init: |_| {
+            early_println!("[kernel] Hello, world!");
+        },
+    },
+    Step {
+        stage: Stage::Heap,
+        name: "heap",
+        init: |_| heap_alloc::init_heap(),
+    },
+    Step {
+        stage: Stage::Heap,
+        name: "console",
+        init: |_| console::init(),
+    },
+    Step {
+        stage: Stage::Heap,
+        name: "app names",
+        init: |_| loader::init(),
+    },
+    Step {
+        stage: Stage::Heap,
+        name: "app metadata",
+        init: |_| appmeta::init(),
+    },
+    Step {
+        stage: Stage::Memory,
+        name: "memory map",
+        init: memmap::init,
+    },
+    Step {
+        stage: Stage::Memory,
+        name: "spare memory",
+        init: heap_alloc::add_spare_memory,
+    },
+    Step {
+        stage: Stage::Drivers,
+        name: "log uart",
+        init: log_uart::init,
+    },
+    Step {
+        stage: Stage::Drivers,
+        name: "timer",
+        init: |_| timer::init(),
+    },
+    Step {
+        stage: Stage::Drivers,
+        name: "random",
+        init: |_| random::init(),
+    },
+    Step {
+        stage: Stage::Drivers,
+        name: "perf counters",
+        init: |_| perf::init(),
+    },
+    Step {
+        stage: Stage::Options,
+        name: "task tunables",
+        init: |_| task::init(),
+    },
+    Step {
+        stage: Stage::Options,
+        name: "coredump tunables",
+        init: |_| coredump::init(),
+    },
+    Step {
+        stage: Stage::Options,
+        name: "trace tunables",
+        init: |_| ktrace::init(),
+    },
+    Step {
+        stage: Stage::Options,
+        name: "command line",
+        init: cmdline::init,
+    },
+    Step {
+        stage: Stage::Traps,
+        name: "trap entry",
+        init: |_| trap::init(),
+    },
+    Step {
+        stage: Stage::Traps,
+        name: "timer interrupt",
+        init: |_| {
+            trap::enable_timer_interrupt();
+            timer::update_time_page();
+            timer::set_next_trigger();
+        },
+    },
+    Step {
+        stage: Stage::Tasks,
+        name: "apps",
+        init: |_| loader::load_apps(),
+    },
+    Step {
+        stage: Stage::Tasks,
+        name: "tasks",
+        init: |_| task::create_tasks(),
+    },
+    Step {
+        stage: Stage::Tasks,
+        name: "self-check",
+        init: |_| {
+            if cmdline::selftest() {
+                selftest::run();
+            }
+        },
+    },
+    Step {
+        stage: Stage::Tasks,
+        name: "benchmarks",
+        init: |_| {
+            if cmdline::bench() {
+                bench::run();
+            }
+        },
+    },
+    Step {
+        stage: Stage::Tasks,
+        name: "memory map report",
+        init: |_| memmap::print(),
+    },
+];
+
 #[no_mangle]
 /// the rust entry-point of os
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize, dtb_addr: usize) -> ! {
     clear_bss();
-    logging::init();
-    println!("[kernel] Hello, world!");
-    heap_alloc::init_heap();
-    trap::init();
-    loader::load_apps();
-    trap::enable_timer_interrupt();
-    timer::set_next_trigger();
+    hart::init(hart_id);
+    boot::run(BOOT, dtb_addr);
     task::run_first_task();
     panic!("Unreachable in rust_main!");
 }
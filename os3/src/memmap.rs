@@ -0,0 +1,156 @@
+//! Physical memory map
+//!
+//! There is no frame allocator in ch3, so the map is fixed by the linker
+//! script, `config.rs` and the board. [`print`] reports it at boot, and
+//! [`regions`] keeps it available to the rest of the kernel.
+//!
+//! The `/reserved-memory` regions of the device tree, firmware memory like
+//! that of OpenSBI, are recorded by [`init`]; memory added to the heap keeps
+//! clear of them, and anything else fixed on top of them is warned about.
+
+use crate::config::MMIO;
+use crate::fdt::Fdt;
+use crate::heap_alloc::{heap_regions, heap_usage};
+use crate::loader::{get_app_name, get_app_range, get_num_app, stack_pool_range};
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use lazy_static::*;
+
+lazy_static! {
+    /// `/reserved-memory` regions `[start, end)` of the device tree
+    static ref RESERVED: UPSafeCell<Vec<(usize, usize)>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// Record the reserved regions of the device tree at `dtb_addr`.
+pub fn init(dtb_addr: usize) {
+    let fdt = match Fdt::from_addr(dtb_addr) {
+        Some(fdt) => fdt,
+        None => return,
+    };
+    // `(base, size)` pairs of two cells each, as on qemu virt; regions with
+    // only a size are allocated by the OS, which this kernel doesn't do
+    let read_be64 = |bytes: &[u8]| u64::from_be_bytes(bytes.try_into().unwrap()) as usize;
+    let mut reserved = RESERVED.exclusive_access();
+    for reg in fdt.child_properties("/reserved-memory", "reg") {
+        for pair in reg.chunks_exact(16) {
+            let (base, size) = (read_be64(&pair[..8]), read_be64(&pair[8..]));
+            reserved.push((base, base + size));
+        }
+    }
+}
+
+/// Get the reserved regions `[start, end)` of the device tree.
+pub fn reserved_ranges() -> Vec<(usize, usize)> {
+    RESERVED.exclusive_access().clone()
+}
+
+/// What a [`MemRegion`] is used for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    /// a section of the kernel image
+    Kernel,
+    /// the kernel heap, inside the kernel `.bss` or added at boot
+    Heap,
+    /// the load region of an app
+    App,
+    /// memory kernel stacks are allocated from
+    Pool,
+    /// device registers
+    Mmio,
+    /// memory the device tree reserves, e.g. for firmware
+    Reserved,
+}
+
+/// A range `[start, end)` of physical addresses
+#[derive(Copy, Clone, Debug)]
+pub struct MemRegion {
+    pub name: &'static str,
+    pub start: usize,
+    pub end: usize,
+    pub kind: RegionKind,
+}
+
+/// Get all regions of the memory map, sorted by address.
+pub fn regions() -> Vec<MemRegion> {
+    extern "C" {
+        fn stext();
+        fn etext();
+        fn srodata();
+        fn erodata();
+        fn sdata();
+        fn edata();
+        fn ebss();
+    }
+    let region = |name, start, end, kind| MemRegion {
+        name,
+        start,
+        end,
+        kind,
+    };
+    let mut v = Vec::new();
+    for &(start, len) in MMIO {
+        v.push(region("mmio", start, start + len, RegionKind::Mmio));
+    }
+    for (start, end) in reserved_ranges() {
+        v.push(region("reserved", start, end, RegionKind::Reserved));
+    }
+    let sections = [
+        (".text", stext as usize, etext as usize),
+        (".rodata", srodata as usize, erodata as usize),
+        (".data", sdata as usize, edata as usize),
+        (".bss", edata as usize, ebss as usize),
+    ];
+    for (name, start, end) in sections {
+        v.push(region(name, start, end, RegionKind::Kernel));
+    }
+    for (heap_start, heap_end) in heap_regions() {
+        v.push(region("heap", heap_start, heap_end, RegionKind::Heap));
+    }
+    for app_id in 0..get_num_app() {
+        let (start, end) = get_app_range(app_id);
+        v.push(region(get_app_name(app_id), start, end, RegionKind::App));
+    }
+    let (pool_start, pool_end) = stack_pool_range();
+    if pool_start < pool_end {
+        v.push(region(
+            "kernel stacks",
+            pool_start,
+            pool_end,
+            RegionKind::Pool,
+        ));
+    }
+    v.sort_by_key(|r| r.start);
+    v
+}
+
+/// Print the memory map, warning about overlapping regions.
+pub fn print() {
+    let regions = regions();
+    println!("[kernel] memory map:");
+    for r in regions.iter() {
+        println!(
+            "[kernel]   [{:#010x}, {:#010x}) {:>8} KiB {:?} {}",
+            r.start,
+            r.end,
+            (r.end - r.start) / 1024,
+            r.kind,
+            r.name
+        );
+    }
+    let (total, used) = heap_usage();
+    println!("[kernel] heap: {} of {} bytes in use", used, total);
+    for (a, b) in overlaps(&regions) {
+        println!("[kernel] warning: {} overlaps {}", b.name, a.name);
+    }
+}
+
+/// Get the pairs of neighbouring `regions`, sorted by address, where the
+/// second overlaps the first.
+pub fn overlaps(regions: &[MemRegion]) -> impl Iterator<Item = (&MemRegion, &MemRegion)> {
+    regions
+        .windows(2)
+        .map(|pair| (&pair[0], &pair[1]))
+        // the heap lives inside .bss on purpose
+        .filter(|(a, b)| b.start < a.end && b.kind != RegionKind::Heap)
+}
@@ -0,0 +1,146 @@
+//! Message queues, for IPC between tasks
+//!
+//! A queue holds up to its capacity of messages of at most
+//! [`MQ_MAX_MSG_SIZE`] bytes each, copied in and out of the kernel. Sending
+//! to a full queue and receiving from an empty one block. The task that
+//! created a queue owns it and may always use it; others may only send or
+//! receive if its mode allows them to. A signal interrupts waiting.
+
+use crate::sync::{UPSafeCell, WaitQueue};
+use crate::task::{block_current_and_run_next, current_signal_pending, get_current_task};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// most queues there can be
+pub const MAX_MQS: usize = 8;
+/// most messages a queue can hold
+pub const MQ_MAX_MSGS: usize = 16;
+/// most bytes in a message
+pub const MQ_MAX_MSG_SIZE: usize = 256;
+/// mode bit: tasks other than the owner may send
+pub const MQ_OTHERS_SEND: usize = 1;
+/// mode bit: tasks other than the owner may receive
+pub const MQ_OTHERS_RECEIVE: usize = 2;
+
+/// Why sending or receiving failed
+pub enum MqError {
+    /// there is no such queue, the current task may not use it that way,
+    /// or the message doesn't fit
+    Invalid,
+    /// a signal came, before or while waiting
+    Interrupted,
+}
+
+struct MessageQueue {
+    owner: usize,
+    mode: usize,
+    capacity: usize,
+    msgs: VecDeque<Vec<u8>>,
+    /// tasks waiting for room
+    senders: WaitQueue,
+    /// tasks waiting for a message
+    receivers: WaitQueue,
+}
+
+impl MessageQueue {
+    /// Whether the current task may use the queue for what `mode_bit` allows.
+    fn allows(&self, mode_bit: usize) -> bool {
+        get_current_task() == self.owner || self.mode & mode_bit != 0
+    }
+}
+
+lazy_static! {
+    static ref MQS: UPSafeCell<Vec<Option<MessageQueue>>> =
+        unsafe { UPSafeCell::new((0..MAX_MQS).map(|_| None).collect()) };
+}
+
+/// Create a queue of `capacity` messages owned by the current task, with the
+/// `MQ_OTHERS_*` bits of `mode`. Returns its id.
+pub fn create(capacity: usize, mode: usize) -> Option<usize> {
+    if capacity == 0 || capacity > MQ_MAX_MSGS || mode & !(MQ_OTHERS_SEND | MQ_OTHERS_RECEIVE) != 0
+    {
+        return None;
+    }
+    let mut mqs = MQS.exclusive_access();
+    let id = mqs.iter().position(Option::is_none)?;
+    mqs[id] = Some(MessageQueue {
+        owner: get_current_task(),
+        mode,
+        capacity,
+        msgs: VecDeque::new(),
+        senders: WaitQueue::default(),
+        receivers: WaitQueue::default(),
+    });
+    Some(id)
+}
+
+/// Send `msg` to queue `id`, waiting for room. Fails if there is no such
+/// queue, the current task may not send to it, `msg` is too long, or a
+/// signal interrupts.
+pub fn send(id: usize, msg: &[u8]) -> Result<(), MqError> {
+    if msg.len() > MQ_MAX_MSG_SIZE {
+        return Err(MqError::Invalid);
+    }
+    let current = get_current_task();
+    let mut waited = false;
+    loop {
+        let mut mqs = MQS.exclusive_access();
+        let mq = match mqs.get_mut(id).and_then(Option::as_mut) {
+            Some(mq) if mq.allows(MQ_OTHERS_SEND) => mq,
+            _ => return Err(MqError::Invalid),
+        };
+        if waited {
+            // still in if woken by a signal rather than a receiver
+            mq.senders.remove(current);
+        }
+        if mq.msgs.len() < mq.capacity {
+            mq.msgs.push_back(msg.to_vec());
+            mq.receivers.wake_one();
+            return Ok(());
+        }
+        if current_signal_pending() {
+            return Err(MqError::Interrupted);
+        }
+        mq.senders.push_current();
+        drop(mqs);
+        block_current_and_run_next();
+        waited = true;
+    }
+}
+
+/// Receive the oldest message of queue `id` into `buf`, waiting for one,
+/// and return its length. Fails if there is no such queue, the current task
+/// may not receive from it, the message doesn't fit, leaving it queued, or a
+/// signal interrupts.
+pub fn receive(id: usize, buf: &mut [u8]) -> Result<usize, MqError> {
+    let current = get_current_task();
+    let mut waited = false;
+    loop {
+        let mut mqs = MQS.exclusive_access();
+        let mq = match mqs.get_mut(id).and_then(Option::as_mut) {
+            Some(mq) if mq.allows(MQ_OTHERS_RECEIVE) => mq,
+            _ => return Err(MqError::Invalid),
+        };
+        if waited {
+            // still in if woken by a signal rather than a sender
+            mq.receivers.remove(current);
+        }
+        match mq.msgs.front() {
+            Some(msg) if msg.len() > buf.len() => return Err(MqError::Invalid),
+            Some(_) => {
+                let msg = mq.msgs.pop_front().unwrap();
+                buf[..msg.len()].copy_from_slice(&msg);
+                mq.senders.wake_one();
+                return Ok(msg.len());
+            }
+            None if current_signal_pending() => return Err(MqError::Interrupted),
+            None => {
+                mq.receivers.push_current();
+                drop(mqs);
+                block_current_and_run_next();
+                waited = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Kernel mutexes, with priority inheritance
+//!
+//! A mutex is held by one task at a time; the others trying to lock it wait,
+//! blocked or, for a spinning mutex, yielding until it is free. While they
+//! wait they lend the holder their nice value if it is better than its own,
+//! and it lends it on in turn if it waits on another mutex, until it unlocks.
+//! Otherwise any task of priority between the holder and a waiter would run
+//! ahead of the holder and so of the waiter, for as long as it pleases:
+//! priority inversion.
+//!
+//! Unlocking a blocking mutex hands it to the first waiter, so that nobody
+//! takes it from under it before it runs. A task exiting gives up the
+//! mutexes it holds the same way, and a signal interrupts waiting. Like
+//! futexes, mutexes are shared by all tasks.
+
+use crate::config::MAX_APP_NUM;
+use crate::sync::{UPSafeCell, WaitQueue};
+use crate::task::{
+    block_current_and_run_next, get_current_task, lent_nice, set_inherited_nice, signal_pending,
+    suspend_current_and_run_next,
+};
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// most mutexes there can be
+pub const MAX_MUTEXES: usize = 16;
+
+/// Why a mutex operation failed
+#[derive(Debug)]
+pub enum MutexError {
+    /// there is no such mutex
+    Invalid,
+    /// the current task already holds it, on lock, or doesn't, on unlock
+    NotOwner,
+    /// a signal came, before or while waiting for it
+    Interrupted,
+}
+
+/// What a task trying to lock a mutex does next
+enum Attempt {
+    Locked,
+    /// wait for it, blocked if `blocking` and yielding otherwise
+    Wait {
+        blocking: bool,
+    },
+}
+
+struct Mutex {
+    /// whether waiters block rather than yield
+    blocking: bool,
+    owner: Option<usize>,
+    waiters: WaitQueue,
+}
+
+struct Mutexes {
+    mutexes: Vec<Mutex>,
+    /// mutex each task waits on, by task id
+    waiting_for: [Option<usize>; MAX_APP_NUM],
+}
+
+impl Mutexes {
+    fn new() -> Self {
+        Self {
+            mutexes: Vec::new(),
+            waiting_for: [None; MAX_APP_NUM],
+        }
+    }
+
+    /// Create a mutex, whose waiters block if `blocking` and yield otherwise.
+    fn create(&mut self, blocking: bool) -> Option<usize> {
+        if self.mutexes.len() == MAX_MUTEXES {
+            return None;
+        }
+        self.mutexes.push(Mutex {
+            blocking,
+            owner: None,
+            waiters: WaitQueue::default(),
+        });
+        Some(self.mutexes.len() - 1)
+    }
+
+    /// The best nice value lent to task `task_id` by the tasks waiting on the
+    /// mutexes it holds.
+    fn inherited_nice(&self, task_id: usize) -> Option<isize> {
+        self.waiting_for
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.map_or(false, |m| self.mutexes[m].owner == Some(task_id)))
+            .filter_map(|(waiter, _)| lent_nice(waiter))
+            .min()
+    }
+
+    /// Work out again what task `task_id` inherits, and so what the holders
+    /// of the mutexes it waits on do, down the chain.
+    fn update_inheritance(&self, mut task_id: usize) {
+        // a chain longer than that runs in circles, a deadlock
+        for _ in 0..MAX_APP_NUM {
+            set_inherited_nice(task_id, self.inherited_nice(task_id));
+            match self.waiting_for[task_id].and_then(|m| self.mutexes[m].owner) {
+                Some(owner) => task_id = owner,
+                None => return,
+            }
+        }
+    }
+
+    /// Try to lock mutex `id` for task `task_id`, `waited` if it waited for
+    /// it already, and tell what it has to do next.
+    fn try_lock(&mut self, id: usize, task_id: usize, waited: bool) -> Result<Attempt, MutexError> {
+        let mutex = self.mutexes.get_mut(id).ok_or(MutexError::Invalid)?;
+        if waited {
+            // still in if woken by a signal rather than handed the mutex
+            mutex.waiters.remove(task_id);
+        }
+        match mutex.owner {
+            Some(owner) if owner == task_id && !waited => return Err(MutexError::NotOwner),
+            // handed over by `release` while blocked
+            Some(owner) if owner == task_id => {}
+            Some(owner) => {
+                if signal_pending(task_id) {
+                    self.waiting_for[task_id] = None;
+                    self.update_inheritance(owner);
+                    return Err(MutexError::Interrupted);
+                }
+                let blocking = mutex.blocking;
+                if blocking {
+                    mutex.waiters.push(task_id);
+                }
+                self.waiting_for[task_id] = Some(id);
+                self.update_inheritance(task_id);
+                return Ok(Attempt::Wait { blocking });
+            }
+            None => mutex.owner = Some(task_id),
+        }
+        self.waiting_for[task_id] = None;
+        // the spinning waiters, if any, lend to the new holder now
+        self.update_inheritance(task_id);
+        Ok(Attempt::Locked)
+    }
+
+    /// Unlock mutex `id`, held by task `task_id`.
+    fn unlock(&mut self, id: usize, task_id: usize) -> Result<(), MutexError> {
+        let mutex = self.mutexes.get(id).ok_or(MutexError::Invalid)?;
+        if mutex.owner != Some(task_id) {
+            return Err(MutexError::NotOwner);
+        }
+        self.release(id, task_id);
+        Ok(())
+    }
+
+    /// Let task `task_id` give up mutex `id`, to the first waiter still
+    /// blocked on it if a blocking one.
+    fn release(&mut self, id: usize, task_id: usize) {
+        let mutex = &mut self.mutexes[id];
+        mutex.owner = if mutex.blocking {
+            mutex.waiters.wake_first()
+        } else {
+            None
+        };
+        if let Some(owner) = mutex.owner {
+            self.waiting_for[owner] = None;
+            self.update_inheritance(owner);
+        }
+        self.update_inheritance(task_id);
+    }
+
+    /// Give up the mutexes task `task_id` holds and stop waiting, as it
+    /// exits.
+    fn release_all(&mut self, task_id: usize) {
+        let waited = self.waiting_for[task_id].take();
+        if let Some(m) = waited {
+            self.mutexes[m].waiters.remove(task_id);
+        }
+        for id in 0..self.mutexes.len() {
+            if self.mutexes[id].owner == Some(task_id) {
+                self.release(id, task_id);
+            }
+        }
+        if let Some(owner) = waited.and_then(|m| self.mutexes[m].owner) {
+            self.update_inheritance(owner);
+        }
+    }
+}
+
+lazy_static! {
+    static ref MUTEXES: UPSafeCell<Mutexes> = unsafe { UPSafeCell::new(Mutexes::new()) };
+}
+
+/// Create a mutex, whose waiters block if `blocking` and yield otherwise.
+/// Returns its id.
+pub fn create(blocking: bool) -> Option<usize> {
+    MUTEXES.exclusive_access().create(blocking)
+}
+
+/// Lock mutex `id`, waiting for it to be unlocked.
+pub fn lock(id: usize) -> Result<(), MutexError> {
+    let current = get_current_task();
+    let mut waited = false;
+    loop {
+        let attempt = MUTEXES.exclusive_access().try_lock(id, current, waited)?;
+        match attempt {
+            Attempt::Locked => return Ok(()),
+            Attempt::Wait { blocking: true } => block_current_and_run_next(),
+            Attempt::Wait { blocking: false } => suspend_current_and_run_next(),
+        }
+        waited = true;
+    }
+}
+
+/// Unlock mutex `id`, held by the current task.
+pub fn unlock(id: usize) -> Result<(), MutexError> {
+    MUTEXES.exclusive_access().unlock(id, get_current_task())
+}
+
+/// Give up the mutexes task `task_id` holds and stop waiting, as it exits.
+pub fn release_all(task_id: usize) {
+    MUTEXES.exclusive_access().release_all(task_id)
+}
@@ -0,0 +1,136 @@
+//! Hardware performance counters
+//!
+//! `cycle` and `instret` are made readable from U-mode by [`init`], so
+//! benchmarks can sample them without a syscall. For per-task figures, the
+//! task manager accumulates [`PerfCounters`] deltas across task switches.
+//!
+//! Other hardware events, like cache or TLB misses, go through the SBI PMU
+//! extension, see [`PerfEvent`]. They only work where the SBI implements it.
+
+use crate::sbi::{pmu_counter_config_matching, pmu_counter_start, pmu_counter_stop};
+use crate::timer::get_cycle;
+use riscv::register::*;
+
+/// hardware events a task can count at the same time
+pub const MAX_PERF_EVENTS: usize = 4;
+
+/// `scounteren` bits letting U-mode read `cycle`, `time` and `instret`
+const SCOUNTEREN_CY_TM_IR: usize = 0b111;
+
+/// read the `instret` counter
+pub fn get_instret() -> usize {
+    let instret;
+    unsafe {
+        core::arch::asm!("rdinstret {}", out(reg) instret);
+    }
+    instret
+}
+
+/// let U-mode read the counters directly
+pub fn init() {
+    unsafe {
+        core::arch::asm!("csrs scounteren, {}", in(reg) SCOUNTEREN_CY_TM_IR);
+    }
+}
+
+/// A sample, or a total, of the counters
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct PerfCounters {
+    pub cycles: usize,
+    pub instret: usize,
+}
+
+impl PerfCounters {
+    /// Sample the counters.
+    pub fn now() -> Self {
+        Self {
+            cycles: get_cycle(),
+            instret: get_instret(),
+        }
+    }
+
+    /// Add what the counters advanced between samples `start` and `end`.
+    pub fn add_delta(&mut self, start: &Self, end: &Self) {
+        self.cycles += end.cycles.wrapping_sub(start.cycles);
+        self.instret += end.instret.wrapping_sub(start.instret);
+    }
+}
+
+/// read counter `idx`, `cycle`, `time`, `instret` or one of `hpmcounter3..31`
+fn read_counter(idx: usize) -> usize {
+    macro_rules! hpmcounters {
+        ($($idx:literal => $csr:ident),*) => {
+            match idx {
+                0 => get_cycle(),
+                1 => time::read(),
+                2 => get_instret(),
+                $($idx => $csr::read(),)*
+                _ => 0,
+            }
+        };
+    }
+    hpmcounters!(
+        3 => hpmcounter3, 4 => hpmcounter4, 5 => hpmcounter5, 6 => hpmcounter6,
+        7 => hpmcounter7, 8 => hpmcounter8, 9 => hpmcounter9, 10 => hpmcounter10,
+        11 => hpmcounter11, 12 => hpmcounter12, 13 => hpmcounter13, 14 => hpmcounter14,
+        15 => hpmcounter15, 16 => hpmcounter16, 17 => hpmcounter17, 18 => hpmcounter18,
+        19 => hpmcounter19, 20 => hpmcounter20, 21 => hpmcounter21, 22 => hpmcounter22,
+        23 => hpmcounter23, 24 => hpmcounter24, 25 => hpmcounter25, 26 => hpmcounter26,
+        27 => hpmcounter27, 28 => hpmcounter28, 29 => hpmcounter29, 30 => hpmcounter30,
+        31 => hpmcounter31
+    )
+}
+
+/// A hardware event counted on behalf of a task
+///
+/// The counter only runs while the task does: it is [`pause`](Self::pause)d
+/// when the task is switched out and [`resume`](Self::resume)d when it is
+/// switched back in.
+#[derive(Copy, Clone, Debug)]
+pub struct PerfEvent {
+    /// SBI PMU event index, e.g. `0x4` for cache misses or `0x10019` for
+    /// DTLB read misses
+    pub event_idx: usize,
+    /// index of the counter claimed from the SBI
+    counter: usize,
+    /// events counted before the current run
+    count: usize,
+}
+
+impl PerfEvent {
+    /// Claim a counter for `event_idx` and start counting.
+    pub fn open(event_idx: usize, event_data: usize) -> Option<Self> {
+        let counter = pmu_counter_config_matching(event_idx, event_data).ok()?;
+        let event = Self {
+            event_idx,
+            counter,
+            count: 0,
+        };
+        event.resume();
+        Some(event)
+    }
+
+    /// Get the number of events counted so far, while counting.
+    pub fn read(&self) -> usize {
+        self.count + read_counter(self.counter)
+    }
+
+    /// Stop counting, until [`resume`](Self::resume).
+    pub fn pause(&mut self) {
+        self.count = self.read();
+        let _ = pmu_counter_stop(self.counter, false);
+    }
+
+    /// Count again, from where [`pause`](Self::pause) left.
+    pub fn resume(&self) {
+        let _ = pmu_counter_start(self.counter, 0);
+    }
+
+    /// Stop counting and give the counter back, returning the final count.
+    pub fn release(self) -> usize {
+        let count = self.read();
+        let _ = pmu_counter_stop(self.counter, true);
+        count
+    }
+}
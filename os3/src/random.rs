@@ -0,0 +1,125 @@
+//! Kernel entropy pool
+//!
+//! The pool is a xoshiro256** state seeded at boot from the cycle counter,
+//! `mtime` and the RTC, and stirred with the cycle counter on every timer
+//! interrupt, whose exact arrival jitters. Other sources feed it through
+//! [`add_entropy`]; a virtio-rng device, if there is one, is drawn on at
+//! boot, every time random bytes are handed out, and every
+//! `RESEED_TICKS` timer interrupts, from a [`crate::workqueue`] item as
+//! waiting for the device has no place in the interrupt handler.
+//!
+//! This is good enough to make runs unpredictable, but it is no CSPRNG.
+
+use crate::sync::UPSafeCell;
+use crate::timer::{get_cycle, get_realtime_ns, get_ticks, get_time};
+use crate::trap::without_interrupts;
+use crate::virtio_rng;
+use crate::workqueue;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// timer interrupts between draws on the virtio-rng device
+const RESEED_TICKS: usize = 100;
+
+/// whether there is a virtio-rng device, known without borrowing it
+static HAS_DEVICE: AtomicBool = AtomicBool::new(false);
+
+/// xoshiro256** state, never all zero
+struct EntropyPool {
+    state: [u64; 4],
+    /// which word the next entropy goes into
+    next: usize,
+}
+
+/// the splitmix64 finalizer, spreads every input bit over the output
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+impl EntropyPool {
+    fn mix(&mut self, x: u64) {
+        self.state[self.next] ^= splitmix64(x ^ self.state[(self.next + 1) % 4]);
+        self.next = (self.next + 1) % 4;
+        if self.state == [0; 4] {
+            self.state[0] = 1;
+        }
+    }
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+}
+
+lazy_static! {
+    static ref POOL: UPSafeCell<EntropyPool> = unsafe {
+        let mut pool = EntropyPool {
+            state: [1, 0, 0, 0],
+            next: 0,
+        };
+        pool.mix(get_cycle() as u64);
+        pool.mix(get_time() as u64);
+        pool.mix(get_realtime_ns() as u64);
+        UPSafeCell::new(pool)
+    };
+}
+
+/// Seed the pool.
+pub fn init() {
+    lazy_static::initialize(&POOL);
+    if virtio_rng::init() {
+        info!("[kernel] Entropy from virtio-rng");
+        HAS_DEVICE.store(true, Ordering::Relaxed);
+        add_device_entropy();
+    }
+}
+
+/// Stir in what the virtio-rng device has to give.
+///
+/// Only stirring masks interrupts, as the timer interrupt stirs the pool
+/// too, not waiting for the device.
+fn add_device_entropy() {
+    let mut buf = [0; 32];
+    let len = virtio_rng::read(&mut buf);
+    without_interrupts(|| {
+        for chunk in buf[..len].chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            add_entropy(usize::from_le_bytes(word));
+        }
+    });
+}
+
+/// Stir `x` into the pool.
+pub fn add_entropy(x: usize) {
+    POOL.exclusive_access().mix(x as u64);
+}
+
+/// Stir in the arrival time of an interrupt, and have the device drawn on
+/// every `RESEED_TICKS` of them.
+pub fn add_interrupt_entropy() {
+    add_entropy(get_cycle());
+    if HAS_DEVICE.load(Ordering::Relaxed) && get_ticks() % RESEED_TICKS == 0 {
+        workqueue::queue(|_, _| add_device_entropy(), 0, 0);
+    }
+}
+
+/// Fill `buf` with random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    add_device_entropy();
+    let mut pool = POOL.exclusive_access();
+    for chunk in buf.chunks_mut(8) {
+        let bytes = pool.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
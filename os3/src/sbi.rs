@@ -7,6 +7,31 @@ const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
 const SBI_SHUTDOWN: usize = 8;
 
+const SBI_EXT_BASE: usize = 0x10;
+const SBI_BASE_PROBE_EXTENSION: usize = 3;
+
+const SBI_EXT_DBCN: usize = 0x4442434e;
+const SBI_DBCN_CONSOLE_WRITE: usize = 0;
+
+const SBI_EXT_SRST: usize = 0x53525354;
+const SBI_SRST_SYSTEM_RESET: usize = 0;
+const SBI_SRST_TYPE_SHUTDOWN: usize = 0;
+const SBI_SRST_REASON_NONE: usize = 0;
+const SBI_SRST_REASON_SYSFAIL: usize = 1;
+
+const SBI_EXT_PMU: usize = 0x504d55;
+const SBI_PMU_NUM_COUNTERS: usize = 0;
+const SBI_PMU_COUNTER_CONFIG_MATCHING: usize = 2;
+const SBI_PMU_COUNTER_START: usize = 3;
+const SBI_PMU_COUNTER_STOP: usize = 4;
+
+/// `counter_config_matching` flag: clear the counter
+const SBI_PMU_CFG_FLAG_CLEAR_VALUE: usize = 1 << 1;
+/// `counter_start` flag: load the initial value first
+const SBI_PMU_START_FLAG_SET_INIT_VALUE: usize = 1 << 0;
+/// `counter_stop` flag: release the counter
+const SBI_PMU_STOP_FLAG_RESET: usize = 1 << 0;
+
 #[inline(always)]
 /// general sbi call
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
@@ -25,6 +50,30 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+#[inline(always)]
+/// sbi call of function `fid` of extension `eid`, returning the value or
+/// the negative SBI error code
+fn sbi_call_ext(eid: usize, fid: usize, args: [usize; 5]) -> Result<usize, isize> {
+    let (error, value): (isize, usize);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") args[0] => error,
+            inlateout("x11") args[1] => value,
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    if error == 0 {
+        Ok(value)
+    } else {
+        Err(error)
+    }
+}
+
 /// use sbi call to set timer
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_SET_TIMER, timer, 0, 0);
@@ -35,13 +84,77 @@ pub fn console_putchar(c: usize) {
     sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0);
 }
 
+/// whether the SBI implements extension `eid`
+fn probe_extension(eid: usize) -> bool {
+    sbi_call_ext(SBI_EXT_BASE, SBI_BASE_PROBE_EXTENSION, [eid, 0, 0, 0, 0])
+        .map_or(false, |v| v != 0)
+}
+
+/// whether the SBI has the debug console extension, for [`console_write`]
+pub fn has_debug_console() -> bool {
+    probe_extension(SBI_EXT_DBCN)
+}
+
+/// write `bytes` to the debug console in one sbi call, returning how many
+/// were written
+pub fn console_write(bytes: &[u8]) -> Result<usize, isize> {
+    let addr = bytes.as_ptr() as usize;
+    sbi_call_ext(
+        SBI_EXT_DBCN,
+        SBI_DBCN_CONSOLE_WRITE,
+        [bytes.len(), addr, 0, 0, 0],
+    )
+}
+
 /// use sbi call to getchar from console (qemu uart handler)
 pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 
-/// use sbi call to shutdown the kernel
-pub fn shutdown() -> ! {
+/// get the number of PMU counters, fails if the SBI has no PMU extension
+pub fn pmu_num_counters() -> Result<usize, isize> {
+    sbi_call_ext(SBI_EXT_PMU, SBI_PMU_NUM_COUNTERS, [0; 5])
+}
+
+/// claim a cleared, stopped counter for `event_idx`, returning its index
+pub fn pmu_counter_config_matching(event_idx: usize, event_data: usize) -> Result<usize, isize> {
+    let mask = (1 << pmu_num_counters()?) - 1;
+    let flags = SBI_PMU_CFG_FLAG_CLEAR_VALUE;
+    sbi_call_ext(
+        SBI_EXT_PMU,
+        SBI_PMU_COUNTER_CONFIG_MATCHING,
+        [0, mask, flags, event_idx, event_data],
+    )
+}
+
+/// start counter `idx` counting from `initial_value`
+pub fn pmu_counter_start(idx: usize, initial_value: usize) -> Result<usize, isize> {
+    sbi_call_ext(
+        SBI_EXT_PMU,
+        SBI_PMU_COUNTER_START,
+        [idx, 1, SBI_PMU_START_FLAG_SET_INIT_VALUE, initial_value, 0],
+    )
+}
+
+/// stop counter `idx`, also releasing it if `release`
+pub fn pmu_counter_stop(idx: usize, release: bool) -> Result<usize, isize> {
+    let flags = if release { SBI_PMU_STOP_FLAG_RESET } else { 0 };
+    sbi_call_ext(SBI_EXT_PMU, SBI_PMU_COUNTER_STOP, [idx, 1, flags, 0, 0])
+}
+
+/// use sbi call to shutdown the kernel, telling QEMU to exit with an error
+/// code on `failure` where the SBI supports system reset
+pub fn shutdown(failure: bool) -> ! {
+    let reason = if failure {
+        SBI_SRST_REASON_SYSFAIL
+    } else {
+        SBI_SRST_REASON_NONE
+    };
+    let _ = sbi_call_ext(
+        SBI_EXT_SRST,
+        SBI_SRST_SYSTEM_RESET,
+        [SBI_SRST_TYPE_SHUTDOWN, reason, 0, 0, 0],
+    );
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     panic!("It should shutdown!");
 }
@@ -0,0 +1,54 @@
+//! Boot self-check
+//!
+//! With the `selftest` boot option, [`run`] checks the invariants the rest of
+//! the kernel relies on right before the first app runs, reporting each as
+//! PASS or FAIL, and refuses to go on if any fails.
+//!
+//! There is no page table or frame allocator in this kernel, so the memory
+//! checks are against the memory map and the heap instead.
+
+use crate::heap_alloc::{heap_regions, heap_usage};
+use crate::loader::kernel_stacks_intact;
+use crate::memmap::{overlaps, regions};
+use crate::trap::entries_installed;
+
+/// the memory map has no overlapping regions
+fn memory_map_disjoint() -> bool {
+    overlaps(&regions()).next().is_none()
+}
+
+/// the heap counts no more memory in use than it has, nor more than it was
+/// given
+fn heap_accounting() -> bool {
+    let (total, used) = heap_usage();
+    let given: usize = heap_regions().iter().map(|(start, end)| end - start).sum();
+    used <= total && total <= given
+}
+
+/// a check and its name
+type Check = (&'static str, fn() -> bool);
+
+const CHECKS: &[Check] = &[
+    ("memory map", memory_map_disjoint),
+    ("heap accounting", heap_accounting),
+    ("kernel stack canaries", kernel_stacks_intact),
+    ("trap entries", entries_installed),
+];
+
+/// Run all checks, panicking if any fails.
+pub fn run() {
+    let mut failed = 0;
+    for (name, check) in CHECKS {
+        let ok = check();
+        println!(
+            "[kernel] selftest: {} {}",
+            name,
+            if ok { "PASS" } else { "FAIL" }
+        );
+        failed += !ok as usize;
+    }
+    if failed > 0 {
+        panic!("selftest: {} of {} checks failed", failed, CHECKS.len());
+    }
+    println!("[kernel] selftest: all {} checks passed", CHECKS.len());
+}
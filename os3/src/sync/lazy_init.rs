@@ -0,0 +1,69 @@
+//! Statics initialized explicitly during boot
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A static set once by [`LazyInit::init`], at a point of the boot sequence
+/// chosen by the caller rather than on first use as with `lazy_static`.
+///
+/// Using it before then panics with its name, so a wrong boot order shows
+/// up as such instead of as whatever the initializer happens to touch.
+pub struct LazyInit<T> {
+    /// name in panic messages
+    name: &'static str,
+    /// whether `value` is initialized
+    ready: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T> Sync for LazyInit<T> {}
+
+impl<T> LazyInit<T> {
+    /// Create the cell of the static `name`, uninitialized.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            ready: AtomicBool::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Set the value, panicking if it was already set.
+    pub fn init(&self, value: T) {
+        if self.ready.swap(true, Ordering::AcqRel) {
+            panic!("{} initialized twice", self.name);
+        }
+        unsafe {
+            (*self.value.get()).write(value);
+        }
+    }
+
+    /// Whether [`LazyInit::init`] has run.
+    pub fn is_init(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Get the value, or `None` before [`LazyInit::init`].
+    pub fn try_get(&self) -> Option<&T> {
+        if self.is_init() {
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Deref for LazyInit<T> {
+    type Target = T;
+
+    /// Panic if the value was not set yet.
+    #[track_caller]
+    fn deref(&self) -> &T {
+        match self.try_get() {
+            Some(value) => value,
+            None => panic!("{} used before initialization", self.name),
+        }
+    }
+}
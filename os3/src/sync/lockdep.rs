@@ -0,0 +1,88 @@
+//! Lock order checking, in debug builds
+//!
+//! Every borrow of a [`UPSafeCell`](super::UPSafeCell) counts as taking a
+//! lock, the cell itself. We keep track of the locks held, and remember for
+//! each pair of locks ever nested which one was taken first. Taking them the
+//! other way round later is a deadlock waiting for a second hart, so we panic
+//! then, telling where both orders come from.
+//!
+//! There is a single hart, so the state lives in plain statics.
+
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// most locks held at once that are checked
+const MAX_HELD: usize = 16;
+/// most lock orders remembered, later ones are not checked
+const MAX_ORDERS: usize = 128;
+
+#[derive(Copy, Clone)]
+/// a lock held, and where it was taken
+struct Held {
+    lock: usize,
+    at: &'static Location<'static>,
+}
+
+#[derive(Copy, Clone)]
+/// lock `second` was taken while holding `first`
+struct LockOrder {
+    first: Held,
+    second: Held,
+}
+
+static mut HELD: [Option<Held>; MAX_HELD] = [None; MAX_HELD];
+static mut ORDERS: [Option<LockOrder>; MAX_ORDERS] = [None; MAX_ORDERS];
+/// set once an inversion is reported, so that panicking does not report more
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Stop checking, e.g. when panicking with locks held.
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Find the order of `first` and `second` seen before, if any.
+fn find_order(orders: &[Option<LockOrder>], first: usize, second: usize) -> Option<&LockOrder> {
+    orders
+        .iter()
+        .flatten()
+        .find(|o| o.first.lock == first && o.second.lock == second)
+}
+
+/// Take `lock` at `at`, panicking if that inverts an order seen before.
+pub fn acquire(lock: usize, at: &'static Location<'static>) {
+    if DISABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let (held, orders) = unsafe { (&mut HELD, &mut ORDERS) };
+    let new = Held { lock, at };
+    for &first in held.iter().flatten() {
+        if find_order(orders, first.lock, lock).is_some() {
+            continue;
+        }
+        if let Some(o) = find_order(orders, lock, first.lock) {
+            disable();
+            panic!(
+                "lock order inversion: taking {:#x} at {} while holding {:#x} taken at {}, \
+                 but {:#x} was taken at {} while holding {:#x} taken at {}",
+                lock, at, first.lock, first.at, o.second.lock, o.second.at, lock, o.first.at
+            );
+        }
+        if let Some(slot) = orders.iter_mut().find(|o| o.is_none()) {
+            *slot = Some(LockOrder { first, second: new });
+        }
+    }
+    if let Some(slot) = held.iter_mut().find(|h| h.is_none()) {
+        *slot = Some(new);
+    }
+}
+
+/// Release `lock`.
+pub fn release(lock: usize) {
+    let held = unsafe { &mut HELD };
+    if let Some(slot) = held
+        .iter_mut()
+        .find(|h| matches!(h, Some(h) if h.lock == lock))
+    {
+        *slot = None;
+    }
+}
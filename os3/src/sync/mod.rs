@@ -1,5 +1,11 @@
 //! Synchronization and interior mutability primitives
 
+mod lazy_init;
+#[cfg(debug_assertions)]
+pub mod lockdep;
 mod up;
+mod wait_queue;
 
-pub use up::UPSafeCell;
+pub use lazy_init::LazyInit;
+pub use up::{UPRefMut, UPSafeCell};
+pub use wait_queue::WaitQueue;
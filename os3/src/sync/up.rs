@@ -1,6 +1,9 @@
 //! Uniprocessor interior mutability primitives
 
 use core::cell::{RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
+#[cfg(debug_assertions)]
+use core::{cell::Cell, panic::Location};
 
 /// Wrap a static data structure inside it so that we are
 /// able to access it without any `unsafe`.
@@ -8,10 +11,14 @@ use core::cell::{RefCell, RefMut};
 /// We should only use it in uniprocessor.
 ///
 /// In order to get mutable reference of inner data, call
-/// `exclusive_access`.
+/// `exclusive_access`. In debug builds, the order borrows of different cells
+/// nest in is checked, see [`super::lockdep`].
 pub struct UPSafeCell<T> {
     /// inner data
     inner: RefCell<T>,
+    /// where the data was last borrowed, to tell who holds it on a conflict
+    #[cfg(debug_assertions)]
+    borrowed_at: Cell<Option<&'static Location<'static>>>,
 }
 
 unsafe impl<T> Sync for UPSafeCell<T> {}
@@ -22,10 +29,71 @@ impl<T> UPSafeCell<T> {
     pub unsafe fn new(value: T) -> Self {
         Self {
             inner: RefCell::new(value),
+            #[cfg(debug_assertions)]
+            borrowed_at: Cell::new(None),
         }
     }
-    /// Panic if the data has been borrowed.
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+    /// Panic if the data has been borrowed, telling where in debug builds.
+    #[track_caller]
+    pub fn exclusive_access(&self) -> UPRefMut<'_, T> {
+        match self.try_exclusive_access() {
+            Some(inner) => inner,
+            None => self.already_borrowed(),
+        }
+    }
+    /// Get the data, or `None` if it has been borrowed.
+    #[track_caller]
+    pub fn try_exclusive_access(&self) -> Option<UPRefMut<'_, T>> {
+        let inner = self.inner.try_borrow_mut().ok()?;
+        #[cfg(debug_assertions)]
+        {
+            self.borrowed_at.set(Some(Location::caller()));
+            super::lockdep::acquire(self.lock(), Location::caller());
+        }
+        Some(UPRefMut {
+            inner,
+            #[cfg(debug_assertions)]
+            lock: self.lock(),
+        })
+    }
+    #[track_caller]
+    fn already_borrowed(&self) -> ! {
+        #[cfg(debug_assertions)]
+        if let Some(location) = self.borrowed_at.get() {
+            panic!("UPSafeCell already borrowed at {}", location);
+        }
+        panic!("UPSafeCell already borrowed");
+    }
+    /// the lock `lockdep` knows the cell as
+    #[cfg(debug_assertions)]
+    fn lock(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+/// The borrowed data of a [`UPSafeCell`], given back when dropped.
+pub struct UPRefMut<'a, T> {
+    inner: RefMut<'a, T>,
+    #[cfg(debug_assertions)]
+    lock: usize,
+}
+
+impl<T> Deref for UPRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UPRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Drop for UPRefMut<'_, T> {
+    fn drop(&mut self) {
+        super::lockdep::release(self.lock);
     }
 }
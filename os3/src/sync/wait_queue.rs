@@ -0,0 +1,60 @@
+//! Queues of tasks blocked until some condition holds
+
+use crate::task::{get_current_task, wakeup_task};
+use alloc::collections::VecDeque;
+
+/// Tasks waiting on something, woken in the order they came.
+///
+/// A task puts itself in with [`WaitQueue::push_current`], lets go of the
+/// queue and then blocks with [`crate::task::block_current_and_run_next`].
+/// Woken, it has to recheck what it waited for, and take itself out with
+/// [`WaitQueue::remove`] if it was a signal that woke it, see
+/// [`crate::task::signal_pending`].
+#[derive(Default)]
+pub struct WaitQueue {
+    waiters: VecDeque<usize>,
+}
+
+impl WaitQueue {
+    /// Queue the current task, which is to block right after.
+    pub fn push_current(&mut self) {
+        self.push(get_current_task());
+    }
+
+    /// Queue task `task_id`, which is to block right after.
+    pub fn push(&mut self, task_id: usize) {
+        self.waiters.push_back(task_id);
+    }
+
+    /// Take task `task_id` out, as it gave up waiting, returning whether it
+    /// was still in.
+    pub fn remove(&mut self, task_id: usize) -> bool {
+        match self.waiters.iter().position(|&id| id == task_id) {
+            Some(i) => {
+                self.waiters.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wake the first task still waiting, returning whether there was one.
+    pub fn wake_one(&mut self) -> bool {
+        self.wake_first().is_some()
+    }
+
+    /// Wake the first task still waiting, returning its id.
+    pub fn wake_first(&mut self) -> Option<usize> {
+        while let Some(task_id) = self.waiters.pop_front() {
+            if wakeup_task(task_id) {
+                return Some(task_id);
+            }
+        }
+        None
+    }
+
+    /// Whether no task is waiting.
+    pub fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+}
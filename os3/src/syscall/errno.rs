@@ -0,0 +1,47 @@
+//! Error numbers
+//!
+//! Syscalls fail by returning a negated [`Errno`], written `-EINVAL` and the
+//! like, with the numbers Linux uses so that user code can tell the reasons
+//! apart the usual way.
+
+use core::ops::Neg;
+
+/// The errors syscalls fail with; not all of them come up in ch3 yet
+#[repr(isize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms, dead_code)]
+pub enum Errno {
+    /// the caller is not privileged, see [`crate::task::current_privileged`]
+    EPERM = 1,
+    /// no such entry, e.g. tunable or message queue
+    ENOENT = 2,
+    /// no such task, or it has exited
+    ESRCH = 3,
+    /// interrupted by a signal
+    EINTR = 4,
+    /// no such file descriptor
+    EBADF = 9,
+    /// no such child, as there are none in ch3
+    ECHILD = 10,
+    /// nothing to do without waiting, and waiting was not allowed
+    EAGAIN = 11,
+    /// out of memory or of a fixed-size table, or memory not mapped
+    ENOMEM = 12,
+    /// a pointer outside of the caller's memory
+    EFAULT = 14,
+    /// an argument out of range or not supported
+    EINVAL = 22,
+    /// the lock is already held by the caller
+    EDEADLK = 35,
+    /// no such syscall
+    ENOSYS = 38,
+}
+
+impl Neg for Errno {
+    type Output = isize;
+
+    /// Get the value a syscall returns when failing with this error.
+    fn neg(self) -> isize {
+        -(self as isize)
+    }
+}
@@ -1,18 +1,228 @@
 //! File and filesystem-related syscalls
 
+use super::errno::Errno::{EAGAIN, EBADF, EFAULT, EINVAL};
+use super::process::TimeSpec;
+use crate::console::{input_ready, read_line, LINE_BUFFER_SIZE};
+use crate::task::{get_current_fd_flags, set_current_fd_flags, suspend_current_and_run_next};
+use crate::timer::get_time_ns;
+use crate::uaccess::{
+    copy_from_user, copy_slice_from_user, copy_slice_to_user, copy_to_user, user_buffer,
+};
+use core::mem::size_of;
+
+const FD_STDIN: usize = 0;
 const FD_STDOUT: usize = 1;
 
+/// `sys_fcntl` commands
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+/// status flag: fail with `EAGAIN` rather than wait
+const O_NONBLOCK: u32 = 0o4000;
+
+/// bytes of a write copied into the kernel at a time
+const WRITE_CHUNK: usize = 256;
+/// most buffers [`sys_writev`] takes
+const IOV_MAX: usize = 1024;
+
+/// `events` and `revents` bits of [`PollFd`]
+const POLLIN: i16 = 0x1;
+const POLLOUT: i16 = 0x4;
+const POLLNVAL: i16 = 0x20;
+
+/// read a line of console input from `fd` 0 into `buf`, waiting for it to
+/// be typed unless `O_NONBLOCK` is set; lines longer than `len` are handed
+/// over in pieces. `buf[..len]` must be memory of the caller, or nothing is
+/// read and it fails with `-EFAULT`.
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let flags = match get_current_fd_flags(fd) {
+        Some(flags) => flags,
+        None => return -EBADF,
+    };
+    match fd {
+        FD_STDIN => {
+            // checked first, not to take input only to lose it
+            if !user_buffer(buf as usize, len) {
+                return -EFAULT;
+            }
+            let mut line = [0u8; LINE_BUFFER_SIZE];
+            let line = &mut line[..len.min(LINE_BUFFER_SIZE)];
+            let nonblock = flags & O_NONBLOCK != 0;
+            loop {
+                match read_line(line) {
+                    Some(n) if copy_slice_to_user(buf, &line[..n]) => return n as isize,
+                    Some(_) => return -EFAULT,
+                    None if nonblock => return -EAGAIN,
+                    None => suspend_current_and_run_next(),
+                }
+            }
+        }
+        _ => -EBADF,
+    }
+}
+
+/// Print the UTF-8 text of user buffer `buf[..len]`, a chunk at a time.
+///
+/// Stops at the first invalid sequence, returning how many bytes were
+/// printed before it, or `-EINVAL` if none were.
+fn write_console(buf: *const u8, len: usize) -> isize {
+    if !user_buffer(buf as usize, len) {
+        return -EFAULT;
+    }
+    let mut chunk = [0u8; WRITE_CHUNK];
+    // bytes of a character cut in two by the end of the last chunk
+    let mut carried = 0;
+    let mut copied = 0;
+    while copied < len {
+        let n = (len - copied).min(WRITE_CHUNK - carried);
+        let end = carried + n;
+        if !copy_slice_from_user(&mut chunk[carried..end], buf.wrapping_add(copied)) {
+            return -EFAULT;
+        }
+        copied += n;
+        let (valid, invalid) = match core::str::from_utf8(&chunk[..end]) {
+            Ok(str) => (str.len(), false),
+            Err(e) if e.error_len().is_none() && copied < len => (e.valid_up_to(), false),
+            Err(e) => (e.valid_up_to(), true),
+        };
+        print!("{}", core::str::from_utf8(&chunk[..valid]).unwrap());
+        if invalid {
+            let printed = copied - end + valid;
+            return if printed == 0 {
+                -EINVAL
+            } else {
+                printed as isize
+            };
+        }
+        chunk.copy_within(valid..end, 0);
+        carried = end - valid;
+    }
+    len as isize
+}
+
 // YOUR JOB: 修改 sys_write 使之通过测试
+/// Write `buf[..len]` to `fd`. `buf[..len]` must be memory of the caller,
+/// or it fails with `-EFAULT`.
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    if get_current_fd_flags(fd).is_none() {
+        return -EBADF;
+    }
     match fd {
-        FD_STDOUT => {
-            let slice = unsafe { core::slice::from_raw_parts(buf, len) };
-            let str = core::str::from_utf8(slice).unwrap();
-            print!("{}", str);
-            len as isize
+        FD_STDOUT => write_console(buf, len),
+        _ => -EBADF,
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+/// one buffer of a scatter-gather write
+pub struct IoVec {
+    pub base: *const u8,
+    pub len: usize,
+}
+
+/// Write `iovcnt` buffers described by `iov` to `fd`, in order. The array
+/// and each buffer must be memory of the caller, or it fails with `-EFAULT`,
+/// or returns what was written before the bad one.
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    if iovcnt > IOV_MAX {
+        return -EINVAL;
+    }
+    let mut written = 0;
+    for i in 0..iovcnt {
+        let (ret, want) = match copy_from_user(iov.wrapping_add(i)) {
+            Some(iov) => (sys_write(fd, iov.base, iov.len), iov.len),
+            None => (-EFAULT, 0),
+        };
+        if ret < 0 {
+            return if written == 0 { ret } else { written };
+        }
+        written += ret;
+        // cut short, by invalid text
+        if (ret as usize) < want {
+            break;
+        }
+    }
+    written
+}
+
+/// get or set the status flags of `fd`, only `O_NONBLOCK` being supported
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    match cmd {
+        F_GETFL => get_current_fd_flags(fd).map_or(-EBADF, |flags| flags as isize),
+        F_SETFL if arg as u32 & !O_NONBLOCK == 0 => {
+            if set_current_fd_flags(fd, arg as u32) {
+                0
+            } else {
+                -EBADF
+            }
+        }
+        _ => -EINVAL,
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+/// a file descriptor to wait for, see [`sys_ppoll`]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+/// the events `fd` is ready for now, of those in `events`
+fn poll_fd(fd: i32, events: i16) -> i16 {
+    match fd {
+        // ignored
+        fd if fd < 0 => 0,
+        fd if get_current_fd_flags(fd as usize).is_none() => POLLNVAL,
+        fd if fd as usize == FD_STDIN && events & POLLIN != 0 && input_ready() => POLLIN,
+        fd if fd as usize == FD_STDIN => 0,
+        fd if fd as usize == FD_STDOUT => events & POLLOUT,
+        _ => POLLNVAL,
+    }
+}
+
+/// Wait until one of the `nfds` descriptors of `fds` is ready, or for
+/// `*timeout` at most, unless null. Returns how many are ready, 0 on timeout.
+///
+/// `fds` and `timeout` must be memory of the caller, or it fails with
+/// `-EFAULT`; a `timeout` with `nsec` out of range, or too far out to count
+/// in nanoseconds, fails with `-EINVAL`.
+pub fn sys_ppoll(fds: *mut PollFd, nfds: usize, timeout: *const TimeSpec) -> isize {
+    if !user_buffer(fds as usize, nfds.saturating_mul(size_of::<PollFd>())) {
+        return -EFAULT;
+    }
+    let deadline = if timeout.is_null() {
+        None
+    } else {
+        let ts = match copy_from_user(timeout) {
+            Some(ts) => ts,
+            None => return -EFAULT,
+        };
+        match ts.as_ns().and_then(|ns| get_time_ns().checked_add(ns)) {
+            Some(deadline) => Some(deadline),
+            None => return -EINVAL,
+        }
+    };
+    loop {
+        let mut ready = 0;
+        for i in 0..nfds {
+            let pfd = fds.wrapping_add(i);
+            let mut polled = match copy_from_user(pfd) {
+                Some(polled) => polled,
+                None => return -EFAULT,
+            };
+            polled.revents = poll_fd(polled.fd, polled.events);
+            if !copy_to_user(pfd, &polled) {
+                return -EFAULT;
+            }
+            if polled.revents != 0 {
+                ready += 1;
+            }
         }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+        if ready > 0 || deadline.map_or(false, |d| get_time_ns() >= d) {
+            return ready;
         }
+        suspend_current_and_run_next();
     }
 }
@@ -0,0 +1,35 @@
+//! Futex syscall
+
+use super::errno::Errno::{EAGAIN, EFAULT, EINTR, ENOSYS};
+use crate::futex::{self, FutexError};
+use crate::loader::{get_app_range, get_num_app, get_user_stack_range};
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// Whether `addr` is an aligned `u32` in the user memory of any task.
+fn user_u32(addr: usize) -> bool {
+    let size = core::mem::size_of::<u32>();
+    addr % size == 0
+        && (0..get_num_app())
+            .flat_map(|id| [get_app_range(id), get_user_stack_range(id)])
+            .any(|(start, end)| start <= addr && addr + size <= end)
+}
+
+/// `FUTEX_WAIT`: block while the `u32` at `addr` is `val`, returning `-EAGAIN`
+/// if it isn't to begin with and `-EINTR` if a signal comes; `FUTEX_WAKE`: wake up to `val` waiters of `addr`,
+/// returning how many were woken
+pub fn sys_futex(addr: usize, op: usize, val: usize) -> isize {
+    if !user_u32(addr) {
+        return -EFAULT;
+    }
+    match op {
+        FUTEX_WAIT => match futex::wait(addr, val as u32) {
+            Ok(()) => 0,
+            Err(FutexError::Again) => -EAGAIN,
+            Err(FutexError::Interrupted) => -EINTR,
+        },
+        FUTEX_WAKE => futex::wake(addr, val) as isize,
+        _ => -ENOSYS,
+    }
+}
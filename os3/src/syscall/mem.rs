@@ -0,0 +1,109 @@
+//! Memory layout syscalls
+//!
+//! There are no address spaces in ch3: a task sees all of physical memory,
+//! and its mappings are the regions it was given, its slot of app memory,
+//! its user stack, and the time page it shares with every other task. They
+//! are resident from load on, nothing is allocated lazily, swapped or
+//! shared copy-on-write.
+
+use super::errno::Errno::{EFAULT, EINVAL, ENOMEM};
+use crate::config::PAGE_SIZE;
+use crate::loader::{get_app_range, get_user_stack_range};
+use crate::task::get_current_task;
+use crate::timer::time_page_addr;
+use crate::uaccess::{copy_slice_to_user, copy_to_user};
+
+pub const MAP_READ: u32 = 1;
+pub const MAP_WRITE: u32 = 2;
+pub const MAP_EXEC: u32 = 4;
+
+/// the app image, with room for its data and heap
+pub const MAP_KIND_ELF: u32 = 0;
+pub const MAP_KIND_STACK: u32 = 1;
+/// mapped into every task
+pub const MAP_KIND_SHARED: u32 = 4;
+
+/// `sys_mincore` bit: the page is in memory
+pub const MINCORE_RESIDENT: u8 = 1;
+
+/// A region of memory of the calling task, see [`sys_get_mappings`]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Mapping {
+    pub start: usize,
+    pub end: usize,
+    /// `MAP_READ`, `MAP_WRITE` and `MAP_EXEC`
+    pub perms: u32,
+    /// one of the `MAP_KIND_*`
+    pub kind: u32,
+}
+
+/// Get the mappings of the current task.
+fn current_mappings() -> [Mapping; 3] {
+    let task_id = get_current_task();
+    let (app_start, app_end) = get_app_range(task_id);
+    let (stack_start, stack_end) = get_user_stack_range(task_id);
+    let time_page = time_page_addr() & !(PAGE_SIZE - 1);
+    [
+        Mapping {
+            start: app_start,
+            end: app_end,
+            perms: MAP_READ | MAP_WRITE | MAP_EXEC,
+            kind: MAP_KIND_ELF,
+        },
+        Mapping {
+            start: stack_start,
+            end: stack_end,
+            perms: MAP_READ | MAP_WRITE,
+            kind: MAP_KIND_STACK,
+        },
+        Mapping {
+            start: time_page,
+            end: time_page + PAGE_SIZE,
+            perms: MAP_READ | MAP_WRITE,
+            kind: MAP_KIND_SHARED,
+        },
+    ]
+}
+
+/// Fill `buf[..len]` with the mappings of the current task, returning how
+/// many there are, which may be more than `len`.
+pub fn sys_get_mappings(buf: *mut Mapping, len: usize) -> isize {
+    let mappings = current_mappings();
+    for (i, mapping) in mappings.iter().take(len).enumerate() {
+        if !copy_to_user(buf.wrapping_add(i), mapping) {
+            return -EFAULT;
+        }
+    }
+    mappings.len() as isize
+}
+
+/// Fill `vec` with a byte of `MINCORE_*` bits for each page of the `len`
+/// bytes at `addr`, which must be page aligned and mapped by the current
+/// task.
+pub fn sys_mincore(addr: usize, len: usize, vec: *mut u8) -> isize {
+    if addr % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    if addr.checked_add(len).is_none() {
+        return -ENOMEM;
+    }
+    let pages = len / PAGE_SIZE + (len % PAGE_SIZE != 0) as usize;
+    let mappings = current_mappings();
+    let mapped = |page: usize| {
+        let start = addr + page * PAGE_SIZE;
+        mappings.iter().any(|m| m.start <= start && start < m.end)
+    };
+    if !(0..pages).all(mapped) {
+        return -ENOMEM;
+    }
+    // every mapped page is resident
+    let resident = [MINCORE_RESIDENT; 64];
+    for start in (0..pages).step_by(resident.len()) {
+        let n = (pages - start).min(resident.len());
+        if !copy_slice_to_user(vec.wrapping_add(start), &resident[..n]) {
+            return -EFAULT;
+        }
+    }
+    0
+}
@@ -9,30 +9,238 @@
 //! For clarity, each single syscall is implemented as its own function, named
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
+//!
+//! Syscalls are looked up by id in a table of handlers, [`SYSCALLS`]. An id
+//! without one fails with `ENOSYS`, and is counted in the `unknown_syscalls`
+//! of the task rather than by id.
 
+// kept in id order, as are the entries of `SYSCALLS`, for gaps and clashes
+// to show
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PPOLL: usize = 73;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_PTRACE: usize = 117;
+const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+const SYSCALL_SCHED_GETAFFINITY: usize = 123;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_SETTIMEOFDAY: usize = 170;
+pub const SYSCALL_GETPID: usize = 172;
+const SYSCALL_SYSINFO: usize = 179;
+const SYSCALL_MINCORE: usize = 232;
+const SYSCALL_GETRANDOM: usize = 278;
 const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_GET_TIME_PAGE: usize = 411;
+const SYSCALL_RING_SETUP: usize = 412;
+const SYSCALL_RING_ENTER: usize = 413;
+const SYSCALL_SYSCTL: usize = 414;
+const SYSCALL_PERF_READ: usize = 415;
+const SYSCALL_PERF_EVENT_OPEN: usize = 416;
+const SYSCALL_PERF_EVENT_READ: usize = 417;
+const SYSCALL_NICE: usize = 418;
+const SYSCALL_SET_TASK_GROUP: usize = 419;
+const SYSCALL_SET_GROUP_QUOTA: usize = 420;
+const SYSCALL_SCHED_LATENCY: usize = 421;
+const SYSCALL_ADJTIME: usize = 422;
+const SYSCALL_YIELD_TO: usize = 423;
+const SYSCALL_MQ_CREATE: usize = 424;
+const SYSCALL_MQ_SEND: usize = 425;
+const SYSCALL_MQ_RECEIVE: usize = 426;
+const SYSCALL_SCHED_GETSTAT: usize = 427;
+const SYSCALL_GET_MAPPINGS: usize = 428;
+const SYSCALL_TRACE_DUMP: usize = 429;
+const SYSCALL_TASK_SET_TIMEOUT: usize = 430;
+const SYSCALL_HEAP_SITES: usize = 431;
+const SYSCALL_TASK_SUSPEND: usize = 432;
+const SYSCALL_TASK_RESUME: usize = 433;
+const SYSCALL_SHUTDOWN: usize = 434;
+const SYSCALL_MUTEX_CREATE: usize = 463;
+const SYSCALL_MUTEX_LOCK: usize = 464;
+const SYSCALL_MUTEX_UNLOCK: usize = 466;
 
+mod errno;
 mod fs;
+mod futex;
+mod mem;
+mod mq;
+mod mutex;
+mod perf;
 mod process;
+mod ptrace;
+mod random;
+mod ring;
+mod signal;
+mod sysctl;
 
 use fs::*;
+use futex::*;
+use mem::*;
+use mq::*;
+use mutex::*;
+use perf::*;
 use process::*;
-use crate::task::record_current_syscall;
+use ptrace::*;
+use random::*;
+use ring::*;
+use signal::*;
+use sysctl::*;
+use crate::ktrace::{self, Event};
+use crate::perf::PerfCounters;
+use crate::config::MAX_SYSCALL_NUM;
+use crate::task::{
+    count_current_unknown_syscall, record_current_syscall, LatencyHistogram, RLimit,
+    SignalAction, TrapStats,
+};
+use errno::Errno::ENOSYS;
+
+/// Handler of a syscall, given its arguments in `a0` to `a3`; those taking
+/// a fourth are called with `syscall6` in user/src/syscall.rs, which loads `a3`
+type Handler = fn([usize; 4]) -> isize;
+
+/// handler of each syscall, by id
+static SYSCALLS: [Option<Handler>; MAX_SYSCALL_NUM] = {
+    let mut table: [Option<Handler>; MAX_SYSCALL_NUM] = [None; MAX_SYSCALL_NUM];
+    table[SYSCALL_FCNTL] = Some(|args| sys_fcntl(args[0], args[1], args[2]));
+    table[SYSCALL_READ] = Some(|args| sys_read(args[0], args[1] as *mut u8, args[2]));
+    table[SYSCALL_WRITE] = Some(|args| sys_write(args[0], args[1] as *const u8, args[2]));
+    table[SYSCALL_WRITEV] = Some(|args| sys_writev(args[0], args[1] as *const IoVec, args[2]));
+    table[SYSCALL_PPOLL] =
+        Some(|args| sys_ppoll(args[0] as *mut PollFd, args[1], args[2] as *const TimeSpec));
+    table[SYSCALL_EXIT] = Some(|args| sys_exit(args[0] as i32));
+    table[SYSCALL_FUTEX] = Some(|args| sys_futex(args[0], args[1], args[2]));
+    table[SYSCALL_SETITIMER] = Some(|args| {
+        sys_setitimer(
+            args[0],
+            args[1] as *const ITimerVal,
+            args[2] as *mut ITimerVal,
+        )
+    });
+    table[SYSCALL_CLOCK_GETTIME] =
+        Some(|args| sys_clock_gettime(args[0], args[1] as *mut TimeSpec));
+    table[SYSCALL_CLOCK_NANOSLEEP] = Some(|args| {
+        sys_clock_nanosleep(
+            args[0],
+            args[1],
+            args[2] as *const TimeSpec,
+            args[3] as *mut TimeSpec,
+        )
+    });
+    table[SYSCALL_PTRACE] = Some(|args| sys_ptrace(args[0], args[1], args[2], args[3]));
+    table[SYSCALL_SCHED_SETAFFINITY] =
+        Some(|args| sys_sched_setaffinity(args[0], args[1], args[2] as *const usize));
+    table[SYSCALL_SCHED_GETAFFINITY] =
+        Some(|args| sys_sched_getaffinity(args[0], args[1], args[2] as *mut usize));
+    table[SYSCALL_YIELD] = Some(|_| sys_yield());
+    table[SYSCALL_KILL] = Some(|args| sys_kill(args[0] as isize, args[1]));
+    table[SYSCALL_SIGACTION] = Some(|args| {
+        sys_sigaction(
+            args[0],
+            args[1] as *const SignalAction,
+            args[2] as *mut SignalAction,
+        )
+    });
+    table[SYSCALL_SIGPROCMASK] = Some(|args| sys_sigprocmask(args[0] as u32));
+    table[SYSCALL_SIGRETURN] = Some(|_| sys_sigreturn());
+    table[SYSCALL_TIMES] = Some(|args| sys_times(args[0] as *mut Tms));
+    table[SYSCALL_SETPGID] = Some(|args| sys_setpgid(args[0], args[1]));
+    table[SYSCALL_GETPGID] = Some(|args| sys_getpgid(args[0]));
+    table[SYSCALL_GETRLIMIT] = Some(|args| sys_getrlimit(args[0], args[1] as *mut RLimit));
+    table[SYSCALL_SETRLIMIT] = Some(|args| sys_setrlimit(args[0], args[1] as *const RLimit));
+    table[SYSCALL_GETRUSAGE] = Some(|args| sys_getrusage(args[0] as isize, args[1] as *mut Rusage));
+    table[SYSCALL_GET_TIME] = Some(|args| sys_get_time(args[0] as *mut TimeVal, args[1]));
+    table[SYSCALL_SETTIMEOFDAY] = Some(|args| sys_settimeofday(args[0] as *const TimeVal, args[1]));
+    table[SYSCALL_GETPID] = Some(|_| sys_getpid());
+    table[SYSCALL_SYSINFO] = Some(|args| sys_sysinfo(args[0] as *mut SysInfo));
+    table[SYSCALL_MINCORE] = Some(|args| sys_mincore(args[0], args[1], args[2] as *mut u8));
+    table[SYSCALL_GETRANDOM] =
+        Some(|args| sys_getrandom(args[0] as *mut u8, args[1], args[2] as u32));
+    table[SYSCALL_TASK_INFO] = Some(|args| {
+        sys_task_info(
+            args[0] as *mut TaskInfo,
+            args[1] as *mut TaskTimes,
+            args[2] as *mut TrapStats,
+        )
+    });
+    table[SYSCALL_GET_TIME_PAGE] = Some(|_| sys_get_time_page());
+    table[SYSCALL_RING_SETUP] = Some(|args| sys_ring_setup(args[0], args[1]));
+    table[SYSCALL_RING_ENTER] = Some(|args| sys_ring_enter(args[0]));
+    table[SYSCALL_SYSCTL] = Some(|args| {
+        sys_sysctl(
+            args[0] as *const u8,
+            args[1],
+            args[2] as *mut usize,
+            args[3] as *const usize,
+        )
+    });
+    table[SYSCALL_PERF_READ] = Some(|args| sys_perf_read(args[0] as *mut PerfCounters));
+    table[SYSCALL_PERF_EVENT_OPEN] = Some(|args| sys_perf_event_open(args[0], args[1]));
+    table[SYSCALL_PERF_EVENT_READ] =
+        Some(|args| sys_perf_event_read(args[0], args[1] as *mut usize));
+    table[SYSCALL_NICE] = Some(|args| sys_nice(args[0] as isize));
+    table[SYSCALL_GETPRIORITY] = Some(|args| sys_getpriority(args[0], args[1]));
+    table[SYSCALL_SET_TASK_GROUP] = Some(|args| sys_set_task_group(args[0], args[1]));
+    table[SYSCALL_SET_GROUP_QUOTA] = Some(|args| sys_set_group_quota(args[0], args[1]));
+    table[SYSCALL_SCHED_LATENCY] =
+        Some(|args| sys_sched_latency(args[0] as isize, args[1] as *mut LatencyHistogram));
+    table[SYSCALL_ADJTIME] = Some(|args| sys_adjtime(args[0] as isize, args[1] as *mut isize));
+    table[SYSCALL_YIELD_TO] = Some(|args| sys_yield_to(args[0]));
+    table[SYSCALL_MQ_CREATE] = Some(|args| sys_mq_create(args[0], args[1]));
+    table[SYSCALL_MQ_SEND] = Some(|args| sys_mq_send(args[0], args[1] as *const u8, args[2]));
+    table[SYSCALL_MQ_RECEIVE] = Some(|args| sys_mq_receive(args[0], args[1] as *mut u8, args[2]));
+    table[SYSCALL_SCHED_GETSTAT] = Some(|args| sys_sched_getstat(args[0] as *mut SchedStat));
+    table[SYSCALL_GET_MAPPINGS] = Some(|args| sys_get_mappings(args[0] as *mut Mapping, args[1]));
+    table[SYSCALL_TRACE_DUMP] = Some(|_| sys_trace_dump());
+    table[SYSCALL_TASK_SET_TIMEOUT] = Some(|args| sys_task_set_timeout(args[0], args[1]));
+    table[SYSCALL_HEAP_SITES] = Some(|_| sys_heap_sites());
+    table[SYSCALL_TASK_SUSPEND] = Some(|args| sys_task_suspend(args[0]));
+    table[SYSCALL_TASK_RESUME] = Some(|args| sys_task_resume(args[0]));
+    table[SYSCALL_SHUTDOWN] = Some(|args| sys_shutdown(args[0]));
+    table[SYSCALL_MUTEX_CREATE] = Some(|args| sys_mutex_create(args[0]));
+    table[SYSCALL_MUTEX_LOCK] = Some(|args| sys_mutex_lock(args[0]));
+    table[SYSCALL_MUTEX_UNLOCK] = Some(|args| sys_mutex_unlock(args[0]));
+    table
+};
+
+/// Get the handler of syscall `syscall_id`, if there is such a syscall.
+fn handler(syscall_id: usize) -> Option<Handler> {
+    SYSCALLS.get(syscall_id).copied().flatten()
+}
+
+/// Whether there is a syscall `syscall_id`.
+pub fn syscall_supported(syscall_id: usize) -> bool {
+    handler(syscall_id).is_some()
+}
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
+    ktrace::record(Event::Syscall, syscall_id);
+    let handler = match handler(syscall_id) {
+        Some(handler) => handler,
+        None => {
+            // not accounted for by id, or ids out of range would pile up
+            count_current_unknown_syscall();
+            return -ENOSYS;
+        }
+    };
     // LAB1: You may need to update syscall info here.
     record_current_syscall(syscall_id);
-    match syscall_id {
-        SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
-        SYSCALL_EXIT => sys_exit(args[0] as i32),
-        SYSCALL_YIELD => sys_yield(),
-        SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
-        SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
-    }
+    handler(args)
 }
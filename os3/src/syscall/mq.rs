@@ -0,0 +1,41 @@
+//! Message queue syscalls
+
+use super::errno::Errno::{EFAULT, EINTR, EINVAL};
+use crate::mq::{self, MqError};
+use crate::uaccess::user_buffer;
+
+/// create a message queue of `capacity` messages, returning its id; see
+/// [`crate::mq`] for `mode`
+pub fn sys_mq_create(capacity: usize, mode: usize) -> isize {
+    match mq::create(capacity, mode) {
+        Some(id) => id as isize,
+        None => -EINVAL,
+    }
+}
+
+/// send `buf[..len]` as a message to queue `id`, waiting for room
+pub fn sys_mq_send(id: usize, buf: *const u8, len: usize) -> isize {
+    if !user_buffer(buf as usize, len) {
+        return -EFAULT;
+    }
+    let msg = unsafe { core::slice::from_raw_parts(buf, len) };
+    match mq::send(id, msg) {
+        Ok(()) => 0,
+        Err(MqError::Invalid) => -EINVAL,
+        Err(MqError::Interrupted) => -EINTR,
+    }
+}
+
+/// receive a message of queue `id` into `buf[..len]`, waiting for one, and
+/// return its length
+pub fn sys_mq_receive(id: usize, buf: *mut u8, len: usize) -> isize {
+    if !user_buffer(buf as usize, len) {
+        return -EFAULT;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+    match mq::receive(id, buf) {
+        Ok(len) => len as isize,
+        Err(MqError::Invalid) => -EINVAL,
+        Err(MqError::Interrupted) => -EINTR,
+    }
+}
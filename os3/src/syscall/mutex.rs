@@ -0,0 +1,35 @@
+//! Mutex syscalls
+
+use super::errno::Errno::{EDEADLK, EINTR, EINVAL, ENOMEM, EPERM};
+use crate::mutex::{self, MutexError};
+
+/// create a mutex, whose waiters block if `blocking` isn't 0 and yield
+/// otherwise, returning its id
+pub fn sys_mutex_create(blocking: usize) -> isize {
+    match mutex::create(blocking != 0) {
+        Some(id) => id as isize,
+        None => -ENOMEM,
+    }
+}
+
+/// lock mutex `id`, waiting for it; the holder runs with the waiter's
+/// priority meanwhile if better
+pub fn sys_mutex_lock(id: usize) -> isize {
+    match mutex::lock(id) {
+        Ok(()) => 0,
+        Err(MutexError::Invalid) => -EINVAL,
+        Err(MutexError::NotOwner) => -EDEADLK,
+        Err(MutexError::Interrupted) => -EINTR,
+    }
+}
+
+/// unlock mutex `id`, held by the caller
+pub fn sys_mutex_unlock(id: usize) -> isize {
+    match mutex::unlock(id) {
+        Ok(()) => 0,
+        Err(MutexError::Invalid) => -EINVAL,
+        Err(MutexError::NotOwner) => -EPERM,
+        // unlocking never waits
+        Err(MutexError::Interrupted) => -EINTR,
+    }
+}
@@ -0,0 +1,51 @@
+//! Performance counter syscalls
+
+use super::errno::Errno::{EFAULT, EINVAL};
+use crate::perf::PerfCounters;
+use crate::task::{get_current_task_perf, open_current_perf_event, read_current_perf_event};
+use crate::uaccess::copy_to_user;
+
+/// get the cycles and instructions retired while the current task ran
+pub fn sys_perf_read(counters: *mut PerfCounters) -> isize {
+    if !copy_to_user(counters, &get_current_task_perf()) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// Count SBI PMU event `event_idx` while the current task runs, returning a
+/// handle for [`sys_perf_event_read`]. The count is also logged at exit.
+pub fn sys_perf_event_open(event_idx: usize, event_data: usize) -> isize {
+    match open_current_perf_event(event_idx, event_data) {
+        Some(handle) => handle as isize,
+        None => -EINVAL,
+    }
+}
+
+/// get the count of perf event `handle` into `count`
+pub fn sys_perf_event_read(handle: usize, count: *mut usize) -> isize {
+    match read_current_perf_event(handle) {
+        Some(value) if copy_to_user(count, &value) => 0,
+        Some(_) => -EFAULT,
+        None => -EINVAL,
+    }
+}
+
+/// print the kernel event trace to the console, returning the number of
+/// records, see [`crate::ktrace`]
+pub fn sys_trace_dump() -> isize {
+    crate::ktrace::dump() as isize
+}
+
+/// print the kernel heap sites holding the most memory, returning the
+/// number of sites; only debug builds track them, see [`crate::heap_sites`]
+pub fn sys_heap_sites() -> isize {
+    #[cfg(debug_assertions)]
+    {
+        crate::heap_sites::dump() as isize
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        -super::errno::Errno::ENOSYS
+    }
+}
@@ -1,63 +1,743 @@
 //! Process management syscalls
 
-use crate::config::{MAX_APP_NUM, MAX_SYSCALL_NUM};
-use crate::task::{exit_current_and_run_next,
+use super::errno::Errno::{EFAULT, EINTR, EINVAL, EPERM, ESRCH};
+use crate::config::{MAX_HARTS, MAX_SYSCALL_NUM, PAGE_SIZE};
+use crate::hart::online_harts;
+use crate::heap_alloc::heap_usage;
+use crate::loader::{free_stack_frames, get_num_app};
+use crate::task::{current_privileged,
+                  current_signal_pending,
+                  exit_current_and_run_next,
+                  get_affinity,
+                  get_current_rlimit,
+                  get_current_task,
+                  get_current_task_cpu_times,
+                  get_current_task_run_time,
                   get_current_task_status,
+                  get_current_task_switches,
                   get_current_task_syscall_accounting,
+                  get_current_task_times,
+                  get_current_trap_stats,
+                  get_idle_time,
+                  get_latency,
+                  get_nice,
+                  get_pgid,
+                  get_sched_stat,
                   get_time_elapsed,
+                  live_tasks,
+                  nice_current,
+                  resume_task,
+                  set_affinity,
+                  set_current_itimer,
+                  set_current_rlimit,
+                  set_group_quota,
+                  set_pgid,
+                  set_task_group,
+                  set_timeout,
+                  sleep_current_and_run_next,
                   suspend_current_and_run_next,
-                  TaskStatus};
-use crate::timer::get_time_us;
+                  suspend_task,
+                  task_alive,
+                  yield_to_and_run_next,
+                  LatencyHistogram,
+                  TaskStatus,
+                  TrapStats,
+                  RLimit,
+                  PRIORITY_LEVELS,
+                  RESIDENT_BYTES,
+                  RLIMIT_RSS};
+use crate::teardown::power_off;
+use crate::timer::{
+    get_realtime_ns, get_time_ns, get_time_us, slew_realtime, step_realtime, time_page_addr,
+};
+use crate::uaccess::{copy_from_user, copy_to_user, user_buffer};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct TimeVal {
     pub sec: usize,
     pub usec: usize,
 }
 
+impl TimeVal {
+    /// Get the time in microseconds, unless `usec` is out of range or it
+    /// overflows.
+    fn as_us(&self) -> Option<usize> {
+        if self.usec >= 1_000_000 {
+            return None;
+        }
+        self.sec.checked_mul(1_000_000)?.checked_add(self.usec)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    /// Get the time in microseconds, unless `nsec` is out of range or it
+    /// overflows.
+    fn as_us(&self) -> Option<usize> {
+        if self.nsec >= 1_000_000_000 {
+            return None;
+        }
+        self.sec
+            .checked_mul(1_000_000)?
+            .checked_add(self.nsec / 1_000)
+    }
+
+    /// Get the time in nanoseconds, unless `nsec` is out of range or it
+    /// overflows.
+    pub fn as_ns(&self) -> Option<usize> {
+        if self.nsec >= 1_000_000_000 {
+            return None;
+        }
+        self.sec.checked_mul(1_000_000_000)?.checked_add(self.nsec)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+const ITIMER_REAL: usize = 0;
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+
+const TIMER_ABSTIME: usize = 1;
+
 pub struct TaskInfo {
     status: TaskStatus,
     syscall_times: [u32; MAX_SYSCALL_NUM],
     time: usize,
 }
 
+/// Where the time of a task went, in microseconds. Kept apart from
+/// [`TaskInfo`] so programs built against the old layout keep working.
+#[repr(C)]
+pub struct TaskTimes {
+    pub run_us: usize,
+    pub wait_us: usize,
+    pub block_us: usize,
+}
+
+/// clock ticks per second of [`Tms`]
+const CLK_TCK: usize = 100;
+
+/// CPU times of a task and its reaped children, in clock ticks
+#[repr(C)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+
+/// Resource usage of a task, or its reaped children, see [`sys_getrusage`]
+#[repr(C)]
+#[derive(Default)]
+pub struct Rusage {
+    /// time `Running` in user mode, in microseconds
+    pub utime_us: usize,
+    /// time `Running` in the kernel, in microseconds
+    pub stime_us: usize,
+    /// most frames resident at once
+    pub maxrss_frames: usize,
+    /// times the CPU was given up voluntarily
+    pub nvcsw: usize,
+    /// times the task was preempted
+    pub nivcsw: usize,
+    /// syscalls made
+    pub nsyscalls: usize,
+    /// time `Ready` waiting for the CPU, in microseconds
+    pub wait_us: usize,
+    /// time `Blocked`, in microseconds
+    pub block_us: usize,
+}
+
+/// Scheduler statistics of the whole system, see [`sys_sched_getstat`]
+#[repr(C)]
+pub struct SchedStat {
+    /// context switches from one task to another
+    pub switches: usize,
+    /// switches where the task gave up the CPU itself
+    pub voluntary: usize,
+    /// switches where the task was preempted
+    pub preemptions: usize,
+    /// tasks `Ready` to run
+    pub ready: usize,
+    /// `Ready` tasks by effective priority, from `NICE_MIN` on
+    pub ready_by_priority: [u32; PRIORITY_LEVELS],
+}
+
+/// System-wide utilization, see [`sys_sysinfo`]
+#[repr(C)]
+pub struct SysInfo {
+    /// time since boot, in microseconds
+    pub uptime_us: usize,
+    /// harts running the kernel, bit `n` standing for hart `n`
+    pub harts: usize,
+    /// time each hart spent idle waiting for a task, in microseconds
+    pub idle_us: [usize; MAX_HARTS],
+    /// tasks `Ready` to run
+    pub ready: usize,
+    /// tasks that have not exited
+    pub tasks: usize,
+    /// pages left for kernel stacks, the only memory handed out in frames
+    pub free_frames: usize,
+    /// bytes in the kernel heap
+    pub heap_total: usize,
+    /// bytes of the kernel heap not in use
+    pub heap_free: usize,
+}
+
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
     info!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
+/// power the machine off once the shutdown hooks have run, telling QEMU
+/// about a failure if `failure` isn't 0; only privileged tasks may
+pub fn sys_shutdown(failure: usize) -> isize {
+    if !current_privileged() {
+        return -EPERM;
+    }
+    info!("[kernel] shutdown requested by task {}", get_current_task());
+    power_off(failure != 0)
+}
+
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
     suspend_current_and_run_next();
     0
 }
 
+/// current task gives the rest of its time slice to task `pid`, or yields
+/// as usual if `pid` isn't `Ready`; only a `pid` out of range fails, with
+/// `-EINVAL`
+pub fn sys_yield_to(pid: usize) -> isize {
+    if pid >= get_num_app() {
+        return -EINVAL;
+    }
+    yield_to_and_run_next(pid);
+    0
+}
+
+/// get the user and kernel time of the current task into `tms` unless null,
+/// returning the clock ticks since boot; there are no children in ch3
+pub fn sys_times(tms: *mut Tms) -> isize {
+    let us_per_tick = 1_000_000 / CLK_TCK;
+    if !tms.is_null() {
+        let (user_us, kernel_us) = get_current_task_cpu_times();
+        let times = Tms {
+            utime: user_us / us_per_tick,
+            stime: kernel_us / us_per_tick,
+            cutime: 0,
+            cstime: 0,
+        };
+        if !copy_to_user(tms, &times) {
+            return -EFAULT;
+        }
+    }
+    (get_time_us() / us_per_tick) as isize
+}
+
+/// get the resource usage of the current task, or of its reaped children,
+/// into `usage`
+///
+/// All of an app's slot and user stack stay resident in ch3, so that is its
+/// `maxrss_frames`; there are no children, whose usage is all zero.
+pub fn sys_getrusage(who: isize, usage: *mut Rusage) -> isize {
+    let rusage = match who {
+        RUSAGE_SELF => {
+            let (utime_us, stime_us) = get_current_task_cpu_times();
+            let (nvcsw, nivcsw) = get_current_task_switches();
+            let (_, wait_us, block_us) = get_current_task_times();
+            let nsyscalls = get_current_task_syscall_accounting()
+                .iter()
+                .map(|&n| n as usize)
+                .sum();
+            Rusage {
+                utime_us,
+                stime_us,
+                maxrss_frames: RESIDENT_BYTES / PAGE_SIZE,
+                nvcsw,
+                nivcsw,
+                nsyscalls,
+                wait_us,
+                block_us,
+            }
+        }
+        RUSAGE_CHILDREN => Rusage::default(),
+        _ => return -EINVAL,
+    };
+    if !copy_to_user(usage, &rusage) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// get the limit of `resource` of the current task into `rlim`, one of
+/// `RLIMIT_CPU`, `RLIMIT_STACK`, `RLIMIT_RSS` and `RLIMIT_NOFILE`
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    match get_current_rlimit(resource) {
+        Some(limit) if copy_to_user(rlim, &limit) => 0,
+        Some(_) => -EFAULT,
+        None => -EINVAL,
+    }
+}
+
+/// set the limit of `resource` of the current task to `*rlim`; only
+/// privileged tasks may raise the hard limit
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    let new = match copy_from_user(rlim) {
+        Some(new) => new,
+        None => return -EFAULT,
+    };
+    let old = match get_current_rlimit(resource) {
+        Some(old) => old,
+        None => return -EINVAL,
+    };
+    // every frame of a task is resident from load on, there is nothing to
+    // give back
+    if new.cur > new.max || (resource == RLIMIT_RSS && new.cur < RESIDENT_BYTES) {
+        return -EINVAL;
+    }
+    if new.max > old.max && !current_privileged() {
+        return -EPERM;
+    }
+    set_current_rlimit(resource, new);
+    0
+}
+
 /// get time with second and microsecond
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     let us = get_time_us();
-    unsafe {
-        *ts = TimeVal {
-            sec: us / 1_000_000,
-            usec: us % 1_000_000,
-        };
+    let tv = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    if !copy_to_user(ts, &tv) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// step the realtime clock to `tv`; the monotonic clock is left alone, a
+/// `tv` with `usec` out of range or too far out fails with `-EINVAL`
+pub fn sys_settimeofday(tv: *const TimeVal, _tz: usize) -> isize {
+    if !current_privileged() {
+        return -EPERM;
+    }
+    let tv = match copy_from_user(tv) {
+        Some(tv) => tv,
+        None => return -EFAULT,
+    };
+    // the clock keeps its offset in signed nanoseconds
+    match tv.as_us().and_then(|us| us.checked_mul(1_000)) {
+        Some(ns) if ns <= isize::MAX as usize => step_realtime(ns),
+        _ => return -EINVAL,
+    }
+    0
+}
+
+/// slew the realtime clock by `delta_us`, storing what was left of the
+/// previous slew, in microseconds, to `old` unless null; a `delta_us` too
+/// large to count in nanoseconds fails with `-EINVAL`
+pub fn sys_adjtime(delta_us: isize, old: *mut isize) -> isize {
+    if !current_privileged() {
+        return -EPERM;
+    }
+    let delta_ns = match delta_us.checked_mul(1_000) {
+        Some(delta_ns) => delta_ns,
+        None => return -EINVAL,
+    };
+    // checked first, not to slew the clock and then fail
+    if !old.is_null() && !user_buffer(old as usize, core::mem::size_of::<isize>()) {
+        return -EFAULT;
+    }
+    let left = slew_realtime(delta_ns);
+    if !old.is_null() && !copy_to_user(old, &(left / 1_000)) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// get the id of the current task
+pub fn sys_getpid() -> isize {
+    get_current_task() as isize
+}
+
+/// the task a `pid` of 0 stands for
+fn task_of(pid: usize) -> usize {
+    if pid == 0 {
+        get_current_task()
+    } else {
+        pid
+    }
+}
+
+/// restrict task `pid`, or the current task if 0, to the harts in `*mask`;
+/// only privileged tasks may restrict other tasks
+pub fn sys_sched_setaffinity(pid: usize, size: usize, mask: *const usize) -> isize {
+    if size < core::mem::size_of::<usize>() {
+        return -EINVAL;
+    }
+    let mask = match copy_from_user(mask) {
+        Some(mask) => mask,
+        None => return -EFAULT,
+    };
+    let task_id = task_of(pid);
+    if !task_alive(task_id) {
+        return -ESRCH;
+    }
+    if task_id != get_current_task() && !current_privileged() {
+        return -EPERM;
+    }
+    if set_affinity(task_id, mask) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// get the harts task `pid`, or the current task if 0, may run on into `*mask`
+pub fn sys_sched_getaffinity(pid: usize, size: usize, mask: *mut usize) -> isize {
+    if size < core::mem::size_of::<usize>() {
+        return -EINVAL;
+    }
+    match get_affinity(task_of(pid)) {
+        Some(cpu_mask) if copy_to_user(mask, &cpu_mask) => core::mem::size_of::<usize>() as isize,
+        Some(_) => -EFAULT,
+        None => -ESRCH,
+    }
+}
+
+/// move task `pid`, or the current task if 0, into task group `group`; only
+/// privileged tasks may move other tasks
+pub fn sys_set_task_group(pid: usize, group: usize) -> isize {
+    let task_id = task_of(pid);
+    if !task_alive(task_id) {
+        return -ESRCH;
+    }
+    if task_id != get_current_task() && !current_privileged() {
+        return -EPERM;
+    }
+    if set_task_group(task_id, group) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// stop task `pid`, or the current task if 0, until it is resumed, a blocked
+/// task once it wakes; only privileged tasks may stop other tasks
+pub fn sys_task_suspend(pid: usize) -> isize {
+    let task_id = task_of(pid);
+    if !task_alive(task_id) {
+        return -ESRCH;
+    }
+    if task_id != get_current_task() && !current_privileged() {
+        return -EPERM;
+    }
+    if suspend_task(task_id) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// let task `pid`, stopped by [`sys_task_suspend`], run again; only
+/// privileged tasks may resume other tasks, and a stopped task can't resume
+/// itself
+pub fn sys_task_resume(pid: usize) -> isize {
+    if !current_privileged() {
+        return -EPERM;
+    }
+    if !task_alive(pid) {
+        return -ESRCH;
+    }
+    if resume_task(pid) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// move task `pid` to process group `pgid`, 0 standing for the current task
+/// and for `pid` itself; only privileged tasks may move other tasks
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let task_id = task_of(pid);
+    if !task_alive(task_id) {
+        return -ESRCH;
+    }
+    if task_id != get_current_task() && !current_privileged() {
+        return -EPERM;
+    }
+    let pgid = if pgid == 0 { task_id } else { pgid };
+    if set_pgid(task_id, pgid) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// get the process group of task `pid`, or of the current task if 0
+pub fn sys_getpgid(pid: usize) -> isize {
+    get_pgid(task_of(pid)).map_or(-ESRCH, |pgid| pgid as isize)
+}
+
+/// kill task `pid`, or the current task if 0, if it still runs `ms`
+/// milliseconds from now, with exit code [`crate::task::EXIT_TIMED_OUT`]; 0
+/// disarms it
+pub fn sys_task_set_timeout(pid: usize, ms: usize) -> isize {
+    let task_id = task_of(pid);
+    if !task_alive(task_id) {
+        return -ESRCH;
+    }
+    if task_id != get_current_task() && !current_privileged() {
+        return -EPERM;
+    }
+    if set_timeout(task_id, ms.saturating_mul(1000)) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// let the tasks of `group` run `quota` percent of the time at most; only
+/// privileged tasks may
+pub fn sys_set_group_quota(group: usize, quota: usize) -> isize {
+    if !current_privileged() {
+        return -EPERM;
+    }
+    if set_group_quota(group, quota) {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// get the scheduling latencies of task `pid`, the current task if 0, or of
+/// all tasks if -1
+pub fn sys_sched_latency(pid: isize, hist: *mut LatencyHistogram) -> isize {
+    let task_id = match pid {
+        -1 => None,
+        pid if pid < 0 => return -EINVAL,
+        pid => Some(task_of(pid as usize)),
+    };
+    match get_latency(task_id) {
+        Some(latency) if copy_to_user(hist, &latency) => 0,
+        Some(_) => -EFAULT,
+        None => -ESRCH,
+    }
+}
+
+/// get the scheduler statistics of the whole system
+pub fn sys_sched_getstat(stat: *mut SchedStat) -> isize {
+    let (voluntary, preemptions, ready_by_priority) = get_sched_stat();
+    let sched_stat = SchedStat {
+        switches: voluntary + preemptions,
+        voluntary,
+        preemptions,
+        ready: ready_by_priority.iter().map(|&n| n as usize).sum(),
+        ready_by_priority,
+    };
+    if !copy_to_user(stat, &sched_stat) {
+        return -EFAULT;
     }
     0
 }
 
+/// get the utilization of the whole system into `info`
+pub fn sys_sysinfo(info: *mut SysInfo) -> isize {
+    let (_, _, ready_by_priority) = get_sched_stat();
+    let (heap_total, heap_used) = heap_usage();
+    let sysinfo = SysInfo {
+        uptime_us: get_time_us(),
+        harts: online_harts(),
+        idle_us: get_idle_time(),
+        ready: ready_by_priority.iter().map(|&n| n as usize).sum(),
+        tasks: live_tasks(),
+        free_frames: free_stack_frames(),
+        heap_total,
+        heap_free: heap_total - heap_used,
+    };
+    if !copy_to_user(info, &sysinfo) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// add `increment` to the nice value of the current task; only privileged
+/// tasks may lower it, others fail with `-EPERM`
+pub fn sys_nice(increment: isize) -> isize {
+    if increment < 0 && !current_privileged() {
+        return -EPERM;
+    }
+    nice_current(increment);
+    0
+}
+
+const PRIO_PROCESS: usize = 0;
+
+/// get the nice value of task `who`, or the current task if 0, as `20 - nice`
+/// like Linux, so that it is never negative and can't be taken for an error
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    if which != PRIO_PROCESS {
+        return -EINVAL;
+    }
+    match get_nice(task_of(who)) {
+        Some(nice) => 20 - nice,
+        None => -ESRCH,
+    }
+}
+
+/// arm or disarm the interval timer `which`, delivering `SIGALRM` on expiry;
+/// times with `usec` out of range, or too long, fail with `-EINVAL`
+pub fn sys_setitimer(which: usize, new: *const ITimerVal, old: *mut ITimerVal) -> isize {
+    if which != ITIMER_REAL {
+        return -EINVAL;
+    }
+    let new = match copy_from_user(new) {
+        Some(new) => new,
+        None => return -EFAULT,
+    };
+    let (interval, value) = match (new.interval.as_us(), new.value.as_us()) {
+        (Some(interval), Some(value)) => (interval, value),
+        _ => return -EINVAL,
+    };
+    let (old_interval, old_value) = set_current_itimer(interval, value);
+    let old_timer = ITimerVal {
+        interval: TimeVal {
+            sec: old_interval / 1_000_000,
+            usec: old_interval % 1_000_000,
+        },
+        value: TimeVal {
+            sec: old_value / 1_000_000,
+            usec: old_value % 1_000_000,
+        },
+    };
+    if !old.is_null() && !copy_to_user(old, &old_timer) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// get time of clock `clock_id` with second and nanosecond
+pub fn sys_clock_gettime(clock_id: usize, ts: *mut TimeSpec) -> isize {
+    let ns = match clock_id {
+        CLOCK_REALTIME => get_realtime_ns(),
+        CLOCK_MONOTONIC => get_time_ns(),
+        CLOCK_PROCESS_CPUTIME_ID => get_current_task_run_time() * 1_000,
+        _ => return -EINVAL,
+    };
+    let time = TimeSpec {
+        sec: ns / 1_000_000_000,
+        nsec: ns % 1_000_000_000,
+    };
+    if !copy_to_user(ts, &time) {
+        return -EFAULT;
+    }
+    0
+}
+
+/// sleep for `req`, or until the absolute monotonic time `req` with `TIMER_ABSTIME`
+///
+/// If woken early by a signal, it fails with `-EINTR`, a relative sleep
+/// storing the time left in `rem`; a `req` with `nsec` out of range, or too
+/// far out to count in microseconds, fails with `-EINVAL`.
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: *const TimeSpec,
+    rem: *mut TimeSpec,
+) -> isize {
+    if clock_id != CLOCK_MONOTONIC {
+        return -EINVAL;
+    }
+    let req = match copy_from_user(req) {
+        Some(req) => req,
+        None => return -EFAULT,
+    };
+    let req_us = match req.as_us() {
+        Some(req_us) => req_us,
+        None => return -EINVAL,
+    };
+    let now = get_time_us();
+    let wake_time = if flags & TIMER_ABSTIME != 0 {
+        req_us
+    } else {
+        match now.checked_add(req_us) {
+            Some(wake_time) => wake_time,
+            None => return -EINVAL,
+        }
+    };
+    // a signal already pending would not wake it up
+    if wake_time > now && !current_signal_pending() {
+        sleep_current_and_run_next(wake_time);
+    }
+    let left = wake_time.saturating_sub(get_time_us());
+    if left == 0 {
+        return 0;
+    }
+    let left = TimeSpec {
+        sec: left / 1_000_000,
+        nsec: left % 1_000_000 * 1_000,
+    };
+    if flags & TIMER_ABSTIME == 0 && !rem.is_null() && !copy_to_user(rem, &left) {
+        return -EFAULT;
+    }
+    -EINTR
+}
+
+/// get the address of the kernel-maintained time page
+pub fn sys_get_time_page() -> isize {
+    time_page_addr() as isize
+}
+
 /// YOUR JOB: Finish sys_task_info to pass testcases
-pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
+///
+/// Also tells where the time of the task went into `*times`, unless null.
+/// Likewise for the traps it took into `*traps`.
+pub fn sys_task_info(ti: *mut TaskInfo, times: *mut TaskTimes, traps: *mut TrapStats) -> isize {
+    if !traps.is_null() && !copy_to_user(traps, &get_current_trap_stats()) {
+        return -EFAULT;
+    }
+    if !times.is_null() {
+        let (run_us, wait_us, block_us) = get_current_task_times();
+        let task_times = TaskTimes {
+            run_us,
+            wait_us,
+            block_us,
+        };
+        if !copy_to_user(times, &task_times) {
+            return -EFAULT;
+        }
+    }
     let time = get_time_elapsed();
     let syscall_times = get_current_task_syscall_accounting();
     let status = get_current_task_status();
-    unsafe {
-        *ti = TaskInfo {
-            status,
-            syscall_times,
-            time
-        }
+    let info = TaskInfo {
+        status,
+        syscall_times,
+        time,
+    };
+    if !copy_to_user(ti, &info) {
+        return -EFAULT;
     }
     0
     // -1
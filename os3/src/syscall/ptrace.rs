@@ -0,0 +1,94 @@
+//! Process tracing syscall
+
+use super::errno::Errno::{EFAULT, EINTR, EINVAL, EPERM, ESRCH};
+use crate::loader::{get_app_range, get_user_stack_range};
+use crate::task::{
+    current_privileged, current_signal_pending, ptrace_attach, ptrace_detach, ptrace_get_regs,
+    ptrace_resume, ptrace_set_regs, ptrace_step, ptrace_stopped, ptrace_wait, task_alive,
+    PtraceEvent,
+};
+use crate::trap::TrapContext;
+use crate::uaccess::{copy_from_user, copy_to_user, user_buffer};
+use core::mem::size_of;
+
+const PTRACE_PEEKDATA: usize = 2;
+const PTRACE_POKEDATA: usize = 5;
+const PTRACE_CONT: usize = 7;
+const PTRACE_SINGLESTEP: usize = 9;
+const PTRACE_GETREGS: usize = 12;
+const PTRACE_SETREGS: usize = 13;
+const PTRACE_ATTACH: usize = 16;
+const PTRACE_DETACH: usize = 17;
+const PTRACE_SYSCALL: usize = 24;
+/// block until the tracee stops, standing in for `waitpid`
+const PTRACE_WAIT: usize = 0x4300;
+
+/// Whether `addr` is an aligned word of the user memory of task `pid`.
+fn user_word(pid: usize, addr: usize) -> bool {
+    let word = core::mem::size_of::<usize>();
+    addr % word == 0
+        && [get_app_range(pid), get_user_stack_range(pid)]
+            .iter()
+            .any(|&(start, end)| start <= addr && addr + word <= end)
+}
+
+/// Trace task `pid` as a debugger would, see [`crate::task::PtraceEvent`].
+///
+/// Registers and memory can only be accessed while `pid` is stopped; `addr`
+/// is an address in `pid`, `data` a value or an address in the caller.
+/// Only privileged tasks may trace.
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    if !current_privileged() {
+        return -EPERM;
+    }
+    // checked up front, a stop is consumed by the time it is written
+    let data_size = match request {
+        PTRACE_WAIT => size_of::<PtraceEvent>(),
+        PTRACE_GETREGS | PTRACE_SETREGS => size_of::<TrapContext>(),
+        PTRACE_PEEKDATA => size_of::<usize>(),
+        _ => 0,
+    };
+    if data_size != 0 && data != 0 && !user_buffer(data, data_size) {
+        return -EFAULT;
+    }
+    let ok = match request {
+        PTRACE_ATTACH => ptrace_attach(pid),
+        PTRACE_DETACH => ptrace_detach(pid),
+        PTRACE_CONT => ptrace_resume(pid, false),
+        PTRACE_SYSCALL => ptrace_resume(pid, true),
+        PTRACE_SINGLESTEP => ptrace_step(pid),
+        PTRACE_WAIT => match ptrace_wait(pid) {
+            Some(event) if data != 0 => copy_to_user(data as *mut PtraceEvent, &event),
+            Some(_) => true,
+            None => false,
+        },
+        PTRACE_GETREGS => match ptrace_get_regs(pid) {
+            Some(regs) if data != 0 => copy_to_user(data as *mut TrapContext, &regs),
+            _ => false,
+        },
+        PTRACE_SETREGS => match copy_from_user(data as *const TrapContext) {
+            Some(regs) => ptrace_set_regs(pid, &regs),
+            None => false,
+        },
+        // there is a single address space, the tracee's memory is right there
+        PTRACE_PEEKDATA if ptrace_stopped(pid) && user_word(pid, addr) => {
+            copy_to_user(data as *mut usize, unsafe { &*(addr as *const usize) })
+        }
+        PTRACE_POKEDATA if ptrace_stopped(pid) && user_word(pid, addr) => {
+            unsafe {
+                *(addr as *mut usize) = data;
+            }
+            true
+        }
+        _ => false,
+    };
+    if ok {
+        0
+    } else if request == PTRACE_WAIT && current_signal_pending() {
+        -EINTR
+    } else if !task_alive(pid) {
+        -ESRCH
+    } else {
+        -EINVAL
+    }
+}
@@ -0,0 +1,34 @@
+//! Randomness syscalls
+
+use super::errno::Errno::{EFAULT, EINVAL};
+use crate::random::fill_bytes;
+use crate::uaccess::copy_slice_to_user;
+
+/// don't block, accepted for compatibility since the pool never blocks
+const GRND_NONBLOCK: u32 = 1;
+/// draw from the blocking pool, which is the same pool here
+const GRND_RANDOM: u32 = 2;
+/// bytes drawn at a time
+const CHUNK: usize = 256;
+
+/// fill `buf[..len]` with random bytes, returning how many were filled
+/// before any outside the caller's memory, or `-EFAULT` if none were
+pub fn sys_getrandom(buf: *mut u8, len: usize, flags: u32) -> isize {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM) != 0 {
+        return -EINVAL;
+    }
+    let mut chunk = [0u8; CHUNK];
+    let mut filled = 0;
+    while filled < len {
+        let n = (len - filled).min(CHUNK);
+        fill_bytes(&mut chunk[..n]);
+        if !copy_slice_to_user(buf.wrapping_add(filled), &chunk[..n]) {
+            break;
+        }
+        filled += n;
+    }
+    if filled == 0 && len > 0 {
+        return -EFAULT;
+    }
+    filled as isize
+}
@@ -0,0 +1,141 @@
+//! Batched syscall submission ring
+//!
+//! A task registers a ring buffer in its own memory with `sys_ring_setup`,
+//! then queues requests in the submission queue and has all of them
+//! processed by a single `sys_ring_enter`, instead of trapping once per
+//! request. The buffer starts with a [`RingHeader`], followed by `entries`
+//! [`RingSqe`]s and then `entries` [`RingCqe`]s. `entries` must be a power of
+//! two; head and tail counters wrap freely and are masked on use.
+
+use super::errno::Errno::{EFAULT, EINVAL};
+use super::fs::{sys_read, sys_write};
+use crate::task::{get_current_ring, set_current_ring};
+use crate::uaccess::{copy_from_user, copy_to_user, user_buffer};
+use core::mem::size_of;
+
+pub const RING_OP_NOP: u32 = 0;
+pub const RING_OP_WRITE: u32 = 1;
+pub const RING_OP_READ: u32 = 2;
+
+const MAX_RING_ENTRIES: usize = 256;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+/// ring counters shared with userspace
+pub struct RingHeader {
+    /// next submission the kernel consumes
+    pub sq_head: u32,
+    /// next submission slot userspace fills
+    pub sq_tail: u32,
+    /// next completion userspace consumes
+    pub cq_head: u32,
+    /// next completion slot the kernel fills
+    pub cq_tail: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+/// submission queue entry
+pub struct RingSqe {
+    pub opcode: u32,
+    pub fd: u32,
+    pub buf: usize,
+    pub len: usize,
+    /// copied to the completion untouched
+    pub user_data: usize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+/// completion queue entry
+pub struct RingCqe {
+    pub user_data: usize,
+    /// return value of the request
+    pub result: isize,
+}
+
+/// Bytes of a ring of `entries` slots per queue, header included.
+fn ring_size(entries: usize) -> usize {
+    size_of::<RingHeader>() + entries * (size_of::<RingSqe>() + size_of::<RingCqe>())
+}
+
+/// Register the ring at `addr` with `entries` slots per queue. The whole
+/// ring must be memory of the caller, or it fails with `-EFAULT`.
+pub fn sys_ring_setup(addr: usize, entries: usize) -> isize {
+    if addr == 0
+        || addr % core::mem::align_of::<RingSqe>() != 0
+        || !entries.is_power_of_two()
+        || entries > MAX_RING_ENTRIES
+    {
+        return -EINVAL;
+    }
+    if !user_buffer(addr, ring_size(entries)) {
+        return -EFAULT;
+    }
+    let header = RingHeader {
+        sq_head: 0,
+        sq_tail: 0,
+        cq_head: 0,
+        cq_tail: 0,
+    };
+    if !copy_to_user(addr as *mut RingHeader, &header) {
+        return -EFAULT;
+    }
+    set_current_ring(addr, entries);
+    0
+}
+
+/// Process up to `to_submit` queued requests, returning how many were
+/// consumed. Requests are read and completions written through
+/// [`crate::uaccess`], and the ring checked again, so that it fails with
+/// `-EFAULT` rather than touch memory that isn't the caller's.
+pub fn sys_ring_enter(to_submit: usize) -> isize {
+    let (addr, entries) = match get_current_ring() {
+        Some(ring) => ring,
+        None => return -EINVAL,
+    };
+    if !user_buffer(addr, ring_size(entries)) {
+        return -EFAULT;
+    }
+    let header_ptr = addr as *mut RingHeader;
+    let sqes = (addr + size_of::<RingHeader>()) as *const RingSqe;
+    let cqes = sqes.wrapping_add(entries) as *mut RingCqe;
+    let mask = entries as u32 - 1;
+    let mut header = match copy_from_user(header_ptr) {
+        Some(header) => header,
+        None => return -EFAULT,
+    };
+    let mut submitted = 0;
+    while submitted < to_submit
+        && header.sq_head != header.sq_tail
+        && (header.cq_tail.wrapping_sub(header.cq_head) as usize) < entries
+    {
+        let sqe = match copy_from_user(sqes.wrapping_add((header.sq_head & mask) as usize)) {
+            Some(sqe) => sqe,
+            None => return -EFAULT,
+        };
+        let result = match sqe.opcode {
+            RING_OP_NOP => 0,
+            RING_OP_READ => sys_read(sqe.fd as usize, sqe.buf as *mut u8, sqe.len),
+            RING_OP_WRITE => sys_write(sqe.fd as usize, sqe.buf as *const u8, sqe.len),
+            _ => -EINVAL,
+        };
+        let cqe = RingCqe {
+            user_data: sqe.user_data,
+            result,
+        };
+        if !copy_to_user(cqes.wrapping_add((header.cq_tail & mask) as usize), &cqe) {
+            return -EFAULT;
+        }
+        header.sq_head = header.sq_head.wrapping_add(1);
+        header.cq_tail = header.cq_tail.wrapping_add(1);
+        submitted += 1;
+    }
+    // only the counters the kernel owns, userspace may be moving the others
+    let sq_head = unsafe { core::ptr::addr_of_mut!((*header_ptr).sq_head) };
+    let cq_tail = unsafe { core::ptr::addr_of_mut!((*header_ptr).cq_tail) };
+    if !copy_to_user(sq_head, &header.sq_head) || !copy_to_user(cq_tail, &header.cq_tail) {
+        return -EFAULT;
+    }
+    submitted as isize
+}
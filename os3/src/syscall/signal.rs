@@ -0,0 +1,75 @@
+//! Signal-related syscalls
+
+use super::errno::Errno::{EFAULT, EINVAL, EPERM, ESRCH};
+use crate::task::{
+    current_privileged, finish_current_signal_handler, get_current_task, get_pgid,
+    raise_group_signal, raise_signal, set_current_signal_action, set_current_signal_mask,
+    SignalAction, SignalFlags,
+};
+use crate::uaccess::{copy_from_user, copy_to_user, user_buffer};
+use core::mem::size_of;
+
+/// send signal `signum` to task `pid`, or to the tasks of process group
+/// `-pid` if negative; only privileged tasks may signal tasks other than
+/// themselves, or groups other than their own
+pub fn sys_kill(pid: isize, signum: usize) -> isize {
+    let current = get_current_task();
+    let own = if pid < 0 {
+        get_pgid(current) == Some(pid.unsigned_abs())
+    } else {
+        pid as usize == current
+    };
+    if !own && !current_privileged() {
+        return -EPERM;
+    }
+    let signal = match SignalFlags::from_signum(signum) {
+        Some(signal) => signal,
+        None => return -EINVAL,
+    };
+    let ok = if pid < 0 {
+        raise_group_signal(pid.unsigned_abs(), signal) > 0
+    } else {
+        raise_signal(pid as usize, signal)
+    };
+    if ok {
+        0
+    } else {
+        -ESRCH
+    }
+}
+
+/// install `action` for `signum`, saving the previous one to `old_action`
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    let action = match copy_from_user(action) {
+        Some(action) => action,
+        None => return -EFAULT,
+    };
+    // checked first, not to install the action and then fail
+    if !old_action.is_null() && !user_buffer(old_action as usize, size_of::<SignalAction>()) {
+        return -EFAULT;
+    }
+    match set_current_signal_action(signum, action) {
+        Some(old) if old_action.is_null() || copy_to_user(old_action, &old) => 0,
+        Some(_) => -EFAULT,
+        None => -EINVAL,
+    }
+}
+
+/// replace the signal mask, returning the previous one
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    let mask = SignalFlags::from_bits_truncate(mask) - SignalFlags::SIGKILL;
+    set_current_signal_mask(mask).bits() as isize
+}
+
+/// return from a signal handler to the interrupted context
+pub fn sys_sigreturn() -> isize {
+    if finish_current_signal_handler() {
+        0
+    } else {
+        -EINVAL
+    }
+}
@@ -0,0 +1,51 @@
+//! Kernel tunable syscalls
+
+use super::errno::Errno::{EFAULT, EINVAL, ENOENT, EPERM};
+use crate::sysctl;
+use crate::task::current_privileged;
+use crate::uaccess::{copy_from_user, copy_slice_from_user, copy_to_user, user_buffer};
+use core::mem::size_of;
+
+/// longest tunable name taken
+const NAME_MAX: usize = 64;
+
+/// Read tunable named by `name[..len]` into `old`, then set it to `*new`.
+/// Either pointer may be null to skip that half; only privileged tasks may
+/// set tunables.
+pub fn sys_sysctl(name: *const u8, len: usize, old: *mut usize, new: *const usize) -> isize {
+    if !new.is_null() && !current_privileged() {
+        return -EPERM;
+    }
+    if len > NAME_MAX {
+        return -EINVAL;
+    }
+    let mut buf = [0u8; NAME_MAX];
+    if !copy_slice_from_user(&mut buf[..len], name) {
+        return -EFAULT;
+    }
+    let name = match core::str::from_utf8(&buf[..len]) {
+        Ok(name) => name,
+        Err(_) => return -EINVAL,
+    };
+    let value = match sysctl::get(name) {
+        Some(value) => value,
+        None => return -ENOENT,
+    };
+    // checked first, not to set the tunable and then fail
+    if !old.is_null() && !user_buffer(old as usize, size_of::<usize>()) {
+        return -EFAULT;
+    }
+    if !new.is_null() {
+        let new = match copy_from_user(new) {
+            Some(new) => new,
+            None => return -EFAULT,
+        };
+        if !sysctl::set(name, new) {
+            return -EINVAL;
+        }
+    }
+    if !old.is_null() && !copy_to_user(old, &value) {
+        return -EFAULT;
+    }
+    0
+}
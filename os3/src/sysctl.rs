@@ -0,0 +1,83 @@
+//! Runtime kernel tunables
+//!
+//! Subsystems [`register`] their knobs under a dotted name (`timer.tick_hz`),
+//! and everybody else reads and writes them by name, from the command line
+//! or `sys_sysctl`. Every value is a `usize`; what it means is up to the
+//! subsystem, whose setter also rejects values it can't take.
+//!
+//! The registry has a fixed capacity so that it can be used before the heap
+//! is ready.
+
+use crate::sync::UPSafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+
+/// capacity of the registry
+const MAX_TUNABLES: usize = 16;
+
+/// A named value owned by some subsystem
+#[derive(Copy, Clone)]
+pub struct Tunable {
+    pub name: &'static str,
+    get: fn() -> usize,
+    set: fn(usize) -> bool,
+}
+
+lazy_static! {
+    static ref TUNABLES: UPSafeCell<[Option<Tunable>; MAX_TUNABLES]> =
+        unsafe { UPSafeCell::new([None; MAX_TUNABLES]) };
+}
+
+/// Register tunable `name`, read with `get` and written with `set`.
+pub fn register(name: &'static str, get: fn() -> usize, set: fn(usize) -> bool) {
+    let mut tunables = TUNABLES.exclusive_access();
+    assert!(
+        tunables.iter().flatten().all(|t| t.name != name),
+        "tunable {} registered twice",
+        name
+    );
+    let slot = tunables
+        .iter_mut()
+        .find(|t| t.is_none())
+        .expect("too many tunables");
+    *slot = Some(Tunable { name, get, set });
+}
+
+/// Set a statistics counter, which only takes 0 to reset it.
+pub fn reset_counter(counter: &AtomicUsize, value: usize) -> bool {
+    if value != 0 {
+        return false;
+    }
+    counter.store(0, Ordering::Relaxed);
+    true
+}
+
+/// find tunable `name`, released from the registry before use so that
+/// getters and setters may touch it
+fn find(name: &str) -> Option<Tunable> {
+    TUNABLES
+        .exclusive_access()
+        .iter()
+        .flatten()
+        .find(|t| t.name == name)
+        .copied()
+}
+
+/// Get the value of tunable `name`.
+pub fn get(name: &str) -> Option<usize> {
+    find(name).map(|t| (t.get)())
+}
+
+/// Set tunable `name` to `value`, fails if there is no such tunable or it
+/// doesn't accept `value`.
+pub fn set(name: &str, value: usize) -> bool {
+    find(name).map_or(false, |t| (t.set)(value))
+}
+
+/// Call `f` with the name and value of every tunable.
+pub fn for_each(mut f: impl FnMut(&'static str, usize)) {
+    let tunables = *TUNABLES.exclusive_access();
+    for t in tunables.iter().flatten() {
+        f(t.name, (t.get)());
+    }
+}
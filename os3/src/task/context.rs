@@ -10,7 +10,7 @@ pub struct TaskContext {
 }
 
 impl TaskContext {
-    pub fn zero_init() -> Self {
+    pub const fn zero_init() -> Self {
         Self {
             ra: 0,
             sp: 0,
@@ -27,4 +27,12 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+    /// Start running `entry` on the stack at `sp` when switched to.
+    pub fn goto_entry(entry: usize, sp: usize) -> Self {
+        Self {
+            ra: entry,
+            sp,
+            s: [0; 12],
+        }
+    }
 }
@@ -0,0 +1,48 @@
+//! Task groups sharing a CPU bandwidth cap
+//!
+//! Every task belongs to one group, group 0 by default. The tasks of a group
+//! together may only run for `quota` percent of every `GROUP_PERIOD_US`; once
+//! they used it up, the scheduler passes them over until the next period.
+
+/// number of task groups
+pub const MAX_TASK_GROUPS: usize = 4;
+/// length of a quota period, in microseconds
+pub const GROUP_PERIOD_US: usize = 100_000;
+
+#[derive(Copy, Clone)]
+/// a task group and its bandwidth accounting
+pub struct TaskGroup {
+    /// share of every period the group may run, in percent
+    pub quota: usize,
+    /// running time used in the current period, in microseconds
+    used: usize,
+    /// start of the current period, in microseconds
+    period_start: usize,
+}
+
+impl TaskGroup {
+    /// A group without a cap.
+    pub const fn new() -> Self {
+        Self {
+            quota: 100,
+            used: 0,
+            period_start: 0,
+        }
+    }
+
+    /// Account `run_time` of running at time `now`.
+    pub fn charge(&mut self, now: usize, run_time: usize) {
+        if now >= self.period_start + GROUP_PERIOD_US {
+            self.period_start = now - (now - self.period_start) % GROUP_PERIOD_US;
+            self.used = 0;
+        }
+        self.used += run_time;
+    }
+
+    /// Whether the group has used up its quota at time `now`.
+    pub fn throttled(&self, now: usize) -> bool {
+        self.quota < 100
+            && now < self.period_start + GROUP_PERIOD_US
+            && self.used >= GROUP_PERIOD_US / 100 * self.quota
+    }
+}
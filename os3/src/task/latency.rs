@@ -0,0 +1,47 @@
+//! Scheduling latency histograms
+//!
+//! The latency of a run is how long the task waited `Ready` before it was
+//! switched in. It is recorded per task and for the whole system.
+
+/// number of histogram buckets
+pub const LATENCY_BUCKETS: usize = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+/// Scheduling latencies in microseconds. Bucket `n > 0` counts latencies in
+/// `[2^(n-1), 2^n)`, bucket 0 those under a microsecond, and the last bucket
+/// also everything longer.
+pub struct LatencyHistogram {
+    pub buckets: [u32; LATENCY_BUCKETS],
+    /// sum of all latencies
+    pub total_us: usize,
+    /// longest latency
+    pub max_us: usize,
+}
+
+impl LatencyHistogram {
+    /// Count a latency of `us` microseconds.
+    pub fn record(&mut self, us: usize) {
+        let bucket = (usize::BITS - us.leading_zeros()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        self.total_us += us;
+        self.max_us = self.max_us.max(us);
+    }
+
+    /// Print the non-empty buckets.
+    pub fn print(&self) {
+        let runs: u32 = self.buckets.iter().sum();
+        if runs == 0 {
+            return;
+        }
+        println!(
+            "[kernel] scheduling latency: {} runs, avg {}us, max {}us",
+            runs,
+            self.total_us / runs as usize,
+            self.max_us
+        );
+        for (n, &count) in self.buckets.iter().enumerate().filter(|(_, &c)| c != 0) {
+            println!("[kernel]   < {:>5}us {:>8}", 1usize << n, count);
+        }
+    }
+}
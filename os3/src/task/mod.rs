@@ -10,19 +10,93 @@
 //! might not be what you expect.
 
 mod context;
+mod group;
+mod latency;
+mod ptrace;
+pub mod replay;
+mod rlimit;
+mod sched;
+mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::config::{MAX_APP_NUM, MAX_SYSCALL_NUM};
-use crate::loader::{get_num_app, init_app_cx};
-use crate::sync::UPSafeCell;
-use lazy_static::*;
+use crate::appmeta::get_app_meta;
+use crate::cmdline::{app_privileged, app_selected, sched_policy};
+use crate::config::{MAX_HARTS, MAX_SYSCALL_NUM};
+use crate::hart::{hart_id, online_harts};
+use crate::ktrace::{self, Event};
+use crate::loader::{
+    app_loaded, free_kernel_stack, get_app_range, get_num_app, get_user_stack_range, init_app_cx,
+};
+use crate::perf::{PerfCounters, PerfEvent};
+use crate::sync::{LazyInit, UPSafeCell};
+use crate::sysctl;
+use crate::trap::TrapContext;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use group::{TaskGroup, MAX_TASK_GROUPS};
+pub use latency::{LatencyHistogram, LATENCY_BUCKETS};
+pub use ptrace::{
+    PtraceEvent, PTRACE_EVENT_EXIT, PTRACE_EVENT_FAULT, PTRACE_EVENT_STEP, PTRACE_EVENT_SYSCALL,
+};
+pub use rlimit::{RLimit, RESIDENT_BYTES, RLIMIT_CPU, RLIMIT_RSS};
+pub use sched::Policy;
+use sched::Scheduler;
+pub use signal::{SignalAction, SignalFlags, MAX_SIG, SIG_DFL, SIG_IGN};
 pub use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
+pub use task::{
+    TaskControlBlock, TaskStatus, TrapStats, EXIT_TIMED_OUT, MAX_FDS, NICE_MAX, NICE_MIN,
+    PRIORITY_LEVELS,
+};
 
 pub use context::TaskContext;
-use crate::timer::get_time_us;
+use crate::timer::{
+    add_timer, get_time_us, handle_timer_interrupt, program_next_trigger, TimerKind,
+};
+use riscv::register::scause::Trap;
+use riscv::register::sip;
+
+/// `task.end_action`: shut down, telling QEMU whether any app failed: exited
+/// itself with a nonzero code, didn't load, or was left stopped. Apps the
+/// kernel killed don't count, as the `ch2b_bad_*` ones are meant to be.
+pub const END_ACTION_SHUTDOWN: usize = 0;
+/// `task.end_action`: keep the kernel idling
+pub const END_ACTION_IDLE: usize = 1;
+/// `task.end_action`: panic, as the kernel always used to
+pub const END_ACTION_PANIC: usize = 2;
+
+/// what to do once all tasks have exited
+static END_ACTION: AtomicUsize = AtomicUsize::new(END_ACTION_SHUTDOWN);
+
+/// Register the tunables of the task manager.
+pub fn init() {
+    sysctl::register(
+        "task.end_action",
+        || END_ACTION.load(Ordering::Relaxed),
+        |action| {
+            if action > END_ACTION_PANIC {
+                return false;
+            }
+            END_ACTION.store(action, Ordering::Relaxed);
+            true
+        },
+    );
+}
+
+/// Id of the `Running` task, `usize::MAX` before the first task starts.
+///
+/// Mirrors `current_task` of [`TaskManager`] so that the logger can read it
+/// without borrowing `TASK_MANAGER`, which may already be borrowed.
+static RUNNING_TASK: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+/// time each hart spent idle waiting for a task to run, in microseconds,
+/// see [`TaskManager::wait_for_next_task`]
+static IDLE_TIME: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
 
 /// The task manager, where all the tasks are managed.
 ///
@@ -43,24 +117,49 @@ pub struct TaskManager {
 /// The task manager inner in 'UPSafeCell'
 struct TaskManagerInner {
     /// task list
-    tasks: [TaskControlBlock; MAX_APP_NUM],
+    tasks: Vec<Arc<TaskControlBlock>>,
     /// id of current `Running` task
     current_task: usize,
+    /// CPU quota accounting of the task groups
+    groups: [TaskGroup; MAX_TASK_GROUPS],
+    /// scheduling latencies of all tasks
+    latency: LatencyHistogram,
+    /// task to run next whatever its priority, see [`yield_to_and_run_next`]
+    yield_to: Option<usize>,
+    /// the scheduling policy chosen with `sched=`
+    scheduler: Box<dyn Scheduler>,
 }
 
-lazy_static! {
-    /// a `TaskManager` instance through lazy_static!
-    pub static ref TASK_MANAGER: TaskManager = {
+/// the `TaskManager` instance, set up by [`create_tasks`]
+pub static TASK_MANAGER: LazyInit<TaskManager> = LazyInit::new("TASK_MANAGER");
+
+impl TaskManager {
+    /// Create a task for each app, `Ready` if the app was loaded.
+    fn new() -> Self {
         let num_app = get_num_app();
-        let mut tasks = [TaskControlBlock {
-            task_cx: TaskContext::zero_init(),
-            task_status: TaskStatus::UnInit,
-            first_start_time:0,
-            syscall_accounting: [0; MAX_SYSCALL_NUM]
-        }; MAX_APP_NUM];
-        for (i, t) in tasks.iter_mut().enumerate().take(num_app) {
-            t.task_cx = TaskContext::goto_restore(init_app_cx(i));
-            t.task_status = TaskStatus::Ready;
+        let mut tasks = Vec::new();
+        for i in 0..num_app {
+            let task = TaskControlBlock::default();
+            let mut t = task.inner_exclusive_access();
+            t.privileged = app_privileged(i);
+            t.pgid = i;
+            if let Some(meta) = get_app_meta(i) {
+                t.nice = meta.nice.unwrap_or(t.nice);
+                t.rlimits.stack.cur = meta.stack_size.unwrap_or(t.rlimits.stack.cur);
+            }
+            if !app_loaded(i) {
+                // nothing to run, the loader has told why unless the app
+                // was left out on purpose; an app whose own metadata asks for
+                // more than the kernel has is refused as asked, no failure
+                let refused = get_app_meta(i).map_or(false, |meta| meta.unmet().is_some());
+                t.task_status = TaskStatus::Exited;
+                t.exit_code = if app_selected(i) && !refused { -1 } else { 0 };
+            } else {
+                t.task_cx = TaskContext::goto_restore(init_app_cx(i));
+                t.make_ready(get_time_us());
+            }
+            drop(t);
+            tasks.push(Arc::new(task));
         }
         TaskManager {
             num_app,
@@ -68,24 +167,70 @@ lazy_static! {
                 UPSafeCell::new(TaskManagerInner {
                     tasks,
                     current_task: 0,
+                    groups: [TaskGroup::new(); MAX_TASK_GROUPS],
+                    latency: LatencyHistogram::default(),
+                    yield_to: None,
+                    scheduler: sched_policy().scheduler(),
                 })
             },
         }
-    };
-}
+    }
 
-impl TaskManager {
-    /// Run the first task in task list.
+    /// Get task `task_id`, if there is such a task.
+    fn task(&self, task_id: usize) -> Option<Arc<TaskControlBlock>> {
+        self.inner.exclusive_access().tasks.get(task_id).cloned()
+    }
+
+    /// Whether there is a task `task_id` that has not exited.
+    fn task_alive(&self, task_id: usize) -> bool {
+        self.task(task_id).map_or(false, |task| {
+            task.inner_exclusive_access().task_status != TaskStatus::Exited
+        })
+    }
+
+    /// Get the current `Running` task.
+    fn current(&self) -> Arc<TaskControlBlock> {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].clone()
+    }
+
+    /// Run task `first` of the task list, `0` unless chosen on the command line.
     ///
     /// Generally, the first task in task list is an idle task (we call it zero process later).
     /// But in ch3, we load apps statically, so the first task is a real app.
-    fn run_first_task(&self) -> ! {
+    fn run_first_task(&self, first: usize) -> ! {
+        // no apps were linked in, there is nothing to pick from
+        if self.num_app == 0 {
+            self.all_tasks_exited();
+        }
         let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_status = TaskStatus::Running;
-        task0.first_start_time = get_time_us();
-        let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
+        // the chosen app may have failed to load
+        let first = match (first..first + self.num_app)
+            .map(|id| id % self.num_app)
+            .find(|id| inner.tasks[*id].inner_exclusive_access().task_status == TaskStatus::Ready)
+        {
+            Some(first) => first,
+            None => {
+                drop(inner);
+                self.all_tasks_exited();
+            }
+        };
+        inner.current_task = first;
+        let mut first_task = inner.tasks[first].inner_exclusive_access();
+        first_task.task_status = TaskStatus::Running;
+        first_task.first_start_time = get_time_us();
+        first_task.last_start_time = first_task.first_start_time;
+        first_task.user_enter = first_task.first_start_time;
+        first_task.perf_start = PerfCounters::now();
+        let latency = first_task.first_start_time - first_task.ready_time;
+        first_task.latency.record(latency);
+        first_task.wait_time += latency;
+        let next_task_cx_ptr = &first_task.task_cx as *const TaskContext;
+        drop(first_task);
+        inner.latency.record(latency);
+        RUNNING_TASK.store(first, Ordering::Relaxed);
         drop(inner);
+        ktrace::record(Event::Switch, first);
         let mut _unused = TaskContext::zero_init();
         // before this, we should drop local variables that must be dropped manually
         unsafe {
@@ -96,83 +241,1222 @@ impl TaskManager {
 
     /// Change the status of current `Running` task into `Ready`.
     fn mark_current_suspended(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+        self.current().inner_exclusive_access().make_ready(get_time_us());
+    }
+
+    /// Change the status of current `Running` task into `Ready`, as it is to
+    /// be preempted.
+    fn mark_current_preempted(&self) {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.make_ready(get_time_us());
+        task.preempted = true;
+    }
+
+    /// Note the current task is being killed by the kernel.
+    fn mark_current_killed(&self) {
+        self.current().inner_exclusive_access().killed = true;
     }
 
     /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+    fn mark_current_exited(&self, exit_code: i32) {
+        let current = self.get_current_task();
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.task_status = TaskStatus::Exited;
+        task.exit_code = exit_code;
+        for event in task.perf_events.iter_mut() {
+            if let Some(event) = event.take() {
+                let event_idx = event.event_idx;
+                println!(
+                    "[kernel] task {} perf event {:#x}: {}",
+                    current,
+                    event_idx,
+                    event.release()
+                );
+            }
+        }
+        if let Some((addr, orig)) = task.step_patch.take() {
+            ptrace::unpatch_ebreak(addr, orig);
+        }
+        let tracer = task.tracer;
+        drop(task);
+        if let Some(tracer) = tracer {
+            self.wake_tracer(tracer, current);
+        }
+        // don't leave tracees stopped for good
+        for id in 0..self.num_app {
+            let tracee = self.task(id).unwrap();
+            let traced = tracee.inner_exclusive_access().tracer == Some(current);
+            if traced {
+                self.ptrace_detach(id);
+            }
+        }
+    }
+
+    /// Change the status of current `Running` task into `Blocked` until `wake_time`.
+    fn mark_current_sleeping(&self, wake_time: usize) {
+        let current = self.get_current_task();
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.task_status = TaskStatus::Blocked;
+        task.block_start = get_time_us();
+        task.wake_time = wake_time;
+        add_timer(wake_time, current, TimerKind::Sleep);
+    }
+
+    /// Wake the tasks of `sleepers`, `(task_id, wake_time)` pairs, that
+    /// are still sleeping until `wake_time`, in priority order. Returns how
+    /// many were.
+    fn wakeup_sleeping_tasks(&self, sleepers: &[(usize, usize)]) -> usize {
+        let mut due: Vec<(isize, Arc<TaskControlBlock>)> = sleepers
+            .iter()
+            .filter_map(|&(task_id, wake_time)| {
+                let task = self.task(task_id)?;
+                let t = task.inner_exclusive_access();
+                if t.task_status != TaskStatus::Blocked || t.wake_time != wake_time {
+                    return None;
+                }
+                let priority = t.effective_priority();
+                drop(t);
+                Some((priority, task))
+            })
+            .collect();
+        // stable, so equal priorities keep the order of their deadlines
+        due.sort_by_key(|(priority, _)| *priority);
+        let now = get_time_us();
+        for (_, task) in due.iter() {
+            let mut task = task.inner_exclusive_access();
+            task.make_ready(now);
+            task.wake_time = 0;
+        }
+        due.len()
+    }
+
+    /// Change the status of current `Running` task into `Blocked` until
+    /// woken with [`wakeup_task`].
+    fn mark_current_wait_queued(&self) {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.task_status = TaskStatus::Blocked;
+        task.block_start = get_time_us();
+        task.wait_queued = true;
+    }
+
+    /// Wake task `task_id` if it is blocked in a wait queue. Returns whether
+    /// it was.
+    fn wakeup_task(&self, task_id: usize) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.task_status != TaskStatus::Blocked || !task.wait_queued {
+            return false;
+        }
+        task.wait_queued = false;
+        task.make_ready(get_time_us());
+        true
+    }
+
+    /// Wait for a `Blocked` task to become `Ready`, or a throttled task
+    /// group to get new quota, and return the id of the task to run.
+    ///
+    /// Returns `None` if no task is blocked or throttled, as nothing could
+    /// change then.
+    fn wait_for_next_task(&self) -> Option<usize> {
+        loop {
+            let inner = self.inner.exclusive_access();
+            if !inner.tasks.iter().any(|t| {
+                let status = t.inner_exclusive_access().task_status;
+                status == TaskStatus::Blocked || status == TaskStatus::Ready
+            }) {
+                return None;
+            }
+            drop(inner);
+            program_next_trigger();
+            let idle_start = get_time_us();
+            // interrupts are off in the kernel, but `wfi` still returns once
+            // the timer interrupt is pending
+            unsafe {
+                riscv::asm::wfi();
+            }
+            IDLE_TIME[hart_id()].fetch_add(get_time_us() - idle_start, Ordering::Relaxed);
+            if sip::read().stimer() {
+                handle_timer_interrupt();
+            }
+            crate::workqueue::run_pending();
+            if let Some(next) = self.find_next_task() {
+                return Some(next);
+            }
+        }
     }
 
     /// Find next task to run and return task id.
     ///
-    /// In this case, we only return the first `Ready` task in task list.
+    /// We return the `Ready` task allowed on this hart and not throttled by
+    /// its group that the scheduler picks, unless one has to run first.
     fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
+        if self.num_app == 0 {
+            return None;
+        }
+        let mut inner = self.inner.exclusive_access();
+        if let Some(target) = replay::next_replayed() {
+            if inner.tasks.get(target).map_or(false, |t| {
+                t.inner_exclusive_access().task_status == TaskStatus::Ready
+            }) {
+                return Some(target);
+            }
+            replay::diverged(target);
+        }
+        if let Some(target) = inner.yield_to.take() {
+            if inner.tasks[target].inner_exclusive_access().task_status == TaskStatus::Ready {
+                return Some(target);
+            }
+        }
         let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+        let hart = 1 << hart_id();
+        let now = get_time_us();
+        let mut runnable = Vec::new();
+        for id in (current + 1..current + self.num_app + 1).map(|id| id % self.num_app) {
+            let task = inner.tasks[id].inner_exclusive_access();
+            if task.task_status == TaskStatus::Ready
+                && task.cpu_mask & hart != 0
+                && !inner.groups[task.group].throttled(now)
+            {
+                runnable.push(task.runnable(id));
+            }
+        }
+        inner.scheduler.pick_next(&runnable)
+    }
+
+    /// Tell the scheduler if the current task is switching away for being
+    /// preempted.
+    fn notify_switch_out(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = inner.tasks[current].inner_exclusive_access();
+        let preempted = task.task_status == TaskStatus::Ready && task.preempted;
+        drop(task);
+        if preempted {
+            inner.scheduler.on_tick(current);
+        }
+    }
+
+    /// Have `task_id` run next if it could run on this hart now, returning
+    /// whether it could.
+    fn set_yield_to(&self, task_id: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if task_id >= self.num_app || task_id == inner.current_task {
+            return false;
+        }
+        let task = inner.tasks[task_id].inner_exclusive_access();
+        let runnable = task.task_status == TaskStatus::Ready
+            && task.cpu_mask & (1 << hart_id()) != 0
+            && !inner.groups[task.group].throttled(get_time_us());
+        drop(task);
+        if runnable {
+            inner.yield_to = Some(task_id);
+        }
+        runnable
+    }
+
+    /// Age the `Ready` tasks passed over in favour of `next`, and reset the
+    /// age of `next`.
+    fn age_tasks(inner: &mut TaskManagerInner, next: usize) {
+        for (id, task) in inner.tasks.iter().enumerate() {
+            let mut task = task.inner_exclusive_access();
+            if id == next {
+                task.age = 0;
+            } else if task.task_status == TaskStatus::Ready {
+                task.age += 1;
+            }
+        }
+    }
+
+    /// Add `increment` to the nice value of the current task.
+    fn nice_current(&self, increment: isize) {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.nice = task.nice.saturating_add(increment).clamp(NICE_MIN, NICE_MAX);
+    }
+
+    /// The nice value of task `task_id`, `None` if there is no such task.
+    fn get_nice(&self, task_id: usize) -> Option<isize> {
+        self.task(task_id).map(|task| task.inner_exclusive_access().nice)
+    }
+
+    /// The nice value task `task_id` lends the holder of a mutex it waits
+    /// on, `None` if it has exited.
+    fn lent_nice(&self, task_id: usize) -> Option<isize> {
+        let task = self.task(task_id)?;
+        let task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Exited {
+            return None;
+        }
+        Some(task.boosted_nice())
+    }
+
+    /// Set the nice value task `task_id` inherits from its waiters.
+    fn set_inherited_nice(&self, task_id: usize, nice: Option<isize>) {
+        if let Some(task) = self.task(task_id) {
+            task.inner_exclusive_access().inherited_nice = nice;
+        }
+    }
+
+    /// Whether the current task needs preempting at the end of its time
+    /// slice: someone else is waiting for the CPU, or its group is capped.
+    fn needs_slice_tick(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        let group = inner.tasks[inner.current_task].inner_exclusive_access().group;
+        inner.groups[group].quota < 100
+            || inner
+                .tasks
+                .iter()
+                .any(|t| t.inner_exclusive_access().task_status == TaskStatus::Ready)
+    }
+
+    /// Free the kernel stacks of exited tasks, except the current one, which
+    /// is still running on its own.
+    fn reap_exited_tasks(&self) {
+        let inner = self.inner.exclusive_access();
+        for (id, task) in inner.tasks.iter().enumerate() {
+            if id != inner.current_task
+                && task.inner_exclusive_access().task_status == TaskStatus::Exited
+            {
+                free_kernel_stack(id);
+            }
+        }
     }
 
     /// Switch current `Running` task to the task we have found,
     /// or there is no `Ready` task and we can exit with all applications completed
     fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
+        // don't leave the output of the current task behind in the buffer
+        crate::console::flush();
+        self.reap_exited_tasks();
+        self.notify_switch_out();
+        if let Some(next) = self
+            .find_next_task()
+            .or_else(|| self.wait_for_next_task())
+        {
             let mut inner = self.inner.exclusive_access();
             let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            if inner.tasks[next].first_start_time == 0 {
-                inner.tasks[next].first_start_time = get_time_us();
+            let now = get_time_us();
+            let perf_now = PerfCounters::now();
+            let mut current_tcb = inner.tasks[current].inner_exclusive_access();
+            let run_time = now - current_tcb.last_start_time;
+            current_tcb.run_time += run_time;
+            if core::mem::take(&mut current_tcb.preempted) {
+                current_tcb.preemptions += 1;
+            } else {
+                current_tcb.voluntary_switches += 1;
             }
+            let perf_start = current_tcb.perf_start;
+            current_tcb.perf.add_delta(&perf_start, &perf_now);
+            current_tcb.perf_events.iter_mut().flatten().for_each(PerfEvent::pause);
+            let group = current_tcb.group;
+            let current_task_cx_ptr = &mut current_tcb.task_cx as *mut TaskContext;
+            drop(current_tcb);
+            inner.groups[group].charge(now, run_time);
+            Self::age_tasks(&mut inner, next);
+            let mut next_tcb = inner.tasks[next].inner_exclusive_access();
+            next_tcb.task_status = TaskStatus::Running;
+            if next_tcb.first_start_time == 0 {
+                next_tcb.first_start_time = now;
+                next_tcb.user_enter = now;
+            }
+            next_tcb.last_start_time = now;
+            let latency = now - next_tcb.ready_time;
+            next_tcb.latency.record(latency);
+            next_tcb.wait_time += latency;
+            next_tcb.perf_start = perf_now;
+            next_tcb.perf_events.iter().flatten().for_each(PerfEvent::resume);
+            let next_task_cx_ptr = &next_tcb.task_cx as *const TaskContext;
+            drop(next_tcb);
+            inner.latency.record(latency);
             inner.current_task = next;
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
+            RUNNING_TASK.store(next, Ordering::Relaxed);
             drop(inner);
+            // the next task may need a time slice the current one didn't
+            program_next_trigger();
+            ktrace::record(Event::Switch, next);
+            replay::switched_to(next);
             // before this, we should drop local variables that must be dropped manually
             unsafe {
                 __switch(current_task_cx_ptr, next_task_cx_ptr);
             }
             // go back to user mode
         } else {
-            panic!("All applications completed!");
+            self.all_tasks_exited();
+        }
+    }
+
+    /// Act on the end of the workload as chosen by the `task.end_action`
+    /// tunable.
+    fn all_tasks_exited(&self) -> ! {
+        replay::report();
+        let failed = {
+            let inner = self.inner.exclusive_access();
+            inner.latency.print();
+            print_utilization();
+            let mut failed = false;
+            for (id, task) in inner.tasks.iter().enumerate() {
+                let task = task.inner_exclusive_access();
+                // nobody is left to resume it
+                if task.task_status == TaskStatus::Stopped {
+                    println!("[kernel] task {} was left stopped", id);
+                    failed = true;
+                }
+                failed |= task.exit_code != 0 && !task.killed;
+            }
+            failed
+        };
+        match END_ACTION.load(Ordering::Relaxed) {
+            END_ACTION_SHUTDOWN => {
+                println!("[kernel] All applications completed!");
+                crate::teardown::power_off(failed)
+            }
+            END_ACTION_IDLE => {
+                println!("[kernel] All applications completed, idling.");
+                crate::console::flush();
+                loop {
+                    unsafe {
+                        riscv::asm::wfi();
+                    }
+                    if sip::read().stimer() {
+                        handle_timer_interrupt();
+                    }
+                }
+            }
+            _ => panic!("All applications completed!"),
         }
     }
 
     // LAB1: Try to implement your function to update or get task info!
     fn get_current_task_first_start_time(&self) -> usize{
-        let mut inner = self.inner.exclusive_access();
-        let current_task_id = inner.current_task;
-        inner.tasks[current_task_id].first_start_time
+        self.current().inner_exclusive_access().first_start_time
     }
 
     fn get_current_task_syscall_accounting(&self) -> [u32;MAX_SYSCALL_NUM]{
-        let mut inner = self.inner.exclusive_access();
-        let current_task_id = inner.current_task;
-        inner.tasks[current_task_id].syscall_accounting
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        let mut syscall_times = [0; MAX_SYSCALL_NUM];
+        for (&id, &count) in task.syscall_accounting.iter() {
+            if id < MAX_SYSCALL_NUM {
+                syscall_times[id] = count;
+            }
+        }
+        syscall_times
     }
 
     fn update_current_task_syscall_accounting(&self, syscall_id:usize){
-        let mut inner = self.inner.exclusive_access();
-        let current_task_id = inner.current_task;
-        inner.tasks[current_task_id].syscall_accounting[syscall_id] += 1;
+        let task = self.current();
+        *task.inner_exclusive_access().syscall_accounting.entry(syscall_id).or_insert(0) += 1;
+
+    }
+    /// Get the id of the current `Running` task.
+    fn get_current_task(&self) -> usize {
+        self.inner.exclusive_access().current_task
+    }
+
+    /// Get the harts task `task_id` may run on.
+    fn get_affinity(&self, task_id: usize) -> Option<usize> {
+        Some(self.task(task_id)?.inner_exclusive_access().cpu_mask)
+    }
 
+    /// Get the scheduling latencies of task `task_id`, or of all tasks.
+    fn get_latency(&self, task_id: Option<usize>) -> Option<LatencyHistogram> {
+        match task_id {
+            Some(id) => Some(self.task(id)?.inner_exclusive_access().latency),
+            None => Some(self.inner.exclusive_access().latency),
+        }
+    }
+
+    /// Restrict task `task_id` to the harts in `mask`.
+    ///
+    /// Fails if there is no such task or none of the harts is online.
+    fn set_affinity(&self, task_id: usize, mask: usize) -> bool {
+        match self.task(task_id) {
+            Some(task) if mask & online_harts() != 0 => {
+                task.inner_exclusive_access().cpu_mask = mask;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move task `task_id` into task group `group`.
+    fn set_task_group(&self, task_id: usize, group: usize) -> bool {
+        match self.task(task_id) {
+            Some(task) if group < MAX_TASK_GROUPS => {
+                task.inner_exclusive_access().group = group;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Cap task group `group` to `quota` percent of the CPU.
+    fn set_group_quota(&self, group: usize, quota: usize) -> bool {
+        if group >= MAX_TASK_GROUPS || quota == 0 || quota > 100 {
+            return false;
+        }
+        self.inner.exclusive_access().groups[group].quota = quota;
+        true
+    }
+
+    /// Add `signal` to the pending signals of task `task_id`.
+    ///
+    /// Fails if there is no such task or it has already exited.
+    fn raise_signal(&self, task_id: usize, signal: SignalFlags) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Exited {
+            return false;
+        }
+        task.signals |= signal;
+        // a deliverable signal interrupts whatever the task waits for, the
+        // waiting code seeing it pending; a tracer has to resume a stopped
+        // tracee for anything but a kill
+        let deliverable = signal.uncatchable() || !task.signal_mask.contains(signal);
+        let trace_stopped = task.trace_stop.is_some();
+        if task.task_status == TaskStatus::Blocked
+            && deliverable
+            && (!trace_stopped || signal.uncatchable())
+        {
+            task.make_ready(get_time_us());
+            task.wake_time = 0;
+            task.wait_queued = false;
+            task.ptrace_wait = None;
+            task.trace_stop = None;
+        }
+        // and a stopped task has to run to be killed
+        if task.task_status == TaskStatus::Stopped && signal == SignalFlags::SIGKILL {
+            task.make_ready(get_time_us());
+        }
+        true
+    }
+
+    /// Whether task `task_id` has a signal pending that interrupts waiting,
+    /// as it woke it up or would have, see [`raise_signal`].
+    fn signal_pending(&self, task_id: usize) -> bool {
+        self.task(task_id).map_or(false, |task| {
+            let task = task.inner_exclusive_access();
+            task.signals.contains(SignalFlags::SIGKILL)
+                || !(task.signals - task.signal_mask).is_empty()
+        })
+    }
+
+    /// Stop task `task_id` if it is `Ready` or `Running`, or once it wakes
+    /// if it is `Blocked`, returning whether it could be. The current task
+    /// still has to switch away.
+    fn suspend_task(&self, task_id: usize) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        match task.task_status {
+            TaskStatus::Ready | TaskStatus::Running => {
+                task.task_status = TaskStatus::Stopped;
+                true
+            }
+            TaskStatus::Blocked => {
+                task.stop_pending = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Make task `task_id` `Ready` if it is stopped, or drop the stop it is
+    /// to take on waking, returning whether it was either.
+    fn resume_task(&self, task_id: usize) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.stop_pending {
+            task.stop_pending = false;
+            return true;
+        }
+        if task.task_status != TaskStatus::Stopped {
+            return false;
+        }
+        task.make_ready(get_time_us());
+        true
+    }
+
+    /// Get the process group of task `task_id`.
+    fn get_pgid(&self, task_id: usize) -> Option<usize> {
+        self.task(task_id).map(|task| task.inner_exclusive_access().pgid)
+    }
+
+    /// Move task `task_id` to process group `pgid`, either a new one led by
+    /// the task or one another live task is in.
+    ///
+    /// Fails if there is no such task or group, or the task has exited.
+    fn set_pgid(&self, task_id: usize, pgid: usize) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        if pgid != task_id && self.group_members(pgid).is_empty() {
+            return false;
+        }
+        let mut task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Exited {
+            return false;
+        }
+        task.pgid = pgid;
+        true
+    }
+
+    /// Get the ids of the live tasks in process group `pgid`.
+    fn group_members(&self, pgid: usize) -> Vec<usize> {
+        (0..self.num_app)
+            .filter(|&id| {
+                let task = self.task(id).unwrap();
+                let task = task.inner_exclusive_access();
+                task.pgid == pgid && task.task_status != TaskStatus::Exited
+            })
+            .collect()
+    }
+
+    /// Kill task `task_id` if it is still alive `timeout_us` from now, or
+    /// never if 0.
+    ///
+    /// Fails if there is no such task or it has already exited.
+    fn set_timeout(&self, task_id: usize, timeout_us: usize) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Exited {
+            return false;
+        }
+        task.timeout_expire = if timeout_us == 0 {
+            0
+        } else {
+            get_time_us().saturating_add(timeout_us)
+        };
+        if task.timeout_expire != 0 {
+            add_timer(task.timeout_expire, task_id, TimerKind::Timeout);
+        }
+        true
+    }
+
+    /// Handle the expiry of a timeout of task `task_id`.
+    fn timeout_expired(&self, task_id: usize, expire_us: usize) {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        let mut inner = task.inner_exclusive_access();
+        // the timeout may have been re-armed or disarmed since
+        if inner.timeout_expire != expire_us || inner.task_status == TaskStatus::Exited {
+            return;
+        }
+        inner.timeout_expire = 0;
+        inner.timed_out = true;
+        drop(inner);
+        warn!("[kernel] task {} timed out, killing it", task_id);
+        self.raise_signal(task_id, SignalFlags::SIGKILL);
+    }
+
+    /// Whether the current task may make the privileged syscalls.
+    fn current_privileged(&self) -> bool {
+        self.current().inner_exclusive_access().privileged
+    }
+
+    /// Whether the current task is being killed for missing its deadline.
+    fn current_timed_out(&self) -> bool {
+        self.current().inner_exclusive_access().timed_out
+    }
+
+    /// Make the current task trace task `task_id`.
+    ///
+    /// Fails if there is no such task but the current one, or it has exited
+    /// or is traced already.
+    fn ptrace_attach(&self, task_id: usize) -> bool {
+        let current = self.get_current_task();
+        let task = match self.task(task_id) {
+            Some(task) if task_id != current => task,
+            _ => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Exited || task.tracer.is_some() {
+            return false;
+        }
+        task.tracer = Some(current);
+        task.trace_syscalls = false;
+        true
+    }
+
+    /// Get task `task_id` if the current task traces it.
+    fn tracee(&self, task_id: usize) -> Option<Arc<TaskControlBlock>> {
+        let task = self.task(task_id)?;
+        let traced = task.inner_exclusive_access().tracer == Some(self.get_current_task());
+        if traced {
+            Some(task)
+        } else {
+            None
+        }
+    }
+
+    /// Get the event tracee `task_id` of the current task is stopped on, if
+    /// it is stopped. An exit is reported once, detaching the tracee.
+    fn ptrace_event(&self, task_id: usize) -> Option<Option<PtraceEvent>> {
+        let task = self.tracee(task_id)?;
+        let mut task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Exited {
+            task.tracer = None;
+            return Some(Some(PtraceEvent {
+                kind: PTRACE_EVENT_EXIT,
+                detail: task.exit_code as usize,
+            }));
+        }
+        Some(task.trace_stop)
+    }
+
+    /// Block the current task until its tracee `task_id` stops or exits.
+    fn mark_current_ptrace_waiting(&self, task_id: usize) {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.task_status = TaskStatus::Blocked;
+        task.block_start = get_time_us();
+        task.ptrace_wait = Some(task_id);
+    }
+
+    /// Wake task `tracer` if it waits for its tracee `tracee`.
+    fn wake_tracer(&self, tracer: usize, tracee: usize) {
+        let task = match self.task(tracer) {
+            Some(task) => task,
+            None => return,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.task_status == TaskStatus::Blocked && task.ptrace_wait == Some(tracee) {
+            task.ptrace_wait = None;
+            task.make_ready(get_time_us());
+        }
+    }
+
+    /// Stop the current task on `event` with registers `cx`, if it is traced
+    /// and, for syscalls, its tracer asked for them. Returns whether it did.
+    fn mark_current_trace_stopped(&self, cx: &TrapContext, event: PtraceEvent) -> bool {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        let tracer = match task.tracer {
+            Some(tracer) if event.kind != PTRACE_EVENT_SYSCALL || task.trace_syscalls => tracer,
+            _ => return false,
+        };
+        // whatever it stopped for, the step is over
+        if let Some((addr, orig)) = task.step_patch.take() {
+            ptrace::unpatch_ebreak(addr, orig);
+        }
+        task.task_status = TaskStatus::Blocked;
+        task.block_start = get_time_us();
+        task.trace_stop = Some(event);
+        task.trace_regs = Some(*cx);
+        drop(task);
+        self.wake_tracer(tracer, self.get_current_task());
+        true
+    }
+
+    /// Put back the instruction under the single-step `c.ebreak` the current
+    /// task trapped on at `addr`. Returns whether there was one.
+    fn finish_current_step(&self, addr: usize) -> bool {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        match task.step_patch {
+            Some((patched, orig)) if patched == addr => {
+                task.step_patch = None;
+                ptrace::unpatch_ebreak(addr, orig);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take the registers of the current task back after a stop.
+    fn take_current_trace_regs(&self) -> Option<TrapContext> {
+        self.current().inner_exclusive_access().trace_regs.take()
+    }
+
+    /// Resume tracee `task_id` of the current task if stopped, stopping on
+    /// the next syscall if `syscalls`.
+    fn ptrace_resume(&self, task_id: usize, syscalls: bool) -> bool {
+        let task = match self.tracee(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        task.trace_syscalls = syscalls;
+        if task.trace_stop.take().is_some() {
+            task.make_ready(get_time_us());
+        }
+        true
+    }
+
+    /// Resume stopped tracee `task_id` of the current task for one
+    /// instruction, by patching the instruction after it with `c.ebreak`.
+    fn ptrace_step(&self, task_id: usize) -> bool {
+        let task = match self.tracee(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        let next = match (task.trace_stop, task.trace_regs.as_ref()) {
+            (Some(_), Some(regs)) => ptrace::next_pc(regs),
+            _ => return false,
+        };
+        // only the code of the task itself can be patched
+        let (start, end) = get_app_range(task_id);
+        if next % 2 != 0 || next < start || next + 2 > end {
+            return false;
+        }
+        task.step_patch = Some((next, ptrace::patch_ebreak(next)));
+        task.trace_syscalls = false;
+        task.trace_stop = None;
+        task.make_ready(get_time_us());
+        true
+    }
+
+    /// Stop tracing task `task_id`, resuming it if stopped.
+    fn ptrace_detach(&self, task_id: usize) -> bool {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        task.tracer = None;
+        task.trace_syscalls = false;
+        if let Some((addr, orig)) = task.step_patch.take() {
+            ptrace::unpatch_ebreak(addr, orig);
+        }
+        if task.trace_stop.take().is_some() {
+            task.make_ready(get_time_us());
+        }
+        true
+    }
+
+    /// Get the registers of stopped tracee `task_id` of the current task.
+    fn ptrace_get_regs(&self, task_id: usize) -> Option<TrapContext> {
+        let task = self.tracee(task_id)?;
+        let task = task.inner_exclusive_access();
+        task.trace_stop?;
+        task.trace_regs
+    }
+
+    /// Set the general purpose registers and `sepc` of stopped tracee
+    /// `task_id` of the current task. `sstatus` is kept, so the task stays
+    /// in user mode.
+    fn ptrace_set_regs(&self, task_id: usize, cx: &TrapContext) -> bool {
+        let task = match self.tracee(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let mut task = task.inner_exclusive_access();
+        if task.trace_stop.is_none() {
+            return false;
+        }
+        match task.trace_regs.as_mut() {
+            Some(regs) => {
+                regs.x = cx.x;
+                regs.sepc = cx.sepc;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Raise `signal` on the current task for a fault at `addr`, if a user
+    /// handler is there to catch it.
+    ///
+    /// Returns `false` if the task has to be killed instead: the signal is
+    /// not handled or masked, or a handler is running already, as returning
+    /// to the faulting instruction would only fault again.
+    fn raise_current_fault(&self, signal: SignalFlags, addr: usize) -> bool {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        let handler = task.signal_actions[signal.lowest_signum().unwrap()].handler;
+        if handler == SIG_DFL
+            || handler == SIG_IGN
+            || task.signal_mask.contains(signal)
+            || task.handling_sig.is_some()
+        {
+            return false;
+        }
+        task.signals |= signal;
+        task.fault_addr = addr;
+        true
+    }
+
+    /// Install `action` for `signum` on the current task, returning the old one.
+    fn set_current_signal_action(
+        &self,
+        signum: usize,
+        action: SignalAction,
+    ) -> Option<SignalAction> {
+        let signal = SignalFlags::from_signum(signum)?;
+        if signal.uncatchable() {
+            return None;
+        }
+        let task = self.current();
+        let actions = &mut task.inner_exclusive_access().signal_actions;
+        Some(core::mem::replace(&mut actions[signum], action))
+    }
+
+    /// Replace the signal mask of the current task, returning the old one.
+    fn set_current_signal_mask(&self, mask: SignalFlags) -> SignalFlags {
+        let task = self.current();
+        let signal_mask = &mut task.inner_exclusive_access().signal_mask;
+        core::mem::replace(signal_mask, mask)
+    }
+
+    /// Finish the running signal handler of the current task.
+    ///
+    /// The interrupted trap context is put back by the next
+    /// [`handle_signals`] on the way out of the trap handler.
+    fn finish_current_signal_handler(&self) -> bool {
+        let task = self.current();
+        let handling_sig = task.inner_exclusive_access().handling_sig.take();
+        handling_sig.is_some()
+    }
+
+    /// Deliver the pending signals of the current task through `cx`.
+    ///
+    /// Returns the signal number if the task has to be terminated.
+    fn handle_current_signals(&self, cx: &mut TrapContext) -> Option<usize> {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        if task.handling_sig.is_none() {
+            if let Some(backup) = task.trap_cx_backup.take() {
+                *cx = backup;
+                task.signal_mask = task.signal_mask_backup;
+            }
+        }
+        if task.signals.contains(SignalFlags::SIGKILL) {
+            return Some(SignalFlags::SIGKILL.lowest_signum().unwrap());
+        }
+        if task.handling_sig.is_some() {
+            return None;
+        }
+        while let Some(signum) = (task.signals - task.signal_mask).lowest_signum() {
+            let signal = SignalFlags::from_signum(signum).unwrap();
+            task.signals.remove(signal);
+            let action = task.signal_actions[signum];
+            match action.handler {
+                SIG_IGN => continue,
+                SIG_DFL if signal.ignored_by_default() => continue,
+                SIG_DFL => return Some(signum),
+                handler => {
+                    task.trap_cx_backup = Some(*cx);
+                    task.signal_mask_backup = task.signal_mask;
+                    task.handling_sig = Some(signum);
+                    task.signal_mask |= action.mask | signal;
+                    cx.sepc = handler;
+                    cx.x[10] = signum;
+                    cx.x[11] = if (SignalFlags::SIGSEGV | SignalFlags::SIGILL).contains(signal) {
+                        task.fault_addr
+                    } else {
+                        0
+                    };
+                    break;
+                }
+            }
+        }
+        None
+    }
+
+    /// Arm `ITIMER_REAL` of the current task, returning the old
+    /// `(interval, remaining)` pair. A zero `value` disarms the timer.
+    fn set_current_itimer(&self, interval: usize, value: usize) -> (usize, usize) {
+        let current = self.get_current_task();
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        let now = get_time_us();
+        let old_remaining = task.itimer_expire.saturating_sub(now);
+        let old = (task.itimer_interval, old_remaining);
+        task.itimer_interval = interval;
+        task.itimer_expire = if value == 0 { 0 } else { now.saturating_add(value) };
+        if task.itimer_expire != 0 {
+            add_timer(task.itimer_expire, current, TimerKind::ITimer);
+        }
+        old
     }
+
+    /// Handle the expiry of an `ITIMER_REAL` deadline of task `task_id`.
+    fn itimer_expired(&self, task_id: usize, expire_us: usize) {
+        let task = match self.task(task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        let mut task = task.inner_exclusive_access();
+        // the timer may have been re-armed or disarmed since
+        if task.itimer_expire != expire_us || task.task_status == TaskStatus::Exited {
+            return;
+        }
+        task.signals |= SignalFlags::SIGALRM;
+        if task.itimer_interval == 0 {
+            task.itimer_expire = 0;
+            return;
+        }
+        // skip periods we have missed instead of firing them in a burst
+        let now = get_time_us();
+        task.itimer_expire = expire_us.max(now).saturating_add(task.itimer_interval);
+        add_timer(task.itimer_expire, task_id, TimerKind::ITimer);
+    }
+
+    /// Register the syscall ring of the current task.
+    fn set_current_ring(&self, addr: usize, entries: usize) {
+        self.current().inner_exclusive_access().ring = Some((addr, entries));
+    }
+
+    /// Get the syscall ring registered by the current task.
+    fn get_current_ring(&self) -> Option<(usize, usize)> {
+        self.current().inner_exclusive_access().ring
+    }
+
+    /// Get the status flags of descriptor `fd` of the current task, if open.
+    fn get_current_fd_flags(&self, fd: usize) -> Option<u32> {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        if fd >= task.rlimits.nofile.cur {
+            return None;
+        }
+        task.fd_flags.get(fd).copied()
+    }
+
+    /// Set the status flags of descriptor `fd` of the current task, failing
+    /// if there is no such descriptor.
+    fn set_current_fd_flags(&self, fd: usize, flags: u32) -> bool {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        if fd >= task.rlimits.nofile.cur {
+            return false;
+        }
+        match task.fd_flags.get_mut(fd) {
+            Some(slot) => {
+                *slot = flags;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the limit of `resource` of the current task, if it is supported.
+    fn get_current_rlimit(&self, resource: usize) -> Option<RLimit> {
+        self.current().inner_exclusive_access().rlimits.get(resource)
+    }
+
+    /// Set the limit of `resource` of the current task, if it is supported.
+    fn set_current_rlimit(&self, resource: usize, limit: RLimit) -> bool {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        match task.rlimits.get_mut(resource) {
+            Some(slot) => *slot = limit,
+            None => return false,
+        }
+        if resource == RLIMIT_CPU {
+            task.rlimits.xcpu_next_s = limit.cur;
+        }
+        true
+    }
+
+    /// Enforce the CPU time and stack limits of the current task, about to
+    /// return to user mode with stack pointer `sp`.
+    fn check_current_rlimits(&self, sp: usize) {
+        let task_id = self.get_current_task();
+        let task = self.current();
+        let mut inner = task.inner_exclusive_access();
+        let cpu_s = (inner.run_time + get_time_us() - inner.last_start_time) / 1_000_000;
+        let limits = &mut inner.rlimits;
+        let cpu_signal = if cpu_s >= limits.cpu.max {
+            Some(SignalFlags::SIGKILL)
+        } else if cpu_s >= limits.xcpu_next_s {
+            limits.xcpu_next_s = cpu_s + 1;
+            Some(SignalFlags::SIGXCPU)
+        } else {
+            None
+        };
+        let stack_limit = limits.stack.cur;
+        drop(inner);
+        if let Some(signal) = cpu_signal {
+            warn!("[kernel] task {} used up its CPU time limit", task_id);
+            self.raise_signal(task_id, signal);
+        }
+        let (stack_bottom, stack_top) = get_user_stack_range(task_id);
+        if (stack_bottom..stack_top).contains(&sp) && stack_top - sp > stack_limit {
+            warn!("[kernel] task {} overran its stack limit", task_id);
+            self.raise_signal(task_id, SignalFlags::SIGSEGV);
+        }
+    }
+
+    /// Get the total time the current task has spent `Running`, in microseconds.
+    fn get_current_task_run_time(&self) -> usize {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        task.run_time + get_time_us() - task.last_start_time
+    }
+
+    /// Get the time the current task has spent `Running`, `Ready` and
+    /// `Blocked`, in microseconds.
+    fn get_current_task_times(&self) -> (usize, usize, usize) {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        let run_time = task.run_time + get_time_us() - task.last_start_time;
+        (run_time, task.wait_time, task.block_time)
+    }
+
+    /// Get the time the current task has spent `Running` in user mode and in
+    /// the kernel, in microseconds.
+    fn get_current_task_cpu_times(&self) -> (usize, usize) {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        let run_time = task.run_time + get_time_us() - task.last_start_time;
+        (task.user_time, run_time.saturating_sub(task.user_time))
+    }
+
+    /// Get how many times the current task gave up the CPU itself and was
+    /// preempted.
+    fn get_current_task_switches(&self) -> (usize, usize) {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        (task.voluntary_switches, task.preemptions)
+    }
+
+    /// Whether the saved user context of the current task may be read or
+    /// rewritten before it returns to user mode: by its tracer, or to
+    /// deliver or return from a signal handler.
+    fn current_context_exposed(&self) -> bool {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        task.tracer.is_some()
+            || !task.signals.is_empty()
+            || task.handling_sig.is_some()
+            || task.trap_cx_backup.is_some()
+    }
+
+    /// Count the tasks that have not exited.
+    fn live_tasks(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .filter(|t| t.inner_exclusive_access().task_status != TaskStatus::Exited)
+            .count()
+    }
+
+    /// Get how many times tasks gave up the CPU themselves and were
+    /// preempted, and how many are `Ready` at each effective priority, from
+    /// `NICE_MIN` on.
+    fn get_sched_stat(&self) -> (usize, usize, [u32; PRIORITY_LEVELS]) {
+        let inner = self.inner.exclusive_access();
+        let (mut voluntary, mut preemptions) = (0, 0);
+        let mut ready = [0; PRIORITY_LEVELS];
+        for task in inner.tasks.iter() {
+            let task = task.inner_exclusive_access();
+            voluntary += task.voluntary_switches;
+            preemptions += task.preemptions;
+            if task.task_status == TaskStatus::Ready {
+                ready[(task.effective_priority() - NICE_MIN) as usize] += 1;
+            }
+        }
+        (voluntary, preemptions, ready)
+    }
+
+    /// Count the time since the current task last returned to user mode as
+    /// user time, on a trap.
+    fn charge_current_user_time(&self) {
+        let task = self.current();
+        let mut task = task.inner_exclusive_access();
+        task.user_time += get_time_us() - task.user_enter;
+    }
+
+    /// Forget the syscalls, traps and user time of task `task_id`.
+    fn clear_accounting(&self, task_id: usize) {
+        if let Some(task) = self.task(task_id) {
+            let mut task = task.inner_exclusive_access();
+            task.syscall_accounting.clear();
+            task.trap_stats = TrapStats::default();
+            task.user_time = 0;
+            task.user_enter = 0;
+        }
+    }
+
+    /// Count a trap of cause `cause` from the current task.
+    fn count_current_trap(&self, cause: Trap) {
+        self.current().inner_exclusive_access().trap_stats.record(cause);
+    }
+
+    /// Count a syscall with no such id from the current task.
+    fn count_current_unknown_syscall(&self) {
+        self.current().inner_exclusive_access().trap_stats.unknown_syscalls += 1;
+    }
+
+    /// Get the traps the current task took.
+    fn get_current_trap_stats(&self) -> TrapStats {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        TrapStats {
+            preemptions: task.preemptions,
+            ..task.trap_stats
+        }
+    }
+
+    /// Note the current task returns to user mode now.
+    fn mark_current_user_enter(&self) {
+        self.current().inner_exclusive_access().user_enter = get_time_us();
+    }
+
+    fn get_current_task_perf(&self) -> PerfCounters {
+        let task = self.current();
+        let task = task.inner_exclusive_access();
+        let mut perf = task.perf;
+        perf.add_delta(&task.perf_start, &PerfCounters::now());
+        perf
+    }
+
+    /// Start counting `event_idx` for the current task, returning a handle.
+    fn open_current_perf_event(&self, event_idx: usize, event_data: usize) -> Option<usize> {
+        let task = self.current();
+        let events = &mut task.inner_exclusive_access().perf_events;
+        let handle = events.iter().position(|e| e.is_none())?;
+        events[handle] = Some(PerfEvent::open(event_idx, event_data)?);
+        Some(handle)
+    }
+
+    fn read_current_perf_event(&self, handle: usize) -> Option<usize> {
+        self.current()
+            .inner_exclusive_access()
+            .perf_events
+            .get(handle)
+            .copied()
+            .flatten()
+            .map(|e| e.read())
+    }
+
     fn get_current_task_status(&self) -> TaskStatus{
-        let mut inner = self.inner.exclusive_access();
-        let current_task_id = inner.current_task;
-        inner.tasks[current_task_id].task_status
+        self.current().inner_exclusive_access().task_status
         // TaskStatus::Running
     }
 }
 
+/// Create the tasks, once [`crate::loader::load_apps`] has loaded the apps.
+pub fn create_tasks() {
+    TASK_MANAGER.init(TaskManager::new());
+}
+
 /// Run the first task in task list.
 pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
+    TASK_MANAGER.run_first_task(crate::cmdline::init_app().unwrap_or(0));
 }
 
 /// Switch current `Running` task to the task we have found,
@@ -187,8 +1471,8 @@ fn mark_current_suspended() {
 }
 
 /// Change the status of current `Running` task into `Exited`.
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+fn mark_current_exited(exit_code: i32) {
+    TASK_MANAGER.mark_current_exited(exit_code);
 }
 
 /// Suspend the current 'Running' task and run the next task in task list.
@@ -197,12 +1481,79 @@ pub fn suspend_current_and_run_next() {
     run_next_task();
 }
 
+/// Stop task `task_id` until [`resume_task`], switching away if it is the
+/// current task; a `Blocked` task stops once it wakes. Returns whether it
+/// could be stopped.
+pub fn suspend_task(task_id: usize) -> bool {
+    if !TASK_MANAGER.suspend_task(task_id) {
+        return false;
+    }
+    if task_id == TASK_MANAGER.get_current_task() {
+        run_next_task();
+    }
+    true
+}
+
+/// Let task `task_id` run again if it was stopped by [`suspend_task`].
+pub fn resume_task(task_id: usize) -> bool {
+    TASK_MANAGER.resume_task(task_id)
+}
+
+/// Suspend the current 'Running' task and run task `task_id` for the rest
+/// of its time slice, or whatever task is next if `task_id` can't run.
+/// Returns whether `task_id` could.
+pub fn yield_to_and_run_next(task_id: usize) -> bool {
+    mark_current_suspended();
+    let donated = TASK_MANAGER.set_yield_to(task_id);
+    run_next_task();
+    donated
+}
+
+/// Preempt the current 'Running' task and run the next task in task list.
+pub fn preempt_current_and_run_next() {
+    TASK_MANAGER.mark_current_preempted();
+    run_next_task();
+}
+
+/// Block the current 'Running' task until `wake_time` and run the next task.
+pub fn sleep_current_and_run_next(wake_time: usize) {
+    TASK_MANAGER.mark_current_sleeping(wake_time);
+    run_next_task();
+}
+
+/// Block the current 'Running' task until [`wakeup_task`] and run the next
+/// task. The caller puts it in a wait queue first.
+pub fn block_current_and_run_next() {
+    TASK_MANAGER.mark_current_wait_queued();
+    run_next_task();
+}
+
+/// Wake task `task_id` if it is blocked in a wait queue, see
+/// [`block_current_and_run_next`]. Returns whether it was.
+pub fn wakeup_task(task_id: usize) -> bool {
+    TASK_MANAGER.wakeup_task(task_id)
+}
+
+/// Wake the tasks of `sleepers`, `(task_id, wake_time)` pairs, that are
+/// still sleeping until `wake_time`. Returns how many were.
+pub fn wakeup_sleeping_tasks(sleepers: &[(usize, usize)]) -> usize {
+    TASK_MANAGER.wakeup_sleeping_tasks(sleepers)
+}
+
 /// Exit the current 'Running' task and run the next task in task list.
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
+pub fn exit_current_and_run_next(exit_code: i32) {
+    mark_current_exited(exit_code);
+    crate::mutex::release_all(get_current_task());
     run_next_task();
 }
 
+/// Exit the current 'Running' task for a fault, a fatal signal or its
+/// deadline, with negative `exit_code`, and run the next task in task list.
+pub fn kill_current_and_run_next(exit_code: i32) {
+    TASK_MANAGER.mark_current_killed();
+    exit_current_and_run_next(exit_code);
+}
+
 // LAB1: Public functions implemented here provide interfaces.
 // You may use TASK_MANAGER member functions to handle requests.
 pub fn get_time_elapsed() -> usize{
@@ -216,4 +1567,402 @@ pub fn get_current_task_syscall_accounting() -> [u32;MAX_SYSCALL_NUM]{
 }
 pub fn get_current_task_status() -> TaskStatus{
     TASK_MANAGER.get_current_task_status()
-}
\ No newline at end of file
+}
+/// Get the total time the current task has spent `Running`, in microseconds.
+pub fn get_current_task_run_time() -> usize {
+    TASK_MANAGER.get_current_task_run_time()
+}
+
+/// Get the time the current task has spent `Running`, `Ready` and `Blocked`,
+/// in microseconds.
+pub fn get_current_task_times() -> (usize, usize, usize) {
+    TASK_MANAGER.get_current_task_times()
+}
+
+/// Get the time the current task has spent `Running` in user mode and in
+/// the kernel, in microseconds.
+pub fn get_current_task_cpu_times() -> (usize, usize) {
+    TASK_MANAGER.get_current_task_cpu_times()
+}
+
+/// Get how many times the current task gave up the CPU itself and was
+/// preempted.
+pub fn get_current_task_switches() -> (usize, usize) {
+    TASK_MANAGER.get_current_task_switches()
+}
+
+/// Whether the saved user context of the current task may be read or
+/// rewritten before it returns to user mode.
+pub fn current_context_exposed() -> bool {
+    TASK_MANAGER.current_context_exposed()
+}
+
+/// Get how many times tasks gave up the CPU themselves and were preempted,
+/// and how many are `Ready` at each effective priority.
+pub fn get_sched_stat() -> (usize, usize, [u32; PRIORITY_LEVELS]) {
+    TASK_MANAGER.get_sched_stat()
+}
+
+/// Count the tasks that have not exited.
+pub fn live_tasks() -> usize {
+    TASK_MANAGER.live_tasks()
+}
+
+/// Get the time each hart spent idle waiting for a task, in microseconds.
+pub fn get_idle_time() -> [usize; MAX_HARTS] {
+    let mut idle = [0; MAX_HARTS];
+    for (idle, hart) in idle.iter_mut().zip(IDLE_TIME.iter()) {
+        *idle = hart.load(Ordering::Relaxed);
+    }
+    idle
+}
+
+/// Print how busy the online harts were since boot.
+fn print_utilization() {
+    let uptime = get_time_us().max(1);
+    let idle = get_idle_time();
+    for hart in (0..MAX_HARTS).filter(|&h| online_harts() & (1 << h) != 0) {
+        println!(
+            "[kernel] hart {}: {}% busy, idle {}us of {}us",
+            hart,
+            100 - idle[hart].min(uptime) * 100 / uptime,
+            idle[hart],
+            uptime
+        );
+    }
+}
+
+/// Count the time the current task ran in user mode before trapping.
+pub fn charge_current_user_time() {
+    TASK_MANAGER.charge_current_user_time()
+}
+
+/// Forget the syscalls, traps and user time of task `task_id`, for the
+/// benchmarks to trap in its place before it first runs.
+pub fn clear_accounting(task_id: usize) {
+    TASK_MANAGER.clear_accounting(task_id)
+}
+
+/// Count a trap of cause `cause` from the current task.
+pub fn count_current_trap(cause: Trap) {
+    TASK_MANAGER.count_current_trap(cause)
+}
+
+/// Count a syscall with no such id from the current task.
+pub fn count_current_unknown_syscall() {
+    TASK_MANAGER.count_current_unknown_syscall()
+}
+
+/// Get the traps the current task took.
+pub fn get_current_trap_stats() -> TrapStats {
+    TASK_MANAGER.get_current_trap_stats()
+}
+
+/// Note the current task is about to return to user mode.
+pub fn mark_current_user_enter() {
+    TASK_MANAGER.mark_current_user_enter()
+}
+
+/// Get the cycles and instructions retired while the current task ran.
+pub fn get_current_task_perf() -> PerfCounters {
+    TASK_MANAGER.get_current_task_perf()
+}
+
+/// Count hardware event `event_idx` for the current task.
+pub fn open_current_perf_event(event_idx: usize, event_data: usize) -> Option<usize> {
+    TASK_MANAGER.open_current_perf_event(event_idx, event_data)
+}
+
+/// Read the count of perf event `handle` of the current task.
+pub fn read_current_perf_event(handle: usize) -> Option<usize> {
+    TASK_MANAGER.read_current_perf_event(handle)
+}
+
+/// Change the nice value of the current task by `increment`.
+pub fn nice_current(increment: isize) {
+    TASK_MANAGER.nice_current(increment)
+}
+
+/// Get the nice value of task `task_id`.
+pub fn get_nice(task_id: usize) -> Option<isize> {
+    TASK_MANAGER.get_nice(task_id)
+}
+
+/// The nice value task `task_id` lends the holder of a mutex it waits on,
+/// `None` if it has exited.
+pub fn lent_nice(task_id: usize) -> Option<isize> {
+    TASK_MANAGER.lent_nice(task_id)
+}
+
+/// Set the nice value task `task_id` inherits from its waiters.
+pub fn set_inherited_nice(task_id: usize, nice: Option<isize>) {
+    TASK_MANAGER.set_inherited_nice(task_id, nice)
+}
+
+/// Get the id of the current `Running` task.
+pub fn get_current_task() -> usize {
+    TASK_MANAGER.get_current_task()
+}
+
+/// Whether there is a task `task_id` that has not exited.
+pub fn task_alive(task_id: usize) -> bool {
+    TASK_MANAGER.task_alive(task_id)
+}
+
+/// Whether the current task needs preempting at the end of its time slice.
+pub fn needs_slice_tick() -> bool {
+    TASK_MANAGER.needs_slice_tick()
+}
+
+/// Get the id of the `Running` task without touching `TASK_MANAGER`,
+/// or `None` while the kernel is still booting.
+pub fn running_task_id() -> Option<usize> {
+    match RUNNING_TASK.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        id => Some(id),
+    }
+}
+
+/// Get the harts task `task_id` may run on.
+pub fn get_affinity(task_id: usize) -> Option<usize> {
+    TASK_MANAGER.get_affinity(task_id)
+}
+
+/// Get the scheduling latencies of task `task_id`, or of all tasks if `None`.
+pub fn get_latency(task_id: Option<usize>) -> Option<LatencyHistogram> {
+    TASK_MANAGER.get_latency(task_id)
+}
+
+/// Restrict task `task_id` to the harts in `mask`.
+pub fn set_affinity(task_id: usize, mask: usize) -> bool {
+    TASK_MANAGER.set_affinity(task_id, mask)
+}
+
+/// Move task `task_id` into task group `group`.
+pub fn set_task_group(task_id: usize, group: usize) -> bool {
+    TASK_MANAGER.set_task_group(task_id, group)
+}
+
+/// Cap task group `group` to `quota` percent of the CPU.
+pub fn set_group_quota(group: usize, quota: usize) -> bool {
+    TASK_MANAGER.set_group_quota(group, quota)
+}
+
+/// Kill task `task_id` with [`EXIT_TIMED_OUT`] if it is still alive
+/// `timeout_us` from now, or never if 0.
+pub fn set_timeout(task_id: usize, timeout_us: usize) -> bool {
+    TASK_MANAGER.set_timeout(task_id, timeout_us)
+}
+
+/// Whether the current task may make the privileged syscalls, see
+/// `privileged=` in [`crate::cmdline`].
+pub fn current_privileged() -> bool {
+    TASK_MANAGER.current_privileged()
+}
+
+/// Handle the expiry of a timeout of task `task_id`.
+pub fn timeout_expired(task_id: usize, expire_us: usize) {
+    TASK_MANAGER.timeout_expired(task_id, expire_us)
+}
+
+/// Add `signal` to the pending signals of task `task_id`.
+pub fn raise_signal(task_id: usize, signal: SignalFlags) -> bool {
+    TASK_MANAGER.raise_signal(task_id, signal)
+}
+
+/// Whether task `task_id` has a signal pending that interrupts waiting.
+///
+/// A task about to block checks it first, and checks it again when woken, as
+/// such a signal wakes it too, leaving it in whatever wait queue it was: it
+/// then takes itself out and fails with `EINTR`, dying on its way back to
+/// user mode for a kill.
+pub fn signal_pending(task_id: usize) -> bool {
+    TASK_MANAGER.signal_pending(task_id)
+}
+
+/// Whether the current task has a signal pending that interrupts waiting,
+/// see [`signal_pending`].
+pub fn current_signal_pending() -> bool {
+    signal_pending(get_current_task())
+}
+
+/// Add `signal` to the pending signals of the live tasks in process group
+/// `pgid`, returning how many there were.
+pub fn raise_group_signal(pgid: usize, signal: SignalFlags) -> usize {
+    TASK_MANAGER
+        .group_members(pgid)
+        .into_iter()
+        .filter(|&id| TASK_MANAGER.raise_signal(id, signal))
+        .count()
+}
+
+/// Get the process group of task `task_id`.
+pub fn get_pgid(task_id: usize) -> Option<usize> {
+    TASK_MANAGER.get_pgid(task_id)
+}
+
+/// Move task `task_id` to process group `pgid`, a new one led by the task
+/// or one another live task is in.
+pub fn set_pgid(task_id: usize, pgid: usize) -> bool {
+    TASK_MANAGER.set_pgid(task_id, pgid)
+}
+
+/// Make the current task trace task `task_id`.
+pub fn ptrace_attach(task_id: usize) -> bool {
+    TASK_MANAGER.ptrace_attach(task_id)
+}
+
+/// Stop tracing tracee `task_id` of the current task, resuming it if stopped.
+pub fn ptrace_detach(task_id: usize) -> bool {
+    TASK_MANAGER.tracee(task_id).is_some() && TASK_MANAGER.ptrace_detach(task_id)
+}
+
+/// Resume tracee `task_id` of the current task if stopped, stopping on the
+/// next syscall if `syscalls`.
+pub fn ptrace_resume(task_id: usize, syscalls: bool) -> bool {
+    TASK_MANAGER.ptrace_resume(task_id, syscalls)
+}
+
+/// Block until tracee `task_id` of the current task stops or exits, and get
+/// why. Returns at once if it is stopped already.
+pub fn ptrace_wait(task_id: usize) -> Option<PtraceEvent> {
+    loop {
+        match TASK_MANAGER.ptrace_event(task_id)? {
+            Some(event) => return Some(event),
+            // the tracee may take long to stop, if ever
+            None if current_signal_pending() => return None,
+            None => {
+                TASK_MANAGER.mark_current_ptrace_waiting(task_id);
+                run_next_task();
+            }
+        }
+    }
+}
+
+/// Get the registers of stopped tracee `task_id` of the current task.
+pub fn ptrace_get_regs(task_id: usize) -> Option<TrapContext> {
+    TASK_MANAGER.ptrace_get_regs(task_id)
+}
+
+/// Set the registers of stopped tracee `task_id` of the current task.
+pub fn ptrace_set_regs(task_id: usize, cx: &TrapContext) -> bool {
+    TASK_MANAGER.ptrace_set_regs(task_id, cx)
+}
+
+/// Whether task `task_id` is a stopped tracee of the current task.
+pub fn ptrace_stopped(task_id: usize) -> bool {
+    TASK_MANAGER.ptrace_get_regs(task_id).is_some()
+}
+
+/// Resume stopped tracee `task_id` of the current task for one instruction,
+/// after which it stops on [`PTRACE_EVENT_STEP`].
+pub fn ptrace_step(task_id: usize) -> bool {
+    TASK_MANAGER.ptrace_step(task_id)
+}
+
+/// Stop the current task for its tracer once the instruction it single-steps
+/// is done, if the breakpoint it trapped on at `cx.sepc` is that of the step.
+/// Returns whether it was.
+pub fn finish_single_step(cx: &mut TrapContext) -> bool {
+    if !TASK_MANAGER.finish_current_step(cx.sepc) {
+        return false;
+    }
+    let event = PtraceEvent {
+        kind: PTRACE_EVENT_STEP,
+        detail: cx.sepc,
+    };
+    trace_stop(cx, event);
+    true
+}
+
+/// Stop the current task for its tracer on `event`, if it is traced, and
+/// put back the registers the tracer left once resumed.
+pub fn trace_stop(cx: &mut TrapContext, event: PtraceEvent) {
+    if TASK_MANAGER.mark_current_trace_stopped(cx, event) {
+        run_next_task();
+        if let Some(regs) = TASK_MANAGER.take_current_trace_regs() {
+            *cx = regs;
+        }
+    }
+}
+
+/// Raise `signal` on the current task for a fault at `addr`, unless it has
+/// to be killed instead as no user handler would catch it.
+pub fn raise_current_fault(signal: SignalFlags, addr: usize) -> bool {
+    TASK_MANAGER.raise_current_fault(signal, addr)
+}
+
+/// Install `action` for `signum` on the current task, returning the old one.
+pub fn set_current_signal_action(signum: usize, action: SignalAction) -> Option<SignalAction> {
+    TASK_MANAGER.set_current_signal_action(signum, action)
+}
+
+/// Replace the signal mask of the current task, returning the old one.
+pub fn set_current_signal_mask(mask: SignalFlags) -> SignalFlags {
+    TASK_MANAGER.set_current_signal_mask(mask)
+}
+
+/// Finish the running signal handler of the current task.
+pub fn finish_current_signal_handler() -> bool {
+    TASK_MANAGER.finish_current_signal_handler()
+}
+
+/// Deliver pending signals of the current task before it returns to user mode.
+///
+/// Terminates the task if a signal with a fatal action is pending.
+pub fn handle_signals(cx: &mut TrapContext) {
+    if let Some(signum) = TASK_MANAGER.handle_current_signals(cx) {
+        if TASK_MANAGER.current_timed_out() {
+            info!("[kernel] Application timed out");
+            kill_current_and_run_next(EXIT_TIMED_OUT);
+        }
+        info!("[kernel] Application killed by signal {}", signum);
+        kill_current_and_run_next(-(signum as i32));
+    }
+}
+
+/// Arm `ITIMER_REAL` of the current task, returning the old `(interval, remaining)`.
+pub fn set_current_itimer(interval: usize, value: usize) -> (usize, usize) {
+    TASK_MANAGER.set_current_itimer(interval, value)
+}
+
+/// Handle the expiry of an `ITIMER_REAL` deadline of task `task_id`.
+pub fn itimer_expired(task_id: usize, expire_us: usize) {
+    TASK_MANAGER.itimer_expired(task_id, expire_us)
+}
+
+/// Register the syscall ring of the current task.
+pub fn set_current_ring(addr: usize, entries: usize) {
+    TASK_MANAGER.set_current_ring(addr, entries)
+}
+
+/// Get the syscall ring registered by the current task.
+pub fn get_current_ring() -> Option<(usize, usize)> {
+    TASK_MANAGER.get_current_ring()
+}
+
+/// Get the status flags of descriptor `fd` of the current task, if open.
+pub fn get_current_fd_flags(fd: usize) -> Option<u32> {
+    TASK_MANAGER.get_current_fd_flags(fd)
+}
+
+/// Set the status flags of descriptor `fd` of the current task.
+pub fn set_current_fd_flags(fd: usize, flags: u32) -> bool {
+    TASK_MANAGER.set_current_fd_flags(fd, flags)
+}
+
+/// Get the limit of `resource` of the current task, if it is supported.
+pub fn get_current_rlimit(resource: usize) -> Option<RLimit> {
+    TASK_MANAGER.get_current_rlimit(resource)
+}
+
+/// Set the limit of `resource` of the current task, if it is supported.
+pub fn set_current_rlimit(resource: usize, limit: RLimit) -> bool {
+    TASK_MANAGER.set_current_rlimit(resource, limit)
+}
+
+/// Enforce the CPU time and stack limits of the current task, about to
+/// return to user mode with stack pointer `sp`.
+pub fn check_current_rlimits(sp: usize) {
+    TASK_MANAGER.check_current_rlimits(sp)
+}
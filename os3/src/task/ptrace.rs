@@ -0,0 +1,138 @@
+//! Tracing of one task by another, for debuggers
+//!
+//! A traced task stops on faults, and on syscalls if its tracer asks for
+//! them. A stopped task is `Blocked` with the event it stopped for, until its
+//! tracer resumes it. The registers of the stopped task are a copy that the
+//! tracer may change, put back into the trap context when the task resumes.
+//!
+//! To single-step, the instruction that runs next is patched with
+//! `c.ebreak`, which is put back when the task traps on it or stops otherwise.
+
+use crate::trap::TrapContext;
+
+/// the tracee stopped on entering syscall `detail`
+pub const PTRACE_EVENT_SYSCALL: usize = 1;
+/// the tracee stopped on a fault, `detail` being `scause`
+pub const PTRACE_EVENT_FAULT: usize = 2;
+/// the tracee exited with code `detail`, and is no longer traced
+pub const PTRACE_EVENT_EXIT: usize = 3;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+/// why a traced task stopped
+pub struct PtraceEvent {
+    /// one of the `PTRACE_EVENT_*` constants
+    pub kind: usize,
+    pub detail: usize,
+}
+
+/// the tracee stopped after a single step, `detail` being the new `sepc`
+pub const PTRACE_EVENT_STEP: usize = 4;
+
+/// `c.ebreak`, patched over the next instruction to single-step
+const C_EBREAK: u16 = 0x9002;
+
+/// sign-extend the low `bits` bits of `value`
+fn sext(value: u32, bits: u32) -> usize {
+    (((value as i64) << (64 - bits)) >> (64 - bits)) as usize
+}
+
+/// Get the address of the instruction that runs after the one at `cx.sepc`,
+/// evaluating branches with the registers in `cx`.
+pub fn next_pc(cx: &TrapContext) -> usize {
+    let pc = cx.sepc;
+    let reg = |i: u32| if i == 0 { 0 } else { cx.x[i as usize] };
+    let low = unsafe { (pc as *const u16).read_volatile() } as u32;
+    if low & 0b11 != 0b11 {
+        let i = low;
+        return match (i & 0b11, (i >> 13) & 0b111) {
+            // c.j
+            (0b01, 0b101) => {
+                let imm = ((i >> 12) & 1) << 11
+                    | ((i >> 11) & 1) << 4
+                    | ((i >> 9) & 0b11) << 8
+                    | ((i >> 8) & 1) << 10
+                    | ((i >> 7) & 1) << 6
+                    | ((i >> 6) & 1) << 7
+                    | ((i >> 3) & 0b111) << 1
+                    | ((i >> 2) & 1) << 5;
+                pc.wrapping_add(sext(imm, 12))
+            }
+            // c.beqz, c.bnez
+            (0b01, funct3 @ (0b110 | 0b111)) => {
+                let zero = reg(8 + ((i >> 7) & 0b111)) == 0;
+                if zero == (funct3 == 0b110) {
+                    let imm = ((i >> 12) & 1) << 8
+                        | ((i >> 10) & 0b11) << 3
+                        | ((i >> 5) & 0b11) << 6
+                        | ((i >> 3) & 0b11) << 1
+                        | ((i >> 2) & 1) << 5;
+                    pc.wrapping_add(sext(imm, 9))
+                } else {
+                    pc + 2
+                }
+            }
+            // c.jr, c.jalr
+            (0b10, 0b100) if (i >> 2) & 0x1f == 0 && (i >> 7) & 0x1f != 0 => {
+                reg((i >> 7) & 0x1f) & !1
+            }
+            _ => pc + 2,
+        };
+    }
+    let high = unsafe { ((pc + 2) as *const u16).read_volatile() } as u32;
+    let i = low | high << 16;
+    let (rs1, rs2) = (reg((i >> 15) & 0x1f), reg((i >> 20) & 0x1f));
+    match i & 0x7f {
+        // jal
+        0x6f => {
+            let imm = ((i >> 31) & 1) << 20
+                | ((i >> 21) & 0x3ff) << 1
+                | ((i >> 20) & 1) << 11
+                | ((i >> 12) & 0xff) << 12;
+            pc.wrapping_add(sext(imm, 21))
+        }
+        // jalr
+        0x67 => rs1.wrapping_add(sext(i >> 20, 12)) & !1,
+        // beq, bne, blt, bge, bltu, bgeu
+        0x63 => {
+            let taken = match (i >> 12) & 0b111 {
+                0b000 => rs1 == rs2,
+                0b001 => rs1 != rs2,
+                0b100 => (rs1 as isize) < (rs2 as isize),
+                0b101 => (rs1 as isize) >= (rs2 as isize),
+                0b110 => rs1 < rs2,
+                0b111 => rs1 >= rs2,
+                _ => false,
+            };
+            if taken {
+                let imm = ((i >> 31) & 1) << 12
+                    | ((i >> 25) & 0x3f) << 5
+                    | ((i >> 8) & 0xf) << 1
+                    | ((i >> 7) & 1) << 11;
+                pc.wrapping_add(sext(imm, 13))
+            } else {
+                pc + 4
+            }
+        }
+        _ => pc + 4,
+    }
+}
+
+/// Put `c.ebreak` at `addr`, returning the halfword it replaces.
+pub fn patch_ebreak(addr: usize) -> u16 {
+    let ptr = addr as *mut u16;
+    unsafe {
+        let orig = ptr.read_volatile();
+        ptr.write_volatile(C_EBREAK);
+        core::arch::asm!("fence.i");
+        orig
+    }
+}
+
+/// Undo [`patch_ebreak`] at `addr`.
+pub fn unpatch_ebreak(addr: usize, orig: u16) {
+    unsafe {
+        (addr as *mut u16).write_volatile(orig);
+        core::arch::asm!("fence.i");
+    }
+}
@@ -0,0 +1,104 @@
+//! Record and replay of scheduling decisions
+//!
+//! With the `sched_record` boot option, every task the scheduler switches to
+//! is logged along with the count of timer interrupts so far, and once all
+//! tasks have exited the whole sequence is printed as a `sched_replay=`
+//! option. Booting with that option makes the scheduler pick the same tasks
+//! in the same order, as long as each is `Ready` on its turn; the first time
+//! one is not, the run has diverged, which is reported, and scheduling goes
+//! back to normal.
+//!
+//! Only the order of the switches is replayed, not where a task gets
+//! preempted, so apps that time their work differently between the runs can
+//! still diverge.
+
+use crate::sync::UPSafeCell;
+use crate::timer::get_ticks;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use lazy_static::*;
+
+enum Mode {
+    Off,
+    /// tasks switched to so far
+    Record(Vec<u8>),
+    /// tasks to switch to, and how many of them were
+    Replay(Vec<u8>, usize),
+}
+
+lazy_static! {
+    static ref MODE: UPSafeCell<Mode> = unsafe { UPSafeCell::new(Mode::Off) };
+}
+
+/// Start recording the scheduling decisions.
+pub fn start_recording() {
+    *MODE.exclusive_access() = Mode::Record(Vec::new());
+}
+
+/// Start replaying the comma separated task ids in `decisions`, failing if
+/// they don't parse.
+pub fn start_replay(decisions: &str) -> bool {
+    let ids: Option<Vec<u8>> = decisions.split(',').map(|id| id.parse().ok()).collect();
+    match ids {
+        Some(ids) => {
+            *MODE.exclusive_access() = Mode::Replay(ids, 0);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Get the task due to run next in the replay, if one is.
+pub fn next_replayed() -> Option<usize> {
+    match &*MODE.exclusive_access() {
+        Mode::Replay(ids, done) => ids.get(*done).map(|&id| id as usize),
+        _ => None,
+    }
+}
+
+/// Give up replaying, `task_id` due next not being `Ready`.
+pub fn diverged(task_id: usize) {
+    let mut mode = MODE.exclusive_access();
+    if let Mode::Replay(_, done) = &*mode {
+        warn!(
+            "[kernel] sched replay diverged at decision {}: task {} is not ready",
+            done, task_id
+        );
+        *mode = Mode::Off;
+    }
+}
+
+/// Note the switch to task `task_id`.
+pub fn switched_to(task_id: usize) {
+    match &mut *MODE.exclusive_access() {
+        Mode::Record(ids) => {
+            debug!(
+                "[kernel] sched: #{} task {} at tick {}",
+                ids.len(),
+                task_id,
+                get_ticks()
+            );
+            ids.push(task_id as u8);
+        }
+        Mode::Replay(ids, done) => {
+            if ids.get(*done) == Some(&(task_id as u8)) {
+                *done += 1;
+            }
+        }
+        Mode::Off => {}
+    }
+}
+
+/// Print what was recorded as a boot option, or how far the replay got.
+pub fn report() {
+    match &*MODE.exclusive_access() {
+        Mode::Record(ids) => {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            println!("[kernel] sched record: sched_replay={}", ids.join(","));
+        }
+        Mode::Replay(ids, done) => {
+            println!("[kernel] sched replay: {} of {} decisions", done, ids.len());
+        }
+        Mode::Off => {}
+    }
+}
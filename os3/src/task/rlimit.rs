@@ -0,0 +1,94 @@
+//! Per-task resource limits
+//!
+//! Each limit has a soft value, which is enforced, and a hard value, the
+//! ceiling the soft value may be raised to. Only privileged tasks may raise
+//! a hard value. The limits are checked where ch3 can check them:
+//!
+//! - `RLIMIT_CPU`: seconds of CPU time, on the way back to user mode;
+//!   past the soft value the task gets `SIGXCPU` once a second, past the
+//!   hard value `SIGKILL`
+//! - `RLIMIT_STACK`: bytes of user stack below its top, against `sp` on
+//!   the way back to user mode, raising `SIGSEGV`
+//! - `RLIMIT_RSS`: bytes resident, which in ch3 is all of the app slot and
+//!   user stack from load on, so the soft value can't be lowered below that
+//! - `RLIMIT_NOFILE`: descriptors, those from the soft value on are closed
+
+use crate::config::{APP_SIZE_LIMIT, USER_STACK_SIZE};
+
+use super::task::MAX_FDS;
+
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIMIT_NOFILE: usize = 7;
+/// no limit
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// bytes resident for every task, see `RLIMIT_RSS`
+pub const RESIDENT_BYTES: usize = APP_SIZE_LIMIT + USER_STACK_SIZE;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+/// A soft and a hard limit
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+impl RLimit {
+    const fn fixed(value: usize) -> Self {
+        Self {
+            cur: value,
+            max: value,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+/// The limits of a task
+pub struct ResourceLimits {
+    pub cpu: RLimit,
+    pub stack: RLimit,
+    pub rss: RLimit,
+    pub nofile: RLimit,
+    /// CPU seconds at which the next `SIGXCPU` is due
+    pub xcpu_next_s: usize,
+}
+
+impl Default for ResourceLimits {
+    /// What every task has to begin with.
+    fn default() -> Self {
+        Self {
+            cpu: RLimit::fixed(RLIM_INFINITY),
+            stack: RLimit::fixed(USER_STACK_SIZE),
+            rss: RLimit::fixed(RESIDENT_BYTES),
+            nofile: RLimit::fixed(MAX_FDS),
+            xcpu_next_s: RLIM_INFINITY,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Get the limit of `resource`, if it is one of those supported.
+    pub fn get(&self, resource: usize) -> Option<RLimit> {
+        match resource {
+            RLIMIT_CPU => Some(self.cpu),
+            RLIMIT_STACK => Some(self.stack),
+            RLIMIT_RSS => Some(self.rss),
+            RLIMIT_NOFILE => Some(self.nofile),
+            _ => None,
+        }
+    }
+
+    /// Get the limit of `resource` to change, if it is one of those
+    /// supported.
+    pub fn get_mut(&mut self, resource: usize) -> Option<&mut RLimit> {
+        match resource {
+            RLIMIT_CPU => Some(&mut self.cpu),
+            RLIMIT_STACK => Some(&mut self.stack),
+            RLIMIT_RSS => Some(&mut self.rss),
+            RLIMIT_NOFILE => Some(&mut self.nofile),
+            _ => None,
+        }
+    }
+}
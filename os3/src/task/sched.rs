@@ -0,0 +1,151 @@
+//! Scheduling policies
+//!
+//! [`super::TaskManager`] keeps the mechanism: which tasks may run at all on
+//! this hart right now, `yield_to` and replayed decisions, and the switch
+//! itself. Which of the runnable tasks goes next is up to a [`Scheduler`],
+//! chosen at boot with `sched=`:
+//!
+//! - `rr`: round robin among the tasks of best nice value, aged so that
+//!   nobody starves, the default
+//! - `stride`: stride scheduling, the lower the nice value the more often a
+//!   task gets to run
+//! - `mlfq`: multi-level feedback queue, tasks using up their time slice
+//!   sink to lower levels until the next boost, while those yielding or
+//!   blocking first keep their level
+//!
+//! `rr` and `stride` go by the nice value a task inherits while holding a
+//! mutex someone better waits on, see [`crate::mutex`]; `mlfq` has no use
+//! for nice values.
+
+use super::task::{NICE_MAX, NICE_MIN};
+use crate::config::MAX_APP_NUM;
+use alloc::boxed::Box;
+
+/// A task the scheduler may pick
+#[derive(Copy, Clone)]
+pub struct Runnable {
+    pub id: usize,
+    /// the nice value, or the one inherited from a waiter if better, see
+    /// `boosted_nice`
+    pub nice: isize,
+    /// the nice value of the task made up for its age, see
+    /// `effective_priority`
+    pub priority: isize,
+}
+
+/// A scheduling policy
+pub trait Scheduler {
+    /// Pick who runs next among `runnable`, in task order starting after the
+    /// current task.
+    fn pick_next(&mut self, runnable: &[Runnable]) -> Option<usize>;
+    /// Task `task_id` was preempted at the end of its time slice.
+    fn on_tick(&mut self, _task_id: usize) {}
+}
+
+/// Round robin by effective priority
+struct RoundRobin;
+
+impl Scheduler for RoundRobin {
+    fn pick_next(&mut self, runnable: &[Runnable]) -> Option<usize> {
+        // the first of the best, so equals take turns
+        let best = runnable.iter().map(|r| r.priority).min()?;
+        runnable.iter().find(|r| r.priority == best).map(|r| r.id)
+    }
+}
+
+/// pass a task of weight 1 advances by per run
+const BIG_STRIDE: usize = 1 << 20;
+
+/// Stride scheduling, weighted by nice value
+struct Stride {
+    pass: [usize; MAX_APP_NUM],
+    /// pass of the task picked last, which tasks coming back from sleep are
+    /// brought up to, not to run alone until they catch up
+    vtime: usize,
+}
+
+impl Stride {
+    /// weight of nice value `nice`, from 1 at `NICE_MAX` to 40 at `NICE_MIN`
+    fn weight(nice: isize) -> usize {
+        (NICE_MAX - nice.clamp(NICE_MIN, NICE_MAX) + 1) as usize
+    }
+}
+
+impl Scheduler for Stride {
+    fn pick_next(&mut self, runnable: &[Runnable]) -> Option<usize> {
+        for r in runnable {
+            self.pass[r.id] = self.pass[r.id].max(self.vtime);
+        }
+        let next = *runnable.iter().min_by_key(|r| self.pass[r.id])?;
+        self.vtime = self.pass[next.id];
+        self.pass[next.id] += BIG_STRIDE / Self::weight(next.nice);
+        Some(next.id)
+    }
+}
+
+/// levels of the feedback queue, 0 running first
+const MLFQ_LEVELS: usize = 4;
+/// time slices ending in a preemption after which every task goes back to
+/// level 0
+const MLFQ_BOOST_TICKS: usize = 100;
+
+/// Multi-level feedback queue
+struct Mlfq {
+    level: [usize; MAX_APP_NUM],
+    /// preemptions since the last boost
+    ticks: usize,
+}
+
+impl Scheduler for Mlfq {
+    fn pick_next(&mut self, runnable: &[Runnable]) -> Option<usize> {
+        let top = runnable.iter().map(|r| self.level[r.id]).min()?;
+        runnable
+            .iter()
+            .find(|r| self.level[r.id] == top)
+            .map(|r| r.id)
+    }
+
+    fn on_tick(&mut self, task_id: usize) {
+        self.level[task_id] = (self.level[task_id] + 1).min(MLFQ_LEVELS - 1);
+        self.ticks += 1;
+        if self.ticks >= MLFQ_BOOST_TICKS {
+            self.ticks = 0;
+            self.level = [0; MAX_APP_NUM];
+        }
+    }
+}
+
+/// Scheduling policies to choose from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    RoundRobin,
+    Stride,
+    Mlfq,
+}
+
+impl Policy {
+    /// Get the policy named `name` in `sched=`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rr" => Some(Policy::RoundRobin),
+            "stride" => Some(Policy::Stride),
+            "mlfq" => Some(Policy::Mlfq),
+            _ => None,
+        }
+    }
+
+    /// Create a scheduler of this policy.
+    pub fn scheduler(self) -> Box<dyn Scheduler> {
+        match self {
+            Policy::RoundRobin => Box::new(RoundRobin),
+            Policy::Stride => Box::new(Stride {
+                pass: [0; MAX_APP_NUM],
+                vtime: 0,
+            }),
+            Policy::Mlfq => Box::new(Mlfq {
+                level: [0; MAX_APP_NUM],
+                ticks: 0,
+            }),
+        }
+    }
+}
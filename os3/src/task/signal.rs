@@ -0,0 +1,82 @@
+//! Signals delivered to tasks
+//!
+//! Raising a signal only records it in the pending set of the target task.
+//! Pending signals are delivered when that task is about to return to
+//! userspace, see [`super::handle_signals`]. Faults raise `SIGSEGV` or
+//! `SIGILL` if the task handles them, and kill it otherwise.
+
+/// largest valid signal number
+pub const MAX_SIG: usize = 31;
+
+/// handler value that requests the default action
+pub const SIG_DFL: usize = 0;
+/// handler value that requests the signal to be ignored
+pub const SIG_IGN: usize = 1;
+
+bitflags! {
+    /// a set of signals, bit `n` standing for signal number `n`
+    pub struct SignalFlags: u32 {
+        const SIGINT = 1 << 2;
+        const SIGILL = 1 << 4;
+        const SIGABRT = 1 << 6;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGCHLD = 1 << 17;
+        const SIGXCPU = 1 << 24;
+    }
+}
+
+impl SignalFlags {
+    /// Get the set holding only signal `signum`, if it is a valid signal.
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            return None;
+        }
+        Self::from_bits(1 << signum)
+    }
+
+    /// Get the lowest signal number in the set.
+    pub fn lowest_signum(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.bits().trailing_zeros() as usize)
+        }
+    }
+
+    /// Signals whose default action is to ignore them rather than terminate.
+    pub fn ignored_by_default(&self) -> bool {
+        *self == Self::SIGCHLD
+    }
+
+    /// Signals that can neither be caught, ignored nor masked.
+    pub fn uncatchable(&self) -> bool {
+        *self == Self::SIGKILL
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+/// what a task does on receiving a signal
+pub struct SignalAction {
+    /// address of the user handler, or `SIG_DFL`/`SIG_IGN`. The handler gets
+    /// the signal number in `a0`, and the faulting address in `a1` for a
+    /// `SIGSEGV` or `SIGILL` raised by a fault.
+    pub handler: usize,
+    /// signals masked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
@@ -1,23 +1,295 @@
 //! Types related to task management
 
-use crate::config::MAX_SYSCALL_NUM;
+use crate::perf::{PerfCounters, PerfEvent, MAX_PERF_EVENTS};
+use crate::sync::{UPRefMut, UPSafeCell};
+use crate::trap::TrapContext;
+use alloc::collections::BTreeMap;
+use super::latency::LatencyHistogram;
+use super::ptrace::PtraceEvent;
+use super::rlimit::ResourceLimits;
+use super::sched::Runnable;
+use super::signal::{SignalAction, SignalFlags, MAX_SIG};
 use super::TaskContext;
+use riscv::register::scause::{Exception, Interrupt, Trap};
+
+/// highest priority nice value
+pub const NICE_MIN: isize = -20;
+/// lowest priority nice value
+pub const NICE_MAX: isize = 19;
+/// number of file descriptors, the console ones
+pub const MAX_FDS: usize = 2;
+/// number of priorities from `NICE_MIN` to `NICE_MAX`
+pub const PRIORITY_LEVELS: usize = (NICE_MAX - NICE_MIN + 1) as usize;
+/// exit code of a task killed for running past its timeout, as `timeout(1)`
+/// exits with and apart from the `-signum` of other kills
+pub const EXIT_TIMED_OUT: i32 = -124;
 
-#[derive(Copy, Clone)]
 /// task control block structure
+///
+/// Shared as `Arc<TaskControlBlock>`, so the task manager lends out a task
+/// without holding its own borrow while the task is used.
 pub struct TaskControlBlock {
+    /// use inner value to get mutable access
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+impl Default for TaskControlBlock {
+    /// Create an `UnInit` task.
+    fn default() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    task_cx: TaskContext::zero_init(),
+                    task_status: TaskStatus::UnInit,
+                    first_start_time: 0,
+                    syscall_accounting: BTreeMap::new(),
+                    run_time: 0,
+                    last_start_time: 0,
+                    perf: PerfCounters::default(),
+                    perf_start: PerfCounters::default(),
+                    perf_events: [None; MAX_PERF_EVENTS],
+                    signals: SignalFlags::empty(),
+                    signal_mask: SignalFlags::empty(),
+                    signal_actions: [SignalAction::default(); MAX_SIG + 1],
+                    handling_sig: None,
+                    trap_cx_backup: None,
+                    signal_mask_backup: SignalFlags::empty(),
+                    fault_addr: 0,
+                    itimer_interval: 0,
+                    itimer_expire: 0,
+                    wake_time: 0,
+                    privileged: false,
+                    timeout_expire: 0,
+                    timed_out: false,
+                    ring: None,
+                    nice: 0,
+                    inherited_nice: None,
+                    age: 0,
+                    cpu_mask: usize::MAX,
+                    group: 0,
+                    pgid: 0,
+                    exit_code: 0,
+                    killed: false,
+                    ready_time: 0,
+                    wait_time: 0,
+                    block_start: 0,
+                    block_time: 0,
+                    latency: LatencyHistogram::default(),
+                    tracer: None,
+                    trace_syscalls: false,
+                    trace_stop: None,
+                    trace_regs: None,
+                    ptrace_wait: None,
+                    step_patch: None,
+                    wait_queued: false,
+                    stop_pending: false,
+                    user_time: 0,
+                    user_enter: 0,
+                    voluntary_switches: 0,
+                    preemptions: 0,
+                    preempted: false,
+                    trap_stats: TrapStats::default(),
+                    fd_flags: [0; MAX_FDS],
+                    rlimits: ResourceLimits::default(),
+                })
+            },
+        }
+    }
+}
+
+impl TaskControlBlock {
+    /// Panic if the task is already borrowed.
+    pub fn inner_exclusive_access(&self) -> UPRefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+}
+
+/// The mutable part of a task control block, in `UPSafeCell`
+pub struct TaskControlBlockInner {
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
     // LAB1: Add whatever you need about the Task.
     pub first_start_time:usize,
-    pub syscall_accounting:[u32; MAX_SYSCALL_NUM],
+    /// times each syscall was made, by syscall id
+    pub syscall_accounting: BTreeMap<usize, u32>,
+    /// total time spent `Running` before the current run, in microseconds
+    pub run_time: usize,
+    /// when the task was last switched in, in microseconds
+    pub last_start_time: usize,
+    /// cycles and instructions retired before the current run
+    pub perf: PerfCounters,
+    /// counters sampled when the task was last switched in
+    pub perf_start: PerfCounters,
+    /// hardware events opened by `sys_perf_event_open`
+    pub perf_events: [Option<PerfEvent>; MAX_PERF_EVENTS],
+    /// signals raised but not delivered yet
+    pub signals: SignalFlags,
+    /// signals blocked from delivery
+    pub signal_mask: SignalFlags,
+    /// per-signal actions installed by `sys_sigaction`
+    pub signal_actions: [SignalAction; MAX_SIG + 1],
+    /// signal whose user handler is currently running
+    pub handling_sig: Option<usize>,
+    /// trap context interrupted by the running signal handler
+    pub trap_cx_backup: Option<TrapContext>,
+    /// signal mask to restore once the running signal handler returns
+    pub signal_mask_backup: SignalFlags,
+    /// address of the last fault raising `SIGSEGV` or `SIGILL`
+    pub fault_addr: usize,
+    /// `ITIMER_REAL` reload value in microseconds, 0 for one-shot
+    pub itimer_interval: usize,
+    /// `ITIMER_REAL` deadline in microseconds, 0 when disarmed
+    pub itimer_expire: usize,
+    /// when a `Blocked` sleeping task is due to wake, in microseconds
+    pub wake_time: usize,
+    /// whether the task may make the privileged syscalls
+    pub privileged: bool,
+    /// wall-clock deadline in microseconds, 0 when there is none
+    pub timeout_expire: usize,
+    /// whether the task is being killed for missing its deadline
+    pub timed_out: bool,
+    /// `(address, entries)` of the registered syscall ring
+    pub ring: Option<(usize, usize)>,
+    /// static priority, from `NICE_MIN` (first) to `NICE_MAX` (last)
+    pub nice: isize,
+    /// best nice value of the tasks waiting on mutexes the task holds, see
+    /// [`crate::mutex`]
+    pub inherited_nice: Option<isize>,
+    /// times the task was passed over while `Ready`
+    pub age: usize,
+    /// harts the task may run on, bit `n` standing for hart `n`
+    pub cpu_mask: usize,
+    /// task group sharing the CPU quota
+    pub group: usize,
+    /// process group signals can be sent to together, the id of its first
+    /// task
+    pub pgid: usize,
+    /// exit code once `Exited`, negative if killed by the kernel
+    pub exit_code: i32,
+    /// whether the kernel ended the task, for a fault, a fatal signal or its
+    /// deadline, instead of the task exiting itself
+    pub killed: bool,
+    /// when the task last became `Ready`, in microseconds
+    pub ready_time: usize,
+    /// total time spent `Ready` but not running, in microseconds
+    pub wait_time: usize,
+    /// when the task last became `Blocked`, in microseconds
+    pub block_start: usize,
+    /// total time spent `Blocked`, in microseconds
+    pub block_time: usize,
+    /// how long the task waited `Ready` before each run
+    pub latency: LatencyHistogram,
+    /// task tracing this one
+    pub tracer: Option<usize>,
+    /// whether to stop for the tracer on syscalls
+    pub trace_syscalls: bool,
+    /// event the task is stopped for its tracer on
+    pub trace_stop: Option<PtraceEvent>,
+    /// registers of the task stopped for its tracer
+    pub trace_regs: Option<TrapContext>,
+    /// tracee this task waits to stop, see `sys_ptrace`
+    pub ptrace_wait: Option<usize>,
+    /// address and original halfword of the `c.ebreak` to single-step with
+    pub step_patch: Option<(usize, u16)>,
+    /// whether the task is `Blocked` in a [`crate::sync::WaitQueue`]
+    pub wait_queued: bool,
+    /// whether the task was suspended while `Blocked`, to stop once it wakes
+    pub stop_pending: bool,
+    /// total time spent `Running` in user mode, in microseconds
+    pub user_time: usize,
+    /// when the task last returned to user mode
+    pub user_enter: usize,
+    /// times the task gave up the CPU itself
+    pub voluntary_switches: usize,
+    /// times the task was preempted
+    pub preemptions: usize,
+    /// whether the task is being preempted, for the next switch to count
+    pub preempted: bool,
+    /// traps taken from user mode
+    pub trap_stats: TrapStats,
+    /// status flags of the console descriptors, set with `sys_fcntl`
+    pub fd_flags: [u32; MAX_FDS],
+    /// resource limits, see [`super::rlimit`]
+    pub rlimits: ResourceLimits,
+}
+
+impl TaskControlBlockInner {
+    /// The nice value, or the one inherited from a waiter if better.
+    pub fn boosted_nice(&self) -> isize {
+        self.inherited_nice.map_or(self.nice, |nice| nice.min(self.nice))
+    }
+
+    /// Priority the scheduler goes by, lower runs first: the boosted nice
+    /// value, improved by one for every time the task was passed over.
+    pub fn effective_priority(&self) -> isize {
+        (self.boosted_nice() - self.age as isize).max(NICE_MIN)
+    }
+
+    /// The task as a candidate for the scheduler, as task `id`.
+    pub fn runnable(&self, id: usize) -> Runnable {
+        Runnable {
+            id,
+            nice: self.boosted_nice(),
+            priority: self.effective_priority(),
+        }
+    }
+
+    /// Make the task `Ready` at time `now`, accounting for the time it was
+    /// `Blocked`, or `Stopped` if it was suspended meanwhile.
+    pub fn make_ready(&mut self, now: usize) {
+        if self.task_status == TaskStatus::Blocked {
+            self.block_time += now - self.block_start;
+        }
+        if self.stop_pending {
+            self.stop_pending = false;
+            self.task_status = TaskStatus::Stopped;
+            return;
+        }
+        self.task_status = TaskStatus::Ready;
+        self.ready_time = now;
+    }
+}
+
+/// Traps from user mode of a task, by kind
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct TrapStats {
+    /// traps of any kind
+    pub traps: usize,
+    pub syscalls: usize,
+    /// store faults, the only memory faults a task survives to count
+    pub page_faults: usize,
+    pub illegal_instructions: usize,
+    pub timer_interrupts: usize,
+    /// times the task was preempted
+    pub preemptions: usize,
+    /// syscalls with no such id, failed with `ENOSYS`
+    pub unknown_syscalls: usize,
+}
+
+impl TrapStats {
+    /// Count a trap of cause `cause`.
+    pub fn record(&mut self, cause: Trap) {
+        self.traps += 1;
+        match cause {
+            Trap::Exception(Exception::UserEnvCall) => self.syscalls += 1,
+            Trap::Exception(Exception::StoreFault)
+            | Trap::Exception(Exception::StorePageFault) => self.page_faults += 1,
+            Trap::Exception(Exception::IllegalInstruction) => self.illegal_instructions += 1,
+            Trap::Interrupt(Interrupt::SupervisorTimer) => self.timer_interrupts += 1,
+            _ => {}
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// task status: UnInit, Ready, Running, Exited, Blocked, Stopped
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
     Exited,
+    Blocked,
+    /// taken off the CPU by `sys_task_suspend` until `sys_task_resume`
+    Stopped,
 }
@@ -0,0 +1,66 @@
+//! Shutdown hooks
+//!
+//! Subsystems holding buffered state [`register`] a hook that flushes it,
+//! and [`power_off`] runs the hooks before the machine goes down: once all
+//! apps have exited, on `sys_shutdown` and on a panic. The hooks run in the
+//! reverse order of registration, so that a subsystem is torn down before
+//! those it was set up on, e.g. the trace ring is dumped before the console
+//! buffer is flushed. They are told whether the run failed, to keep what is
+//! only worth looking at then.
+//!
+//! Like the tunables, the registry has a fixed capacity so that it can be
+//! used before the heap is ready.
+
+use crate::sbi::shutdown;
+use crate::sync::UPSafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// capacity of the registry
+const MAX_HOOKS: usize = 8;
+
+/// A teardown callback of some subsystem
+#[derive(Copy, Clone)]
+struct Hook {
+    name: &'static str,
+    /// called with whether the run failed
+    run: fn(bool),
+}
+
+lazy_static! {
+    static ref HOOKS: UPSafeCell<[Option<Hook>; MAX_HOOKS]> =
+        unsafe { UPSafeCell::new([None; MAX_HOOKS]) };
+}
+
+/// whether the hooks have started running, so that a panic in one of them
+/// doesn't run them all over again
+static TORN_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Register hook `name`, calling `run` before the machine powers off.
+pub fn register(name: &'static str, run: fn(bool)) {
+    let mut hooks = HOOKS.exclusive_access();
+    assert!(
+        hooks.iter().flatten().all(|h| h.name != name),
+        "shutdown hook {} registered twice",
+        name
+    );
+    let slot = hooks
+        .iter_mut()
+        .find(|h| h.is_none())
+        .expect("too many shutdown hooks");
+    *slot = Some(Hook { name, run });
+}
+
+/// Run the hooks, latest first, then shut down, telling QEMU whether this
+/// is a `failure`.
+pub fn power_off(failure: bool) -> ! {
+    if !TORN_DOWN.swap(true, Ordering::Relaxed) {
+        // copied out of the registry, which a panic may have left borrowed
+        let hooks = HOOKS.try_exclusive_access().map(|hooks| *hooks);
+        for hook in hooks.iter().flat_map(|hooks| hooks.iter().rev().flatten()) {
+            debug!("[kernel] shutdown: {}", hook.name);
+            (hook.run)(failure);
+        }
+    }
+    shutdown(failure)
+}
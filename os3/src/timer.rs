@@ -1,23 +1,392 @@
 //! RISC-V timer-related functionality
 
-use crate::config::CLOCK_FREQ;
+use crate::config::{CLOCK_FREQ, RTC_BASE_ADDRESS};
+use crate::console::poll_console;
+use crate::random::add_interrupt_entropy;
 use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::sysctl;
+use crate::task::{
+    itimer_expired, needs_slice_tick, running_task_id, timeout_expired, wakeup_sleeping_tasks,
+};
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::sync::atomic::{self, AtomicBool, AtomicUsize};
+use lazy_static::*;
 use riscv::register::time;
 
-const TICKS_PER_SEC: usize = 100;
+/// timer interrupts per second, see [`set_tick_hz`]
+static TICKS_PER_SEC: AtomicUsize = AtomicUsize::new(100);
+/// `mtime` when the current time slice started
+static SLICE_START: AtomicUsize = AtomicUsize::new(0);
+/// only interrupt when something is due, see [`program_next_trigger`]
+static TICKLESS: AtomicBool = AtomicBool::new(false);
+/// sleepers woken by [`check_timer`], see `timer.wakeups`
+static WAKEUPS: AtomicUsize = AtomicUsize::new(0);
+/// calls of [`check_timer`] that woke a sleeper, see `timer.wakeup_batches`
+static WAKEUP_BATCHES: AtomicUsize = AtomicUsize::new(0);
+/// most sleepers woken by one call of [`check_timer`]
+static MAX_WAKEUP_BATCH: AtomicUsize = AtomicUsize::new(0);
+/// timer interrupts handled since boot
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+/// highest accepted tick rate
+const MAX_TICKS_PER_SEC: usize = 10_000;
 const MICRO_PER_SEC: usize = 1_000_000;
+const NANO_PER_SEC: usize = 1_000_000_000;
+/// how fast a slew moves the realtime clock, in parts per million
+const SLEW_PPM: usize = 500;
 
-/// read the `mtime` register
+/// goldfish RTC register offsets
+const RTC_TIME_LOW: usize = 0x00;
+const RTC_TIME_HIGH: usize = 0x04;
+
+lazy_static! {
+    /// `(rtc_ns, mtime)` sampled together once, anchoring the realtime clock
+    static ref REALTIME_ANCHOR: (usize, usize) = (read_rtc_ns(), get_time());
+    static ref REALTIME_ADJUST: UPSafeCell<RealtimeAdjust> = unsafe {
+        UPSafeCell::new(RealtimeAdjust {
+            offset_ns: 0,
+            slew_start: 0,
+            slew_ns: 0,
+        })
+    };
+}
+
+/// How far the realtime clock was set off the RTC anchor. The monotonic
+/// clock knows nothing of this.
+struct RealtimeAdjust {
+    /// nanoseconds the clock was stepped by, plus slews done with
+    offset_ns: isize,
+    /// `mtime` when `slew_ns` started to be slewed in
+    slew_start: usize,
+    /// nanoseconds to slew in at [`SLEW_PPM`] from `slew_start`
+    slew_ns: isize,
+}
+
+impl RealtimeAdjust {
+    /// the part of the slew that is done at `mtime` `now`
+    fn slewed(&self, now: usize) -> isize {
+        let max = (ticks_to_ns(now - self.slew_start) / (MICRO_PER_SEC / SLEW_PPM)) as isize;
+        self.slew_ns.clamp(-max, max)
+    }
+    /// move the part of the slew done at `now` into the offset
+    fn settle(&mut self, now: usize) {
+        let slewed = self.slewed(now);
+        self.offset_ns += slewed;
+        self.slew_ns -= slewed;
+        self.slew_start = now;
+    }
+}
+
+/// read the `mtime` register, refreshing the time page with it
 pub fn get_time() -> usize {
-    time::read()
+    let time = time::read();
+    write_time_page(time);
+    time
+}
+
+/// read the `cycle` counter
+pub fn get_cycle() -> usize {
+    let cycle;
+    unsafe {
+        core::arch::asm!("rdcycle {}", out(reg) cycle);
+    }
+    cycle
 }
 
 /// get current time in microseconds
 pub fn get_time_us() -> usize {
-    time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
+    get_time() / (CLOCK_FREQ / MICRO_PER_SEC)
+}
+
+/// get current time in nanoseconds
+pub fn get_time_ns() -> usize {
+    ticks_to_ns(get_time())
+}
+
+/// convert `mtime` ticks to nanoseconds without overflowing
+fn ticks_to_ns(ticks: usize) -> usize {
+    ticks / CLOCK_FREQ * NANO_PER_SEC + ticks % CLOCK_FREQ * NANO_PER_SEC / CLOCK_FREQ
+}
+
+/// read the RTC, in nanoseconds since the Unix epoch
+fn read_rtc_ns() -> usize {
+    unsafe {
+        // reading TIME_LOW latches TIME_HIGH, so the order matters
+        let low = ((RTC_BASE_ADDRESS + RTC_TIME_LOW) as *const u32).read_volatile() as usize;
+        let high = ((RTC_BASE_ADDRESS + RTC_TIME_HIGH) as *const u32).read_volatile() as usize;
+        (high << 32) | low
+    }
 }
 
-/// set the next timer interrupt
+/// the realtime clock at `mtime` `now` as the RTC had it, unadjusted
+fn rtc_realtime_ns(now: usize) -> usize {
+    let (rtc_ns, anchor) = *REALTIME_ANCHOR;
+    rtc_ns + ticks_to_ns(now - anchor)
+}
+
+/// get wall-clock time in nanoseconds since the Unix epoch
+pub fn get_realtime_ns() -> usize {
+    let now = get_time();
+    let adjust = REALTIME_ADJUST.exclusive_access();
+    (rtc_realtime_ns(now) as isize + adjust.offset_ns + adjust.slewed(now)) as usize
+}
+
+/// Step the realtime clock to `ns` since the Unix epoch, dropping any slew.
+pub fn step_realtime(ns: usize) {
+    let now = get_time();
+    let mut adjust = REALTIME_ADJUST.exclusive_access();
+    adjust.settle(now);
+    adjust.slew_ns = 0;
+    adjust.offset_ns = ns as isize - rtc_realtime_ns(now) as isize;
+}
+
+/// Slew the realtime clock by `delta_ns`, gradually so it never jumps or
+/// runs backwards, replacing the slew still to be done, which is returned.
+pub fn slew_realtime(delta_ns: isize) -> isize {
+    let mut adjust = REALTIME_ADJUST.exclusive_access();
+    adjust.settle(get_time());
+    core::mem::replace(&mut adjust.slew_ns, delta_ns)
+}
+
+/// sample the RTC anchor for the realtime clock
+pub fn init() {
+    lazy_static::initialize(&REALTIME_ANCHOR);
+    REALTIME_ADJUST.exclusive_access().slew_start = get_time();
+    sysctl::register("timer.tick_hz", tick_hz, set_tick_hz);
+    sysctl::register(
+        "timer.tickless",
+        || TICKLESS.load(atomic::Ordering::Relaxed) as usize,
+        |on| {
+            if on > 1 {
+                return false;
+            }
+            TICKLESS.store(on == 1, atomic::Ordering::Relaxed);
+            true
+        },
+    );
+    sysctl::register(
+        "timer.wakeups",
+        || WAKEUPS.load(atomic::Ordering::Relaxed),
+        |v| sysctl::reset_counter(&WAKEUPS, v),
+    );
+    sysctl::register(
+        "timer.wakeup_batches",
+        || WAKEUP_BATCHES.load(atomic::Ordering::Relaxed),
+        |v| sysctl::reset_counter(&WAKEUP_BATCHES, v),
+    );
+    sysctl::register(
+        "timer.max_wakeup_batch",
+        || MAX_WAKEUP_BATCH.load(atomic::Ordering::Relaxed),
+        |v| sysctl::reset_counter(&MAX_WAKEUP_BATCH, v),
+    );
+}
+
+/// start a new time slice and set the next timer interrupt
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    SLICE_START.store(get_time(), atomic::Ordering::Relaxed);
+    program_next_trigger();
+}
+
+/// Set the timer interrupt for the next event.
+///
+/// Periodically, that is the end of the current time slice. In tickless mode,
+/// it is the earliest timer deadline, or the end of the slice if another task
+/// waits for the CPU, so an idle or lone task is not woken up for nothing.
+/// With ticks that far apart, the time page is refreshed here as well.
+pub fn program_next_trigger() {
+    update_time_page();
+    let slice_end = SLICE_START.load(atomic::Ordering::Relaxed) + CLOCK_FREQ / tick_hz();
+    if !TICKLESS.load(atomic::Ordering::Relaxed) || running_task_id().is_none() {
+        set_timer(slice_end);
+        return;
+    }
+    let mut next = match TIMERS.exclusive_access().peek() {
+        // saturated, as deadlines may be too far out to count in ticks
+        Some(timer) => timer.expire_us.saturating_mul(CLOCK_FREQ / MICRO_PER_SEC),
+        None => usize::MAX,
+    };
+    if needs_slice_tick() {
+        next = next.min(slice_end);
+    }
+    set_timer(next);
+}
+
+/// get the number of timer interrupts per second
+pub fn tick_hz() -> usize {
+    TICKS_PER_SEC.load(atomic::Ordering::Relaxed)
+}
+
+/// Change the number of timer interrupts per second.
+pub fn set_tick_hz(hz: usize) -> bool {
+    if hz == 0 || hz > MAX_TICKS_PER_SEC {
+        return false;
+    }
+    TICKS_PER_SEC.store(hz, atomic::Ordering::Relaxed);
+    true
+}
+
+/// Kernel-maintained time page shared with userspace.
+///
+/// In ch3 every app runs directly on physical addresses, so apps can read
+/// this page at the address returned by `sys_get_time_page` without trapping
+/// into the kernel. The kernel refreshes it whenever it reads the clock,
+/// which is at least on every timer interrupt.
+///
+/// The page is advisory: nothing keeps apps from writing it, just as they can
+/// write any other memory, so the time read from it is only as good as the
+/// apps sharing it, and the kernel never reads it back.
+///
+/// `seq` is odd while the kernel writes the page, so that readers can tell
+/// a torn read: read `seq`, and again if odd, then the fields, then `seq`
+/// once more, and start over unless it is the same, see `time_page_us` in
+/// `user/src/lib.rs`.
+#[repr(C, align(4096))]
+pub struct TimePage {
+    /// bumped before and after every update
+    pub seq: usize,
+    /// `mtime` value when last refreshed
+    pub time: usize,
+    /// time in microseconds when last refreshed
+    pub time_us: usize,
+    /// frequency of `mtime`, to convert `time` by hand
+    pub clock_freq: usize,
+}
+
+/// time page instance
+static mut TIME_PAGE: TimePage = TimePage {
+    seq: 0,
+    time: 0,
+    time_us: 0,
+    clock_freq: CLOCK_FREQ,
+};
+
+/// refresh the shared time page with the current time
+pub fn update_time_page() {
+    get_time();
+}
+
+/// Write `mtime` value `time` to the time page.
+fn write_time_page(time: usize) {
+    unsafe {
+        let page = &mut *core::ptr::addr_of_mut!(TIME_PAGE);
+        let seq = core::ptr::read_volatile(&page.seq);
+        if seq % 2 == 1 {
+            // an update was interrupted, leave the page to it
+            return;
+        }
+        core::ptr::write_volatile(&mut page.seq, seq.wrapping_add(1));
+        // the odd `seq` is seen before any of the new fields
+        atomic::fence(atomic::Ordering::Release);
+        core::ptr::write_volatile(&mut page.time, time);
+        core::ptr::write_volatile(&mut page.time_us, time / (CLOCK_FREQ / MICRO_PER_SEC));
+        // and the new fields before the even one
+        atomic::fence(atomic::Ordering::Release);
+        core::ptr::write_volatile(&mut page.seq, seq.wrapping_add(2));
+    }
+}
+
+/// get the address of the shared time page
+pub fn time_page_addr() -> usize {
+    unsafe { &TIME_PAGE as *const TimePage as usize }
+}
+
+/// What to do when a timer expires
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TimerKind {
+    /// raise `SIGALRM` for an interval timer
+    ITimer,
+    /// wake up a sleeping task
+    Sleep,
+    /// kill a task that ran past its deadline
+    Timeout,
+}
+
+/// A deadline armed on behalf of a task
+pub struct TimerCondVar {
+    pub expire_us: usize,
+    pub task_id: usize,
+    pub kind: TimerKind,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_us == other.expire_us
+    }
+}
+impl Eq for TimerCondVar {}
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so that `BinaryHeap` pops the earliest deadline first
+        other.expire_us.cmp(&self.expire_us)
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondVar>> =
+        unsafe { UPSafeCell::new(BinaryHeap::<TimerCondVar>::new()) };
+}
+
+/// arm a timer of `kind` for task `task_id` at `expire_us`
+pub fn add_timer(expire_us: usize, task_id: usize, kind: TimerKind) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar {
+        expire_us,
+        task_id,
+        kind,
+    });
+}
+
+/// Fire all expired timers. The sleepers due are woken together, in
+/// priority order.
+pub fn check_timer() {
+    let current_us = get_time_us();
+    let mut sleepers = Vec::new();
+    loop {
+        let mut timers = TIMERS.exclusive_access();
+        match timers.peek() {
+            Some(timer) if timer.expire_us <= current_us => {
+                let timer = timers.pop().unwrap();
+                // the handler may arm a new timer
+                drop(timers);
+                match timer.kind {
+                    TimerKind::ITimer => itimer_expired(timer.task_id, timer.expire_us),
+                    TimerKind::Sleep => sleepers.push((timer.task_id, timer.expire_us)),
+                    TimerKind::Timeout => timeout_expired(timer.task_id, timer.expire_us),
+                }
+            }
+            _ => break,
+        }
+    }
+    if sleepers.is_empty() {
+        return;
+    }
+    let woken = wakeup_sleeping_tasks(&sleepers);
+    if woken > 0 {
+        WAKEUPS.fetch_add(woken, atomic::Ordering::Relaxed);
+        WAKEUP_BATCHES.fetch_add(1, atomic::Ordering::Relaxed);
+        MAX_WAKEUP_BATCH.fetch_max(woken, atomic::Ordering::Relaxed);
+    }
+}
+
+/// get the number of timer interrupts handled since boot
+pub fn get_ticks() -> usize {
+    TICKS.load(atomic::Ordering::Relaxed)
+}
+
+/// handle a supervisor timer interrupt
+pub fn handle_timer_interrupt() {
+    TICKS.fetch_add(1, atomic::Ordering::Relaxed);
+    add_interrupt_entropy();
+    poll_console();
+    update_time_page();
+    check_timer();
+    set_next_trigger();
 }
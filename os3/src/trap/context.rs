@@ -1,8 +1,17 @@
 //! Implementation of [`TrapContext`]
 
 use riscv::register::sstatus::{self, Sstatus, SPP};
+use riscv::register::{scause, stval};
+
+/// ABI names of the general purpose registers
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 /// trap context structure containing sstatus, sepc and registers
 pub struct TrapContext {
     pub x: [usize; 32],
@@ -14,6 +23,13 @@ impl TrapContext {
     pub fn set_sp(&mut self, sp: usize) {
         self.x[2] = sp;
     }
+    /// Zero `ra` and the temporaries, which the syscall fast path of
+    /// `trap.S` leaves unsaved.
+    pub fn clear_temporaries(&mut self) {
+        for i in [1, 5, 6, 7, 28, 29, 30, 31] {
+            self.x[i] = 0;
+        }
+    }
     pub fn app_init_context(entry: usize, sp: usize) -> Self {
         let mut sstatus = sstatus::read();
         sstatus.set_spp(SPP::User);
@@ -25,4 +41,33 @@ impl TrapContext {
         cx.set_sp(sp);
         cx
     }
+    /// Print all registers saved in the context, along with `scause` and
+    /// `stval` of the latest trap.
+    pub fn dump(&self) {
+        let scause = scause::read();
+        println!(
+            "sepc   = {:#018x} sstatus = {:#018x}",
+            self.sepc,
+            self.sstatus.bits()
+        );
+        println!(
+            "scause = {:#018x} stval   = {:#018x} ({:?})",
+            scause.bits(),
+            stval::read(),
+            scause.cause()
+        );
+        for i in (0..32).step_by(4) {
+            println!(
+                "{:>4} = {:#018x} {:>4} = {:#018x} {:>4} = {:#018x} {:>4} = {:#018x}",
+                REG_NAMES[i],
+                self.x[i],
+                REG_NAMES[i + 1],
+                self.x[i + 1],
+                REG_NAMES[i + 2],
+                self.x[i + 2],
+                REG_NAMES[i + 3],
+                self.x[i + 3]
+            );
+        }
+    }
 }
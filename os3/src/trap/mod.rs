@@ -1,37 +1,173 @@
 //! Trap handling functionality
 //!
-//! For rCore, we have a single trap entry point, namely `__alltraps`. At
-//! initialization in [`init()`], we set the `stvec` CSR to point to it.
-//!
-//! All traps go through `__alltraps`, which is defined in `trap.S`. The
-//! assembly language code does just enough work restore the kernel space
-//! context, ensuring that Rust code safely runs, and transfers control to
-//! [`trap_handler()`].
+//! For rCore, all traps from user space go through a single entry point,
+//! namely `__alltraps`, which is defined in `trap.S`. The assembly language
+//! code does just enough work restore the kernel space context, ensuring that
+//! Rust code safely runs, and transfers control to [`trap_handler()`].
 //!
 //! It then calls different functionality based on what exactly the exception
 //! was. For example, timer interrupts trigger task preemption, and syscalls go
 //! to [`syscall()`].
+//!
+//! While in the kernel, `stvec` points to `__kerneltrap` instead, which
+//! handles the timer interrupts that arrive inside
+//! [`with_interrupts_enabled()`] and the faults of copies from user memory,
+//! see [`crate::uaccess`], and `__restore` switches back on its way to user
+//! space.
+//!
+//! With [`set_vectored()`], traps from user space enter through the vector
+//! table `__uservec` instead, which sends timer interrupts straight to
+//! [`user_timer_handler()`] and everything else to `__alltraps`.
+//!
+//! With the `fast-syscall` feature, syscalls take a fast path out of
+//! `__alltraps` to [`syscall_handler()`], saving and restoring only the
+//! registers the syscall ABI preserves. It is off by default, as apps whose
+//! `ecall` wrappers don't declare `ra` and the temporaries clobbered, like
+//! those of `ci-user`, would lose them; syscalls then go to
+//! [`trap_handler()`] with everything saved like any other trap.
 
 mod context;
 
+use crate::bench;
+use crate::coredump;
+use crate::ktrace::{self, Event};
+use crate::loader::{get_app_name, get_app_range, get_user_stack_range};
 use crate::syscall::syscall;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::set_next_trigger;
+use crate::task::{
+    charge_current_user_time, check_current_rlimits, count_current_trap, current_context_exposed,
+    finish_single_step, get_current_task, handle_signals, kill_current_and_run_next, mark_current_user_enter,
+    preempt_current_and_run_next, raise_current_fault, trace_stop, PtraceEvent, SignalFlags,
+    PTRACE_EVENT_FAULT, PTRACE_EVENT_SYSCALL,
+};
+use crate::task::running_task_id;
+use crate::uaccess;
+use crate::workqueue;
+use crate::timer::{handle_timer_interrupt, program_next_trigger};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sepc, sie, sstatus, stval, stvec,
 };
 
+#[cfg(feature = "fast-syscall")]
+core::arch::global_asm!(".set SYSCALL_FAST_PATH, 1");
+#[cfg(not(feature = "fast-syscall"))]
+core::arch::global_asm!(".set SYSCALL_FAST_PATH, 0");
 core::arch::global_asm!(include_str!("trap.S"));
 
-/// initialize CSR `stvec` as the entry of `__alltraps`
+/// a timer tick arrived while the kernel ran with interrupts enabled, so
+/// the current task is due to be preempted
+static NEED_RESCHED: AtomicBool = AtomicBool::new(false);
+
+/// `stvec` for traps from user space, set by `__restore`
+#[no_mangle]
+static USER_STVEC: AtomicUsize = AtomicUsize::new(0);
+
+/// initialize CSR `stvec` as the entry of traps from the kernel, `__restore`
+/// sets [`USER_STVEC`] up on its way to user space
 pub fn init() {
+    if USER_STVEC.load(Ordering::Relaxed) == 0 {
+        set_vectored(false);
+    }
+    set_kernel_trap_entry();
+}
+
+/// Have traps from user space enter through the vector table if `vectored`,
+/// or all through `__alltraps`.
+pub fn set_vectored(vectored: bool) {
+    extern "C" {
+        fn __alltraps();
+        fn __uservec();
+    }
+    let stvec = if vectored {
+        __uservec as usize | TrapMode::Vectored as usize
+    } else {
+        __alltraps as usize | TrapMode::Direct as usize
+    };
+    USER_STVEC.store(stvec, Ordering::Relaxed);
+}
+
+/// Whether `stvec` and [`USER_STVEC`] point at the trap entries, for the
+/// boot self-check.
+pub fn entries_installed() -> bool {
     extern "C" {
         fn __alltraps();
+        fn __uservec();
+        fn __kerneltrap();
     }
+    let user = USER_STVEC.load(Ordering::Relaxed);
+    stvec::read().bits() == __kerneltrap as usize
+        && (user == __alltraps as usize | TrapMode::Direct as usize
+            || user == __uservec as usize | TrapMode::Vectored as usize)
+}
+
+fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kerneltrap();
+    }
+    unsafe {
+        stvec::write(__kerneltrap as usize, TrapMode::Direct);
+    }
+}
+
+/// Run `f` with interrupts enabled in the kernel, so that timer ticks are
+/// not lost during long work.
+///
+/// `f` must not hold borrows the timer interrupt takes, that is of
+/// `TASK_MANAGER` or the timers.
+pub fn with_interrupts_enabled<T>(f: impl FnOnce() -> T) -> T {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::set_sie();
+    }
+    let ret = f();
+    if !enabled {
+        unsafe {
+            sstatus::clear_sie();
+        }
+    }
+    ret
+}
+
+/// Run `f` with interrupts masked, for work inside
+/// [`with_interrupts_enabled()`] to update state the timer interrupt also
+/// touches.
+pub fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+    let enabled = sstatus::read().sie();
     unsafe {
-        stvec::write(__alltraps as usize, TrapMode::Direct);
+        sstatus::clear_sie();
+    }
+    let ret = f();
+    if enabled {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+    ret
+}
+
+#[no_mangle]
+/// handle an interrupt arriving inside [`with_interrupts_enabled()`], or a
+/// fault in a copy from user memory
+pub fn kernel_trap_handler(cx: &mut TrapContext) {
+    match scause::read().cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            handle_timer_interrupt();
+            if running_task_id().is_some() {
+                NEED_RESCHED.store(true, Ordering::Relaxed);
+            }
+        }
+        cause => match uaccess::fixup(cx.sepc) {
+            // ends the copy short
+            Some(fixup) if matches!(cause, Trap::Exception(_)) => cx.sepc = fixup,
+            _ => panic!(
+                "Unsupported trap from kernel {:?}, stval = {:#x}, sepc = {:#x}!",
+                cause,
+                stval::read(),
+                sepc::read()
+            ),
+        },
     }
 }
 
@@ -45,24 +181,41 @@ pub fn enable_timer_interrupt() {
 #[no_mangle]
 /// handle an interrupt, exception, or system call from user space
 pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
+    set_kernel_trap_entry();
+    charge_current_user_time();
     let scause = scause::read(); // get trap cause
     let stval = stval::read(); // get extra value
+    count_current_trap(scause.cause());
+    ktrace::record(Event::TrapEnter, scause.bits());
     match scause.cause() {
-        Trap::Exception(Exception::UserEnvCall) => {
-            cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
-        }
+        Trap::Exception(Exception::UserEnvCall) => handle_syscall(cx),
         Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
-            error!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.", stval, cx.sepc);
-            exit_current_and_run_next();
+            trace_stop(cx, fault_event(scause.bits()));
+            // a handler gets it on the way out, see `handle_signals`
+            if !raise_current_fault(SignalFlags::SIGSEGV, stval) {
+                error!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.", stval, cx.sepc);
+                dump_task(get_current_task(), cx);
+                coredump::dump(get_current_task(), cx, scause.bits(), stval);
+                kill_current_and_run_next(-2);
+            }
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            error!("[kernel] IllegalInstruction in application, core dumped.");
-            exit_current_and_run_next();
+            trace_stop(cx, fault_event(scause.bits()));
+            if !raise_current_fault(SignalFlags::SIGILL, cx.sepc) {
+                error!("[kernel] IllegalInstruction in application, core dumped.");
+                dump_task(get_current_task(), cx);
+                coredump::dump(get_current_task(), cx, scause.bits(), stval);
+                kill_current_and_run_next(-3);
+            }
         }
+        // resumes at the restored instruction
+        Trap::Exception(Exception::Breakpoint) if finish_single_step(cx) => {}
+        // doesn't return, the benchmark goes on in the kernel
+        Trap::Exception(Exception::Breakpoint) if bench::leave_user(cx) => {}
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
+            handle_timer_interrupt();
+            NEED_RESCHED.store(false, Ordering::Relaxed);
+            preempt_current_and_run_next();
         }
         _ => {
             panic!(
@@ -72,7 +225,131 @@ pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
             );
         }
     }
+    return_to_user(cx)
+}
+
+#[no_mangle]
+/// handle a system call from user space, entered from `__syscall` with `ra`
+/// and the temporaries left unsaved
+///
+/// Returns whether `__restore` has to load the whole context back, as
+/// something else may have read or rewritten it: the tracer, or the signal
+/// code. The unsaved registers are zeroed before they get to it.
+pub fn syscall_handler(cx: &mut TrapContext) -> usize {
+    set_kernel_trap_entry();
+    charge_current_user_time();
+    count_current_trap(Trap::Exception(Exception::UserEnvCall));
+    ktrace::record(Event::TrapEnter, scause::read().bits());
+    let exposed = current_context_exposed();
+    if exposed {
+        cx.clear_temporaries();
+    }
+    handle_syscall(cx);
+    prepare_return(cx, !exposed) as usize
+}
+
+/// make the system call in `cx`, stopping for the tracer first
+fn handle_syscall(cx: &mut TrapContext) {
+    cx.sepc += 4;
+    let event = PtraceEvent {
+        kind: PTRACE_EVENT_SYSCALL,
+        detail: cx.x[17],
+    };
+    trace_stop(cx, event);
+    cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13]]) as usize;
+}
+
+#[no_mangle]
+/// handle a timer interrupt from user space, entered from `__uservec`
+/// without decoding `scause`
+pub fn user_timer_handler(cx: &mut TrapContext) -> &mut TrapContext {
+    set_kernel_trap_entry();
+    charge_current_user_time();
+    count_current_trap(Trap::Interrupt(Interrupt::SupervisorTimer));
+    ktrace::record(Event::TrapEnter, scause::read().bits());
+    handle_timer_interrupt();
+    NEED_RESCHED.store(false, Ordering::Relaxed);
+    preempt_current_and_run_next();
+    return_to_user(cx)
+}
+
+/// the work left before any trap returns to user space
+fn return_to_user(cx: &mut TrapContext) -> &mut TrapContext {
+    prepare_return(cx, false);
     cx
 }
 
+/// the work of [`return_to_user`] on `cx`, `partial` if it lacks `ra` and
+/// the temporaries, as left by `__syscall`
+///
+/// Returns whether `cx` has to be restored whole: if it wasn't partial, or
+/// if it has been exposed since, the missing registers zeroed first.
+fn prepare_return(cx: &mut TrapContext, partial: bool) -> bool {
+    workqueue::run_pending();
+    if NEED_RESCHED.swap(false, Ordering::Relaxed) {
+        preempt_current_and_run_next();
+    }
+    check_current_rlimits(cx.x[2]);
+    // any signal raised by now, by the syscall, while preempted or for a
+    // limit, may be delivered below, saving the context away
+    let exposed = partial && current_context_exposed();
+    if exposed {
+        cx.clear_temporaries();
+    }
+    handle_signals(cx);
+    // the trap may have armed a timer or woken up a task
+    program_next_trigger();
+    mark_current_user_enter();
+    ktrace::record(Event::TrapExit, get_current_task());
+    !partial || exposed
+}
+
+/// the event a fault with cause `scause` stops a traced task on
+fn fault_event(scause: usize) -> PtraceEvent {
+    PtraceEvent {
+        kind: PTRACE_EVENT_FAULT,
+        detail: scause,
+    }
+}
+
+/// frames [`dump_user_backtrace`] prints at most
+const MAX_USER_FRAMES: usize = 16;
+
+/// Print the id and name of task `task_id`, its saved user registers `cx`
+/// and where it was called from.
+pub fn dump_task(task_id: usize, cx: &TrapContext) {
+    println!("[kernel] task {} ({}):", task_id, get_app_name(task_id));
+    cx.dump();
+    dump_user_backtrace(task_id, cx);
+}
+
+/// Print the return addresses on the user stack of task `task_id`, walking
+/// its frame pointers from `cx`.
+///
+/// The apps are loaded from raw binaries, so the addresses are left for
+/// `objdump` or `addr2line` on the app ELF to make sense of.
+fn dump_user_backtrace(task_id: usize, cx: &TrapContext) {
+    let (app_start, app_end) = get_app_range(task_id);
+    let (stack_bottom, stack_top) = get_user_stack_range(task_id);
+    println!("backtrace:");
+    println!("  #0 {:#018x}", cx.sepc);
+    let mut fp = cx.x[8];
+    for i in 1..MAX_USER_FRAMES {
+        // a frame record is the return address and the caller's frame
+        // pointer, right below the frame pointer
+        if fp % 8 != 0 || fp < stack_bottom + 16 || fp > stack_top {
+            break;
+        }
+        let (ra, next) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if !(app_start..app_end).contains(&ra) {
+            break;
+        }
+        println!("  #{} {:#018x}", i, ra);
+        if next <= fp {
+            break;
+        }
+        fp = next;
+    }
+}
+
 pub use context::TrapContext;
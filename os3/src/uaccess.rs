@@ -0,0 +1,118 @@
+//! Copies from and to user memory
+//!
+//! Syscall arguments passed by pointer are read with [`copy_from_user`], and
+//! results written with [`copy_to_user`], byte buffers going through
+//! [`copy_slice_from_user`] and [`copy_slice_to_user`], so that a bad pointer
+//! fails the syscall with `-EFAULT` instead of taking the kernel down or
+//! writing over it. The copy is done by `__copy_user` in `uaccess.S`, whose
+//! loads and stores are listed in the exception table `.ex_table`: when one
+//! of them faults, [`crate::trap::kernel_trap_handler`] resumes at the
+//! address [`fixup`] finds for it, which ends the copy short.
+
+use crate::loader::{get_app_range, get_user_stack_range};
+use crate::task::get_current_task;
+use core::mem::{size_of, MaybeUninit};
+
+core::arch::global_asm!(include_str!("uaccess.S"));
+
+extern "C" {
+    fn __copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn sex_table();
+    fn eex_table();
+}
+
+/// An entry of the exception table
+#[repr(C)]
+struct ExTableEntry {
+    /// address of an instruction that may fault
+    insn: usize,
+    /// where to resume if it does
+    fixup: usize,
+}
+
+/// Get where to resume after a fault at `pc`, if it is one of the accesses
+/// to user memory.
+pub fn fixup(pc: usize) -> Option<usize> {
+    let start = sex_table as usize;
+    let len = (eex_table as usize - start) / size_of::<ExTableEntry>();
+    let table = unsafe { core::slice::from_raw_parts(start as *const ExTableEntry, len) };
+    table.iter().find(|e| e.insn == pc).map(|e| e.fixup)
+}
+
+/// Whether `[addr, addr + len)` lies in the memory of the current task, its
+/// app slot or its user stack.
+///
+/// There is no paging to fault on the kernel or other apps, so buffers the
+/// kernel uses in place must be checked with this first.
+pub fn user_buffer(addr: usize, len: usize) -> bool {
+    let task_id = get_current_task();
+    let end = match addr.checked_add(len) {
+        Some(end) => end,
+        None => return false,
+    };
+    [get_app_range(task_id), get_user_stack_range(task_id)]
+        .iter()
+        .any(|&(start, limit)| start <= addr && end <= limit)
+}
+
+/// Read a `T` from user address `src`, failing if it is outside the current
+/// task, see [`user_buffer`], or the read faults.
+///
+/// `T` must be valid whatever its bytes are.
+pub fn copy_from_user<T: Copy>(src: *const T) -> Option<T> {
+    if !user_buffer(src as usize, size_of::<T>()) {
+        return None;
+    }
+    let mut value = MaybeUninit::<T>::uninit();
+    let left = unsafe {
+        __copy_user(
+            value.as_mut_ptr() as *mut u8,
+            src as *const u8,
+            size_of::<T>(),
+        )
+    };
+    if left == 0 {
+        Some(unsafe { value.assume_init() })
+    } else {
+        None
+    }
+}
+
+/// Write `value` to user address `dst`, returning whether it was, which it
+/// isn't if `dst` is outside the current task, see [`user_buffer`], or the
+/// write faults.
+///
+/// `T` must be plain data, its bytes are copied as they are.
+pub fn copy_to_user<T>(dst: *mut T, value: &T) -> bool {
+    if !user_buffer(dst as usize, size_of::<T>()) {
+        return false;
+    }
+    let left = unsafe {
+        __copy_user(
+            dst as *mut u8,
+            value as *const T as *const u8,
+            size_of::<T>(),
+        )
+    };
+    left == 0
+}
+
+/// Copy `src` to user address `dst`, returning whether it all was, see
+/// [`copy_to_user`].
+pub fn copy_slice_to_user(dst: *mut u8, src: &[u8]) -> bool {
+    if !user_buffer(dst as usize, src.len()) {
+        return false;
+    }
+    let left = unsafe { __copy_user(dst, src.as_ptr(), src.len()) };
+    left == 0
+}
+
+/// Copy `dst.len()` bytes from user address `src` into `dst`, returning
+/// whether they all were, see [`copy_from_user`].
+pub fn copy_slice_from_user(dst: &mut [u8], src: *const u8) -> bool {
+    if !user_buffer(src as usize, dst.len()) {
+        return false;
+    }
+    let left = unsafe { __copy_user(dst.as_mut_ptr(), src, dst.len()) };
+    left == 0
+}
@@ -0,0 +1,214 @@
+//! virtio-rng driver, an entropy source for [`crate::random`]
+//!
+//! The device is looked for in the virtio-mmio slots of the virt machine,
+//! in either the legacy or the modern register layout. It gets a single
+//! one-descriptor queue and is polled, as the kernel takes no external
+//! interrupts; with no device, the pool makes do with timer jitter.
+
+use crate::config::{PAGE_SIZE, VIRTIO_MMIO_BASE, VIRTIO_MMIO_SLOTS, VIRTIO_MMIO_STRIDE};
+use crate::sync::UPSafeCell;
+use alloc::alloc::{alloc_zeroed, Layout};
+use alloc::boxed::Box;
+use core::sync::atomic::{fence, Ordering};
+use lazy_static::*;
+
+const VIRTIO_MAGIC: u32 = 0x7472_6976;
+const VIRTIO_DEVICE_RNG: u32 = 4;
+
+/// virtio-mmio register offsets
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+/// `VIRTIO_F_VERSION_1`, bit 0 of the second feature word
+const FEATURE_VERSION_1: u32 = 1;
+/// descriptor flag: the device writes the buffer
+const DESC_F_WRITE: u16 = 2;
+
+/// bytes asked for per request
+const RNG_CHUNK: usize = 64;
+/// how long to poll for a request to complete
+const POLL_LIMIT: usize = 1 << 20;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// a queue of one descriptor in the legacy layout, the used ring on the
+/// page after the descriptor table, plus the buffer it points to
+#[repr(C, align(4096))]
+struct VirtQueue {
+    desc: Descriptor,
+    avail_flags: u16,
+    avail_idx: u16,
+    avail_ring: [u16; 1],
+    used_event: u16,
+    _pad: [u8; PAGE_SIZE - 24],
+    used_flags: u16,
+    used_idx: u16,
+    used_ring: [UsedElem; 1],
+    avail_event: u16,
+    buf: [u8; RNG_CHUNK],
+}
+
+struct VirtioRng {
+    base: usize,
+    queue: Box<VirtQueue>,
+}
+
+fn read_reg(base: usize, off: usize) -> u32 {
+    unsafe { ((base + off) as *const u32).read_volatile() }
+}
+
+fn write_reg(base: usize, off: usize, value: u32) {
+    unsafe { ((base + off) as *mut u32).write_volatile(value) }
+}
+
+/// Get the registers of the first virtio-rng device.
+fn find_device() -> Option<usize> {
+    (0..VIRTIO_MMIO_SLOTS)
+        .map(|i| VIRTIO_MMIO_BASE + i * VIRTIO_MMIO_STRIDE)
+        .find(|&base| {
+            read_reg(base, MAGIC_VALUE) == VIRTIO_MAGIC
+                && read_reg(base, DEVICE_ID) == VIRTIO_DEVICE_RNG
+        })
+}
+
+impl VirtioRng {
+    /// Set up the device at `base`, failing if it won't take the queue.
+    fn new(base: usize) -> Option<Self> {
+        let version = read_reg(base, VERSION);
+        write_reg(base, STATUS, 0);
+        write_reg(base, STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        // no features, but a modern device insists on VERSION_1
+        write_reg(base, DRIVER_FEATURES_SEL, 0);
+        write_reg(base, DRIVER_FEATURES, 0);
+        if version >= 2 {
+            write_reg(base, DRIVER_FEATURES_SEL, 1);
+            write_reg(base, DRIVER_FEATURES, FEATURE_VERSION_1);
+            write_reg(
+                base,
+                STATUS,
+                STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK,
+            );
+            if read_reg(base, STATUS) & STATUS_FEATURES_OK == 0 {
+                return None;
+            }
+        }
+        write_reg(base, QUEUE_SEL, 0);
+        if read_reg(base, QUEUE_NUM_MAX) == 0 {
+            return None;
+        }
+        let queue = unsafe {
+            let ptr = alloc_zeroed(Layout::new::<VirtQueue>()) as *mut VirtQueue;
+            if ptr.is_null() {
+                return None;
+            }
+            Box::from_raw(ptr)
+        };
+        let addr = &*queue as *const VirtQueue as usize;
+        write_reg(base, QUEUE_NUM, 1);
+        if version >= 2 {
+            let used = &queue.used_flags as *const u16 as usize;
+            let avail = &queue.avail_flags as *const u16 as usize;
+            for (low, high, addr) in [
+                (QUEUE_DESC_LOW, QUEUE_DESC_HIGH, addr),
+                (QUEUE_DRIVER_LOW, QUEUE_DRIVER_HIGH, avail),
+                (QUEUE_DEVICE_LOW, QUEUE_DEVICE_HIGH, used),
+            ] {
+                write_reg(base, low, addr as u32);
+                write_reg(base, high, (addr >> 32) as u32);
+            }
+            write_reg(base, QUEUE_READY, 1);
+        } else {
+            write_reg(base, GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+            write_reg(base, QUEUE_ALIGN, PAGE_SIZE as u32);
+            write_reg(base, QUEUE_PFN, (addr / PAGE_SIZE) as u32);
+        }
+        let status = read_reg(base, STATUS);
+        write_reg(base, STATUS, status | STATUS_DRIVER_OK);
+        Some(Self { base, queue })
+    }
+
+    /// Fill `buf` from the device, returning how many bytes it gave.
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let queue = &mut *self.queue;
+        queue.desc = Descriptor {
+            addr: queue.buf.as_ptr() as u64,
+            len: RNG_CHUNK as u32,
+            flags: DESC_F_WRITE,
+            next: 0,
+        };
+        queue.avail_ring[0] = 0;
+        let used_idx = unsafe { core::ptr::read_volatile(&queue.used_idx) };
+        fence(Ordering::SeqCst);
+        unsafe {
+            core::ptr::write_volatile(&mut queue.avail_idx, queue.avail_idx.wrapping_add(1));
+        }
+        fence(Ordering::SeqCst);
+        write_reg(self.base, QUEUE_NOTIFY, 0);
+        for _ in 0..POLL_LIMIT {
+            if unsafe { core::ptr::read_volatile(&queue.used_idx) } != used_idx {
+                fence(Ordering::SeqCst);
+                let len = (queue.used_ring[0].len as usize)
+                    .min(RNG_CHUNK)
+                    .min(buf.len());
+                buf[..len].copy_from_slice(&queue.buf[..len]);
+                return len;
+            }
+            core::hint::spin_loop();
+        }
+        0
+    }
+}
+
+lazy_static! {
+    static ref RNG: UPSafeCell<Option<VirtioRng>> =
+        unsafe { UPSafeCell::new(find_device().and_then(VirtioRng::new)) };
+}
+
+/// Look for the device, reporting whether there is one.
+pub fn init() -> bool {
+    RNG.exclusive_access().is_some()
+}
+
+/// Fill `buf` with bytes from the device, returning how many there were,
+/// 0 without a device.
+pub fn read(buf: &mut [u8]) -> usize {
+    match RNG.exclusive_access().as_mut() {
+        Some(rng) => rng.read(buf),
+        None => 0,
+    }
+}
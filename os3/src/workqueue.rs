@@ -0,0 +1,90 @@
+//! Deferred kernel work
+//!
+//! Interrupt handlers run with interrupts masked, so they [`queue`] what can
+//! wait instead of doing it there, and [`run_pending`] runs it later with
+//! interrupts enabled: on the way back to user mode and in the idle loop,
+//! which is as close to a kernel thread as ch3 gets.
+//!
+//! A work item is a function and two words for it rather than a boxed
+//! closure, so that queueing doesn't allocate in interrupt context. Like
+//! anything run by [`with_interrupts_enabled`], it must not hold borrows the
+//! timer interrupt takes, see [`crate::trap::without_interrupts`] for short
+//! updates of state shared with it.
+
+use crate::sync::UPSafeCell;
+use crate::trap::with_interrupts_enabled;
+use lazy_static::*;
+
+/// capacity of the queue
+const MAX_WORK: usize = 32;
+
+/// A function to call with its arguments
+#[derive(Copy, Clone)]
+struct Work {
+    func: fn(usize, usize),
+    args: (usize, usize),
+}
+
+/// Work items in the order they were queued
+struct WorkQueue {
+    items: [Option<Work>; MAX_WORK],
+    /// index of the oldest item
+    head: usize,
+    len: usize,
+}
+
+impl WorkQueue {
+    fn push(&mut self, work: Work) -> bool {
+        if self.len == MAX_WORK {
+            return false;
+        }
+        self.items[(self.head + self.len) % MAX_WORK] = Some(work);
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<Work> {
+        if self.len == 0 {
+            return None;
+        }
+        let work = self.items[self.head].take();
+        self.head = (self.head + 1) % MAX_WORK;
+        self.len -= 1;
+        work
+    }
+}
+
+lazy_static! {
+    static ref QUEUE: UPSafeCell<WorkQueue> = unsafe {
+        UPSafeCell::new(WorkQueue {
+            items: [None; MAX_WORK],
+            head: 0,
+            len: 0,
+        })
+    };
+}
+
+/// Have `func(a, b)` called later with interrupts enabled, or right away if
+/// the queue is full, not to lose it.
+pub fn queue(func: fn(usize, usize), a: usize, b: usize) {
+    let work = Work { func, args: (a, b) };
+    if !QUEUE.exclusive_access().push(work) {
+        func(a, b);
+    }
+}
+
+/// Run the queued work, as well as whatever interrupts queue meanwhile.
+///
+/// Must be called with interrupts masked and no borrows held that the work
+/// might take.
+pub fn run_pending() {
+    loop {
+        // taken out with interrupts still masked, so a handler can't find
+        // the queue borrowed
+        let work = match QUEUE.exclusive_access().pop() {
+            Some(work) => work,
+            None => return,
+        };
+        with_interrupts_enabled(|| (work.func)(work.args.0, work.args.1));
+    }
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, get_nice, mutex_blocking_create, mutex_lock, nice, sys_mutex_unlock, TimeSpec,
+    CLOCK_PROCESS_CPUTIME_ID,
+};
+
+/// CPU time spent holding the mutex
+const HOLD_MS: usize = 100;
+
+fn cpu_time_ms() -> usize {
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &mut ts), 0);
+    ts.sec * 1000 + ts.nsec / 1_000_000
+}
+
+/// 与 ch3b_lock_waiter、ch3b_syscalls 一起测试优先级继承：
+/// 本任务以最低优先级持有互斥锁 0，再创建互斥锁 1 表示已经持有，
+/// 然后占用 100ms CPU 时间后释放。
+/// 三者必须一起构建、一起运行，见 Makefile 的 TOGETHER。
+/// 正确输出：（无报错信息）
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(nice(10), 0);
+    assert_eq!(get_nice(0), Some(10));
+    assert_eq!(mutex_blocking_create(), 0);
+    assert_eq!(mutex_lock(0), 0);
+    // locking it again would never return
+    assert_eq!(mutex_lock(0), -35);
+    assert_eq!(mutex_blocking_create(), 1);
+    let start = cpu_time_ms();
+    while cpu_time_ms() < start + HOLD_MS {}
+    // handed to the waiter, no longer ours
+    assert_eq!(sys_mutex_unlock(0), 0);
+    assert_eq!(sys_mutex_unlock(0), -1);
+    0
+}
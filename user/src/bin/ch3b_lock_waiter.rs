@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::mem::size_of_val;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use user_lib::{
+    futex_wake, get_nice, get_time, kill, mq_receive, mutex_lock, mutex_unlock, setpgid, sigaction,
+    sigreturn, sleep_ms, sys_mutex_unlock, task_resume, task_suspend, AppMeta, SignalAction,
+    SIGUSR1,
+};
+
+// only privileged tasks may lower their nice value with nice()
+app_meta!(AppMeta::new().nice(-10));
+
+/// longest wait for the holder's 100ms of CPU time
+const MAX_WAIT_MS: isize = 500;
+
+static USR1_CAUGHT: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigusr1(_signum: usize) -> ! {
+    USR1_CAUGHT.store(true, Ordering::SeqCst);
+    sigreturn();
+    unreachable!();
+}
+
+fn priority_inheritance() {
+    // mutex 1 exists, so not ours to unlock, once the holder holds mutex 0
+    while sys_mutex_unlock(1) != -1 {
+        sleep_ms(10);
+    }
+    // let the middle task start spinning
+    sleep_ms(20);
+    let start = get_time();
+    assert_eq!(mutex_lock(0), 0);
+    let waited = get_time() - start;
+    mutex_unlock(0);
+    assert!(waited < MAX_WAIT_MS, "waited {}ms for the mutex", waited);
+    println!("Test priority inheritance OK!");
+}
+
+/// Suspend the member in ch3b_syscalls while it is blocked on `word`, and
+/// check it stays stopped when woken until resumed.
+fn suspend(member: usize, word: &AtomicU32) {
+    assert_eq!(task_suspend(member), 0);
+    assert_eq!(futex_wake(word, 1), 1);
+    sleep_ms(20);
+    assert_eq!(word.load(Ordering::SeqCst), 0);
+    assert_eq!(task_resume(member), 0);
+    assert_eq!(task_resume(member), -22);
+    sleep_ms(20);
+    assert_eq!(word.load(Ordering::SeqCst), 1);
+    // blocked again, a stop dropped before it wakes is not taken
+    assert_eq!(task_suspend(member), 0);
+    assert_eq!(task_resume(member), 0);
+    println!("Test suspend and resume OK!");
+}
+
+/// Join the process group of the member in ch3b_syscalls and kill it, ours
+/// caught, while the member waits on a futex.
+fn group_kill() {
+    let mut msg = [0usize; 2];
+    let buf =
+        unsafe { core::slice::from_raw_parts_mut(msg.as_mut_ptr() as *mut u8, size_of_val(&msg)) };
+    // there is no queue until the member is ready
+    loop {
+        match mq_receive(0, buf) {
+            -22 => sleep_ms(10),
+            len => {
+                assert_eq!(len, 16);
+                break;
+            }
+        }
+    }
+    let [member, addr] = msg;
+    let word = unsafe { &*(addr as *const AtomicU32) };
+    // let it block on the futex
+    sleep_ms(20);
+    suspend(member, word);
+    assert_eq!(setpgid(0, member), 0);
+    let action = SignalAction {
+        handler: on_sigusr1 as usize,
+        mask: 0,
+    };
+    assert_eq!(sigaction(SIGUSR1, &action, None), 0);
+    assert_eq!(kill(-(member as isize), SIGUSR1), 0);
+    assert!(USR1_CAUGHT.load(Ordering::SeqCst));
+    sleep_ms(20);
+    // gone, having taken itself off the futex
+    assert_eq!(futex_wake(word, 1), 0);
+    assert_eq!(task_resume(member), -3);
+    println!("Test group kill OK!");
+}
+
+/// 与 ch3b_lock_holder、ch3b_syscalls 一起测试优先级继承：
+/// 本任务以最高优先级等待 ch3b_lock_holder 持有的互斥锁 0，
+/// ch3b_syscalls 以中等优先级空转。没有优先级继承时，持有者要等中等
+/// 优先级的任务跑完才能释放。
+/// 然后在 ch3b_syscalls 等待 futex 时挂起、唤醒、恢复它，
+/// 再加入它的进程组，kill 整个进程组。
+/// 本任务须有特权（privileged=ch3b_lock_waiter），见 os3 Makefile 的 BOOTARGS。
+/// 三者必须一起构建、一起运行，见 Makefile 的 TOGETHER。
+/// 正确输出：（无报错信息）
+/// Test priority inheritance OK!
+/// Test suspend and resume OK!
+/// Test group kill OK!
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(get_nice(0), Some(-10));
+    priority_inheritance();
+    group_kill();
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::AppMeta;
+
+// no kernel has syscall 999, so this app is never run
+app_meta!(AppMeta::new()
+    .syscalls(&[999])
+    .description("needs a syscall the kernel lacks"));
+
+/// 正确输出：
+/// [kernel] app N (ch3b_meta_rejected) needs syscall 999, which is not supported, not loaded
+/// 内核拒绝加载本程序，不计为失败
+
+#[no_mangle]
+fn main() -> i32 {
+    panic!("loaded despite needing syscall 999");
+}
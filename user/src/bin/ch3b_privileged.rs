@@ -0,0 +1,294 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    adjtime, clock_gettime, get_mappings, getpid, getrandom, getrlimit, getrusage, perf_event_open,
+    perf_event_read, perf_read, ptrace, sched_getstat, sched_latency, set_group_quota, setrlimit,
+    settimeofday, syscall, sysctl, sysinfo, task_times, task_trap_stats, time_page, times, yield_,
+    AppMeta, LatencyHistogram, Mapping, PerfCounters, RLimit, Rusage, SchedStat, SysInfo, TaskInfo,
+    TaskTimes, TimeSpec, TimeVal, Tms, TrapStats, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME,
+    MAP_EXEC, MAP_KIND_ELF, MAP_KIND_SHARED, MAP_KIND_STACK, MAP_WRITE, PTRACE_ATTACH,
+    PTRACE_DETACH, PTRACE_PEEKDATA, PTRACE_SINGLESTEP, RLIMIT_CPU, RLIMIT_NOFILE, RLIMIT_RSS,
+    RLIM_INFINITY, RUSAGE_CHILDREN, RUSAGE_SELF, SYSCALL_GETRANDOM, SYSCALL_PERF_EVENT_OPEN,
+    SYSCALL_PTRACE, SYSCALL_SYSCTL,
+};
+
+app_meta!(AppMeta::new()
+    .syscalls(&[
+        SYSCALL_SYSCTL as u16,
+        SYSCALL_GETRANDOM as u16,
+        SYSCALL_PERF_EVENT_OPEN as u16,
+        SYSCALL_PTRACE as u16,
+    ])
+    .description("tests the privileged syscalls"));
+
+const APP_SIZE_LIMIT: usize = 0x20000;
+const USER_STACK_SIZE: usize = 4096;
+const PAGE_SIZE: usize = 4096;
+/// more than the kernel loads
+const MAX_APPS: usize = 64;
+const SYSCALL_ADJTIME: usize = 422;
+/// SBI PMU event: CPU cycles
+const HW_CPU_CYCLES: usize = 1;
+
+fn cpu_time_ms() -> usize {
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &mut ts), 0);
+    ts.sec * 1000 + ts.nsec / 1_000_000
+}
+
+fn spin_cpu_ms(ms: usize) {
+    let start = cpu_time_ms();
+    while cpu_time_ms() < start + ms {}
+}
+
+fn tunables() {
+    let mut hz = 0;
+    assert_eq!(sysctl("timer.tick_hz", Some(&mut hz), None), 0);
+    assert_ne!(hz, 0);
+    let mut old = 0;
+    assert_eq!(sysctl("coredump.enable", Some(&mut old), Some(&1)), 0);
+    let mut now = 0;
+    assert_eq!(sysctl("coredump.enable", Some(&mut now), Some(&old)), 0);
+    assert_eq!(now, 1);
+    assert_eq!(sysctl("coredump.enable", None, Some(&2)), -22);
+    assert_eq!(sysctl("no.such.tunable", Some(&mut now), None), -2);
+    println!("Test sysctl OK!");
+}
+
+fn random() {
+    // more than the kernel draws at a time
+    let mut a = [0u8; 300];
+    let mut b = [0u8; 300];
+    assert_eq!(getrandom(&mut a, 0), 300);
+    assert_eq!(getrandom(&mut b, 0), 300);
+    assert_ne!(a, b);
+    assert_eq!(getrandom(&mut a[..0], 0), 0);
+    assert_eq!(getrandom(&mut a, 4), -22);
+    println!("Test getrandom OK!");
+}
+
+fn perf() {
+    let mut before = PerfCounters::default();
+    let mut after = PerfCounters::default();
+    assert_eq!(perf_read(&mut before), 0);
+    spin_cpu_ms(5);
+    assert_eq!(perf_read(&mut after), 0);
+    assert!(after.cycles > before.cycles && after.instret > before.instret);
+    // whether there is a PMU depends on the SBI
+    let mut count = 0;
+    match perf_event_open(HW_CPU_CYCLES, 0) {
+        -22 => {}
+        handle => {
+            assert!(handle >= 0);
+            assert_eq!(perf_event_read(handle as usize, &mut count), 0);
+        }
+    }
+    assert_eq!(perf_event_open(usize::MAX, 0), -22);
+    assert_eq!(perf_event_read(1000, &mut count), -22);
+    println!("Test perf OK!");
+}
+
+fn group_quota() {
+    assert_eq!(set_group_quota(3, 50), 0);
+    assert_eq!(set_group_quota(3, 0), -22);
+    assert_eq!(set_group_quota(100, 50), -22);
+    assert_eq!(set_group_quota(3, 100), 0);
+    println!("Test set_group_quota OK!");
+}
+
+fn accounting() {
+    for _ in 0..3 {
+        yield_();
+    }
+    let mut hist = LatencyHistogram::default();
+    assert_eq!(sched_latency(0, &mut hist), 0);
+    let own: u32 = hist.buckets.iter().sum();
+    assert!(own > 0 && hist.max_us <= hist.total_us);
+    assert_eq!(sched_latency(-1, &mut hist), 0);
+    assert!(hist.buckets.iter().sum::<u32>() >= own);
+    assert_eq!(sched_latency(-2, &mut hist), -22);
+    assert_eq!(sched_latency(MAX_APPS as isize, &mut hist), -3);
+    println!("Test sched_latency OK!");
+
+    let mut before = Tms::default();
+    let mut after = Tms::default();
+    let ticks = times(&mut before);
+    spin_cpu_ms(30);
+    assert!(times(&mut after) >= ticks + 3);
+    assert!(after.utime + after.stime >= before.utime + before.stime + 2);
+    assert_eq!((after.cutime, after.cstime), (0, 0));
+    let info = TaskInfo::new();
+    let mut task = TaskTimes::default();
+    assert_eq!(task_times(&info, &mut task), 0);
+    assert!(task.run_us >= 30_000);
+    println!("Test times OK!");
+
+    let mut usage = Rusage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut usage), 0);
+    assert!(usage.utime_us + usage.stime_us >= 30_000);
+    assert_eq!(
+        usage.maxrss_frames,
+        (APP_SIZE_LIMIT + USER_STACK_SIZE) / PAGE_SIZE
+    );
+    assert!(usage.nvcsw >= 3 && usage.nsyscalls > 0);
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut usage), 0);
+    assert_eq!(usage.nsyscalls, 0);
+    assert_eq!(getrusage(1, &mut usage), -22);
+    println!("Test getrusage OK!");
+
+    let mut stat = SchedStat::new();
+    assert_eq!(sched_getstat(&mut stat), 0);
+    assert!(stat.switches > 0 && stat.switches == stat.voluntary + stat.preemptions);
+    assert_eq!(
+        stat.ready,
+        stat.ready_by_priority.iter().map(|&n| n as usize).sum()
+    );
+    println!("Test sched_getstat OK!");
+
+    let mut before = TrapStats::default();
+    let mut after = TrapStats::default();
+    assert_eq!(task_trap_stats(&info, &mut before), 0);
+    assert_eq!(syscall(9999, [0; 3]), -38);
+    assert_eq!(task_trap_stats(&info, &mut after), 0);
+    assert_eq!(after.unknown_syscalls, before.unknown_syscalls + 1);
+    assert!(after.syscalls >= before.syscalls + 2 && after.traps >= after.syscalls);
+    println!("Test trap stats OK!");
+}
+
+/// The layout of the task, as the boot memory map and `get_mappings` have it.
+fn memory_map() {
+    let mut maps = [Mapping::default(); 4];
+    assert_eq!(get_mappings(&mut maps), 3);
+    let find = |kind| *maps[..3].iter().find(|map| map.kind == kind).unwrap();
+    let elf = find(MAP_KIND_ELF);
+    let code = main as usize;
+    assert!(elf.start <= code && code < elf.end && elf.perms & MAP_EXEC != 0);
+    assert_eq!(elf.end - elf.start, APP_SIZE_LIMIT);
+    let stack = find(MAP_KIND_STACK);
+    let local = &stack as *const _ as usize;
+    assert!(stack.start <= local && local < stack.end);
+    let shared = find(MAP_KIND_SHARED);
+    let page = time_page().unwrap() as *const _ as usize;
+    assert!(shared.start <= page && page < shared.end && shared.perms & MAP_WRITE != 0);
+    assert_eq!(get_mappings(&mut maps[..1]), 3);
+    println!("Test get_mappings OK!");
+}
+
+fn tracing(pid: usize) {
+    assert_eq!(ptrace(PTRACE_ATTACH, pid, 0, 0), -22);
+    assert_eq!(ptrace(PTRACE_ATTACH, MAX_APPS, 0, 0), -3);
+    // any other task will do, it is let go before it could stop
+    let tracee = (0..MAX_APPS)
+        .filter(|&id| id != pid)
+        .find(|&id| ptrace(PTRACE_ATTACH, id, 0, 0) == 0)
+        .unwrap();
+    assert_eq!(ptrace(PTRACE_ATTACH, tracee, 0, 0), -22);
+    // it has to be stopped to be read or stepped
+    let mut word = 0usize;
+    let data = &mut word as *mut _ as usize;
+    assert_eq!(ptrace(PTRACE_PEEKDATA, tracee, 0, data), -22);
+    assert_eq!(ptrace(PTRACE_SINGLESTEP, tracee, 0, 0), -22);
+    assert_eq!(ptrace(PTRACE_DETACH, tracee, 0, 0), 0);
+    assert_eq!(ptrace(PTRACE_DETACH, tracee, 0, 0), -22);
+    println!("Test ptrace OK!");
+}
+
+fn realtime() -> TimeSpec {
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_REALTIME, &mut ts), 0);
+    ts
+}
+
+fn clock() {
+    let now = realtime();
+    let tv = TimeVal {
+        sec: now.sec + 100,
+        usec: now.nsec / 1000,
+    };
+    assert_eq!(settimeofday(&tv), 0);
+    assert!(realtime().sec >= now.sec + 100);
+    let back = TimeVal {
+        sec: now.sec,
+        usec: now.nsec / 1000,
+    };
+    assert_eq!(settimeofday(&back), 0);
+    assert!(realtime().sec < now.sec + 100);
+    // a slew that can't be reported isn't made
+    assert_eq!(syscall(SYSCALL_ADJTIME, [1000, 1, 0]), -14);
+    let mut old = -1;
+    assert_eq!(adjtime(0, Some(&mut old)), 0);
+    assert_eq!(old, 0);
+    assert_eq!(adjtime(isize::MAX, None), -22);
+    println!("Test settimeofday and adjtime OK!");
+}
+
+fn rlimits() {
+    let mut lim = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut lim), 0);
+    assert!(lim.cur > 0 && lim.cur <= lim.max);
+    assert_eq!(getrlimit(99, &mut lim), -22);
+    let cpu = RLimit {
+        cur: 3600,
+        max: RLIM_INFINITY,
+    };
+    assert_eq!(setrlimit(RLIMIT_CPU, &cpu), 0);
+    assert_eq!(getrlimit(RLIMIT_CPU, &mut lim), 0);
+    assert_eq!((lim.cur, lim.max), (3600, RLIM_INFINITY));
+    let inverted = RLimit { cur: 2, max: 1 };
+    assert_eq!(setrlimit(RLIMIT_CPU, &inverted), -22);
+    // everything is resident from load on
+    let rss = RLimit {
+        cur: PAGE_SIZE,
+        max: RLIM_INFINITY,
+    };
+    assert_eq!(setrlimit(RLIMIT_RSS, &rss), -22);
+    println!("Test rlimit OK!");
+}
+
+fn system() {
+    let mut info = SysInfo::default();
+    assert_eq!(sysinfo(&mut info), 0);
+    // longer than this task has run
+    assert!(info.uptime_us >= 30_000);
+    assert!(info.harts & 1 != 0);
+    assert!(info.tasks >= 1 && info.tasks <= MAX_APPS);
+    assert!(info.ready < info.tasks);
+    assert!(info.heap_free <= info.heap_total);
+    println!("Test sysinfo OK!");
+}
+
+/// 测试 ch3 新增的统计、调试与系统管理类系统调用，要求本任务有特权
+/// （privileged=ch3b_privileged），见 os3 Makefile 的 BOOTARGS。
+/// 正确输出：（无报错信息）
+/// Test sysctl OK!
+/// Test getrandom OK!
+/// Test perf OK!
+/// Test set_group_quota OK!
+/// Test sched_latency OK!
+/// Test times OK!
+/// Test getrusage OK!
+/// Test sched_getstat OK!
+/// Test trap stats OK!
+/// Test get_mappings OK!
+/// Test ptrace OK!
+/// Test settimeofday and adjtime OK!
+/// Test rlimit OK!
+/// Test sysinfo OK!
+#[no_mangle]
+fn main() -> i32 {
+    tunables();
+    random();
+    perf();
+    group_quota();
+    accounting();
+    memory_map();
+    tracing(getpid() as usize);
+    clock();
+    rlimits();
+    system();
+    0
+}
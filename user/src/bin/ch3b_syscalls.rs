@@ -0,0 +1,310 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::mem::size_of_val;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use user_lib::{
+    adjtime, flush, futex_wait, futex_wake, get_nice, get_time, getpgid, getpid, kill, mincore,
+    mq_create, mq_send, nice, ppoll, ptrace, ring_enter, ring_setup, sched_getaffinity,
+    sched_setaffinity, set_group_quota, set_task_group, setitimer, setpgid, setrlimit,
+    settimeofday, sigaction, sigprocmask, sigreturn, sleep_ms, sys_futex, sys_mutex_unlock,
+    sys_ring_setup, sysctl, task_resume, writev, yield_, yield_to, ITimerVal, IoVec, PollFd,
+    RLimit, Ring, RingSqe, SignalAction, TimeSpec, TimeVal, ITIMER_REAL, MINCORE_RESIDENT,
+    MQ_OTHERS_RECEIVE, POLLIN, POLLNVAL, POLLOUT, PTRACE_ATTACH, RING_OP_NOP, RING_OP_READ,
+    RING_OP_WRITE, RLIMIT_CPU, RLIM_INFINITY, SIGALRM, SIGKILL, SIGUSR2, STDOUT,
+};
+
+/// wall time spent spinning, well past what the holder needs
+const SPIN_MS: isize = 2000;
+
+static USR2_CAUGHT: AtomicUsize = AtomicUsize::new(0);
+static ALRM_CAUGHT: AtomicUsize = AtomicUsize::new(0);
+static mut RING: Ring<4> = Ring::new();
+const PAGE_SIZE: usize = 4096;
+/// more than the kernel loads
+const MAX_APPS: usize = 64;
+
+/// the futex the group member blocks on until killed, 1 once it was resumed
+static WORD: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn on_signal(signum: usize) -> ! {
+    match signum {
+        SIGUSR2 => USR2_CAUGHT.fetch_add(1, Ordering::SeqCst),
+        SIGALRM => ALRM_CAUGHT.fetch_add(1, Ordering::SeqCst),
+        _ => panic!("unexpected signal {}", signum),
+    };
+    sigreturn();
+    unreachable!();
+}
+
+fn handle(signum: usize) -> isize {
+    let action = SignalAction {
+        handler: on_signal as usize,
+        mask: 0,
+    };
+    sigaction(signum, &action, None)
+}
+
+fn signals(pid: isize) {
+    assert_eq!(handle(SIGUSR2), 0);
+    assert_eq!(handle(SIGKILL), -22);
+    assert_eq!(kill(pid, SIGUSR2), 0);
+    assert_eq!(USR2_CAUGHT.load(Ordering::SeqCst), 1);
+    // held back while masked
+    assert_eq!(sigprocmask(1 << SIGUSR2), 0);
+    assert_eq!(kill(pid, SIGUSR2), 0);
+    assert_eq!(USR2_CAUGHT.load(Ordering::SeqCst), 1);
+    assert_eq!(sigprocmask(0), 1 << SIGUSR2);
+    assert_eq!(USR2_CAUGHT.load(Ordering::SeqCst), 2);
+    assert_eq!(kill(pid, 0), -22);
+    println!("Test signals OK!");
+}
+
+fn itimer() {
+    assert_eq!(handle(SIGALRM), 0);
+    let timer = ITimerVal {
+        interval: TimeVal::new(),
+        value: TimeVal {
+            sec: 0,
+            usec: 10_000,
+        },
+    };
+    let start = get_time();
+    assert_eq!(setitimer(ITIMER_REAL, &timer, None), 0);
+    while ALRM_CAUGHT.load(Ordering::SeqCst) == 0 {
+        yield_();
+    }
+    assert!(get_time() - start >= 10);
+    let bad = ITimerVal {
+        interval: TimeVal::new(),
+        value: TimeVal {
+            sec: 0,
+            usec: 1_000_000,
+        },
+    };
+    assert_eq!(setitimer(ITIMER_REAL, &bad, None), -22);
+    println!("Test setitimer OK!");
+}
+
+fn io() {
+    let parts = ["Test ", "writev OK!\n"];
+    let iov = parts.map(|part| IoVec {
+        base: part.as_ptr(),
+        len: part.len(),
+    });
+    // not to be overtaken by what println! buffered
+    flush();
+    assert_eq!(writev(STDOUT, &iov), 16);
+    let bad = [IoVec {
+        base: core::ptr::null(),
+        len: 1,
+    }];
+    assert_eq!(writev(STDOUT, &bad), -14);
+
+    let mut fds = [
+        PollFd {
+            fd: STDOUT as i32,
+            events: POLLOUT,
+            revents: 0,
+        },
+        PollFd {
+            fd: 7,
+            events: POLLIN,
+            revents: 0,
+        },
+    ];
+    assert_eq!(ppoll(&mut fds, Some(&TimeSpec::default())), 2);
+    assert_eq!(fds[0].revents, POLLOUT);
+    assert_eq!(fds[1].revents, POLLNVAL);
+    let bad = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(ppoll(&mut fds, Some(&bad)), -22);
+    println!("Test ppoll OK!");
+}
+
+fn futex() {
+    assert_eq!(futex_wait(&WORD, 1), -11);
+    assert_eq!(futex_wake(&WORD, 1), 0);
+    // not aligned
+    assert_eq!(sys_futex(&WORD as *const _ as usize + 1, 1, 1), -14);
+    println!("Test futex OK!");
+}
+
+fn scheduling(pid: isize) {
+    assert_eq!(yield_to(pid as usize), 0);
+    assert_eq!(yield_to(100), -22);
+    assert_eq!(getpgid(0), pid);
+    assert_eq!(setpgid(0, 100), -22);
+    assert_eq!(setpgid(0, 0), 0);
+    assert_eq!(getpgid(0), pid);
+    // only privileged tasks may lower it
+    assert_eq!(nice(-1), -1);
+    assert_eq!(nice(0), 0);
+    assert_eq!(get_nice(0), Some(0));
+    assert_eq!(get_nice(100), None);
+    // only privileged tasks may resume, nor could a stopped task itself
+    assert_eq!(task_resume(pid as usize), -1);
+    println!("Test yield_to, process groups and nice OK!");
+}
+
+fn task_groups(pid: isize) {
+    assert_eq!(set_task_group(0, 1), 0);
+    assert_eq!(set_task_group(0, 100), -22);
+    // only privileged tasks may move others, the lock waiter among them
+    let others: Vec<isize> = (0..MAX_APPS)
+        .filter(|&id| id != pid as usize)
+        .map(|id| set_task_group(id, 1))
+        .filter(|&ret| ret != -3)
+        .collect();
+    assert!(!others.is_empty() && others.iter().all(|&ret| ret == -1));
+    assert_eq!(set_group_quota(1, 50), -1);
+    assert_eq!(set_task_group(pid as usize, 0), 0);
+    println!("Test task groups OK!");
+}
+
+fn affinity(pid: isize) {
+    let mut mask = 0;
+    assert_eq!(sched_getaffinity(0, &mut mask), 8);
+    assert_ne!(mask, 0);
+    assert_eq!(sched_setaffinity(0, mask), 0);
+    assert_eq!(sched_setaffinity(0, 0), -22);
+    // only privileged tasks may restrict others
+    let others: Vec<isize> = (0..MAX_APPS)
+        .filter(|&id| id != pid as usize)
+        .map(|id| sched_setaffinity(id, mask))
+        .filter(|&ret| ret != -3)
+        .collect();
+    assert!(!others.is_empty() && others.iter().all(|&ret| ret == -1));
+    println!("Test sched affinity OK!");
+}
+
+/// What takes privilege, which ch3b_privileged has.
+fn unprivileged() {
+    assert_eq!(sysctl("coredump.enable", None, Some(&0)), -1);
+    let mut tick_hz = 0;
+    assert_eq!(sysctl("timer.tick_hz", Some(&mut tick_hz), None), 0);
+    assert_ne!(tick_hz, 0);
+    assert_eq!(settimeofday(&TimeVal::new()), -1);
+    assert_eq!(adjtime(0, None), -1);
+    assert_eq!(ptrace(PTRACE_ATTACH, 0, 0, 0), -1);
+    // lowering the hard limit is allowed, raising it back isn't
+    let lowered = RLimit {
+        cur: 3600,
+        max: 3600,
+    };
+    assert_eq!(setrlimit(RLIMIT_CPU, &lowered), 0);
+    let raised = RLimit {
+        cur: 3600,
+        max: RLIM_INFINITY,
+    };
+    assert_eq!(setrlimit(RLIMIT_CPU, &raised), -1);
+    println!("Test privileged syscalls refused OK!");
+}
+
+fn memory() {
+    let page = main as usize & !(PAGE_SIZE - 1);
+    let mut vec = [0u8; 2];
+    assert_eq!(mincore(page, PAGE_SIZE + 1, &mut vec), 0);
+    assert_eq!(vec, [MINCORE_RESIDENT; 2]);
+    assert_eq!(mincore(page + 1, 1, &mut vec), -22);
+    assert_eq!(mincore(page, usize::MAX, &mut vec), -12);
+    // the page count would overflow if rounded up by adding
+    assert_eq!(mincore(0, usize::MAX, &mut vec), -12);
+    println!("Test mincore OK!");
+}
+
+fn ring() {
+    assert_eq!(ring_enter(1), -22);
+    assert_eq!(sys_ring_setup(0x1000, 4), -14);
+    let ring = unsafe { &mut RING };
+    assert_eq!(ring_setup(ring), 0);
+    let requests = [
+        (RING_OP_NOP, STDOUT, core::ptr::null()),
+        // read from and write to the wrong end of the console
+        (RING_OP_READ, STDOUT, b"x".as_ptr()),
+        (RING_OP_WRITE, STDOUT, core::ptr::null()),
+        (7, STDOUT, core::ptr::null()),
+    ];
+    for (i, &(opcode, fd, buf)) in requests.iter().enumerate() {
+        ring.sqes[i] = RingSqe {
+            opcode,
+            fd: fd as u32,
+            buf: buf as usize,
+            len: 1,
+            user_data: i + 100,
+        };
+    }
+    ring.header.sq_tail = 4;
+    assert_eq!(ring_enter(3), 3);
+    assert_eq!(ring_enter(8), 1);
+    assert_eq!((ring.header.sq_head, ring.header.cq_tail), (4, 4));
+    let results = ring.cqes.map(|cqe| (cqe.user_data, cqe.result));
+    assert_eq!(results, [(100, 0), (101, -9), (102, -14), (103, -22)]);
+    println!("Test ring OK!");
+}
+
+/// Spin ahead of the lock holder, but not of the lock waiter.
+fn middle() {
+    while sys_mutex_unlock(1) != -1 {
+        sleep_ms(10);
+    }
+    let start = get_time();
+    while get_time() < start + SPIN_MS {}
+}
+
+/// Tell the killer in ch3b_lock_waiter where to find us, and wait on a futex
+/// for it to suspend, wake and resume us, then to kill our process group.
+fn member(pid: isize) -> ! {
+    assert_eq!(mq_send(0, b"early"), -22);
+    assert_eq!(mq_create(1, MQ_OTHERS_RECEIVE), 0);
+    let msg = [pid as usize, &WORD as *const _ as usize];
+    let bytes =
+        unsafe { core::slice::from_raw_parts(msg.as_ptr() as *const u8, size_of_val(&msg)) };
+    assert_eq!(mq_send(0, bytes), 0);
+    // woken while suspended, so not until resumed
+    futex_wait(&WORD, 0);
+    WORD.store(1, Ordering::SeqCst);
+    futex_wait(&WORD, 1);
+    panic!("not killed while waiting on a futex");
+}
+
+/// 测试 ch3 新增的系统调用，再与 ch3b_lock_holder、ch3b_lock_waiter
+/// 一起测试优先级继承（本任务在互斥锁 0 被持有后以中等优先级空转 2 秒）、
+/// 挂起恢复和进程组 kill（本任务等待 futex 时被 ch3b_lock_waiter 挂起、
+/// 唤醒、恢复，再被杀死）。
+/// 三者必须一起构建、一起运行，见 Makefile 的 TOGETHER。
+/// 正确输出：（无报错信息）
+/// Test signals OK!
+/// Test setitimer OK!
+/// Test writev OK!
+/// Test ppoll OK!
+/// Test futex OK!
+/// Test yield_to, process groups and nice OK!
+/// Test task groups OK!
+/// Test sched affinity OK!
+/// Test privileged syscalls refused OK!
+/// Test mincore OK!
+/// Test ring OK!
+#[no_mangle]
+fn main() -> i32 {
+    let pid = getpid();
+    signals(pid);
+    itimer();
+    io();
+    futex();
+    scheduling(pid);
+    task_groups(pid);
+    affinity(pid);
+    unprivileged();
+    memory();
+    ring();
+    middle();
+    member(pid)
+}
@@ -6,6 +6,7 @@
 #[macro_use]
 pub mod console;
 mod lang_items;
+pub mod meta;
 mod syscall;
 
 extern crate alloc;
@@ -16,6 +17,8 @@ extern crate bitflags;
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
 pub use console::{flush, STDIN, STDOUT};
+use core::sync::atomic::AtomicU32;
+pub use meta::AppMeta;
 pub use syscall::*;
 
 const USER_HEAP_SIZE: usize = 16384;
@@ -98,6 +101,134 @@ impl TimeVal {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+/// CPU time of the calling task
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+/// `clock_nanosleep` flag: sleep until an absolute time
+pub const TIMER_ABSTIME: usize = 1;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+pub const ITIMER_REAL: usize = 0;
+
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGUSR2: usize = 12;
+pub const SIGALRM: usize = 14;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// What to do on a signal. A `handler` gets the signal number as its
+/// argument and must finish with [`sigreturn`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct SignalAction {
+    pub handler: usize,
+    /// signals masked while the handler runs, bit `signum` for each
+    pub mask: u32,
+}
+
+#[repr(C)]
+pub struct IoVec {
+    pub base: *const u8,
+    pub len: usize,
+}
+
+pub const POLLIN: i16 = 0x1;
+pub const POLLOUT: i16 = 0x4;
+pub const POLLNVAL: i16 = 0x20;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+/// `mq_create` mode bit: tasks other than the owner may send
+pub const MQ_OTHERS_SEND: usize = 1;
+/// `mq_create` mode bit: tasks other than the owner may receive
+pub const MQ_OTHERS_RECEIVE: usize = 2;
+
+pub const RING_OP_NOP: u32 = 0;
+pub const RING_OP_WRITE: u32 = 1;
+pub const RING_OP_READ: u32 = 2;
+
+/// Ring counters; userspace moves `sq_tail` and `cq_head`, the kernel the
+/// others.
+#[repr(C)]
+#[derive(Debug)]
+pub struct RingHeader {
+    pub sq_head: u32,
+    pub sq_tail: u32,
+    pub cq_head: u32,
+    pub cq_tail: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RingSqe {
+    pub opcode: u32,
+    pub fd: u32,
+    pub buf: usize,
+    pub len: usize,
+    pub user_data: usize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RingCqe {
+    pub user_data: usize,
+    pub result: isize,
+}
+
+/// A ring of `N` slots per queue, laid out the way the kernel expects.
+#[repr(C)]
+pub struct Ring<const N: usize> {
+    pub header: RingHeader,
+    pub sqes: [RingSqe; N],
+    pub cqes: [RingCqe; N],
+}
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Ring {
+            header: RingHeader {
+                sq_head: 0,
+                sq_tail: 0,
+                cq_head: 0,
+                cq_tail: 0,
+            },
+            sqes: [RingSqe {
+                opcode: RING_OP_NOP,
+                fd: 0,
+                buf: 0,
+                len: 0,
+                user_data: 0,
+            }; N],
+            cqes: [RingCqe {
+                user_data: 0,
+                result: 0,
+            }; N],
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum TaskStatus {
     UnInit,
@@ -131,6 +262,155 @@ impl TaskInfo {
     }
 }
 
+/// Time spent running, ready and blocked, in microseconds.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct TaskTimes {
+    pub run_us: usize,
+    pub wait_us: usize,
+    pub block_us: usize,
+}
+
+/// Traps taken from user mode, by kind.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct TrapStats {
+    pub traps: usize,
+    pub syscalls: usize,
+    pub page_faults: usize,
+    pub illegal_instructions: usize,
+    pub timer_interrupts: usize,
+    pub preemptions: usize,
+    pub unknown_syscalls: usize,
+}
+
+/// Cycles and instructions retired while the task ran.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct PerfCounters {
+    pub cycles: usize,
+    pub instret: usize,
+}
+
+/// CPU time in clock ticks, see [`times`].
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Rusage {
+    pub utime_us: usize,
+    pub stime_us: usize,
+    pub maxrss_frames: usize,
+    /// times the CPU was given up voluntarily
+    pub nvcsw: usize,
+    /// times the task was preempted
+    pub nivcsw: usize,
+    pub nsyscalls: usize,
+    pub wait_us: usize,
+    pub block_us: usize,
+}
+
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+/// Scheduling latencies in microseconds, bucket `n > 0` counting those in
+/// `[2^(n-1), 2^n)`.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    pub buckets: [u32; 16],
+    pub total_us: usize,
+    pub max_us: usize,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct SchedStat {
+    pub switches: usize,
+    pub voluntary: usize,
+    pub preemptions: usize,
+    pub ready: usize,
+    /// `Ready` tasks by effective priority, from nice -20 on
+    pub ready_by_priority: [u32; 40],
+}
+
+impl SchedStat {
+    pub fn new() -> Self {
+        SchedStat {
+            switches: 0,
+            voluntary: 0,
+            preemptions: 0,
+            ready: 0,
+            ready_by_priority: [0; 40],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct SysInfo {
+    pub uptime_us: usize,
+    /// bit `n` for hart `n`
+    pub harts: usize,
+    pub idle_us: [usize; 8],
+    pub ready: usize,
+    pub tasks: usize,
+    pub free_frames: usize,
+    pub heap_total: usize,
+    pub heap_free: usize,
+}
+
+pub const MAP_READ: u32 = 1;
+pub const MAP_WRITE: u32 = 2;
+pub const MAP_EXEC: u32 = 4;
+pub const MAP_KIND_ELF: u32 = 0;
+pub const MAP_KIND_STACK: u32 = 1;
+pub const MAP_KIND_SHARED: u32 = 4;
+
+/// A region of memory of the task, see [`get_mappings`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Mapping {
+    pub start: usize,
+    pub end: usize,
+    pub perms: u32,
+    pub kind: u32,
+}
+
+/// The time page the kernel refreshes whenever it reads the clock, see
+/// [`time_page_us`].
+///
+/// Any app can write it too, so the time is advisory.
+#[repr(C)]
+#[derive(Debug)]
+pub struct TimePage {
+    /// odd while the kernel updates the page
+    pub seq: usize,
+    pub time: usize,
+    pub time_us: usize,
+    pub clock_freq: usize,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Stat {
@@ -195,6 +475,16 @@ pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+
+/// Wait until one of `fds` is ready, or for `timeout` at most. Returns how
+/// many are ready, 0 on timeout.
+pub fn ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    sys_ppoll(fds, timeout)
+}
+
 pub fn link(old_path: &str, new_path: &str) -> isize {
     sys_linkat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
 }
@@ -224,6 +514,76 @@ pub fn yield_() -> isize {
     sys_yield()
 }
 
+/// Give the rest of the time slice to task `pid`.
+pub fn yield_to(pid: usize) -> isize {
+    sys_yield_to(pid)
+}
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// Block while `word` holds `val`, until woken by [`futex_wake`] or a
+/// signal.
+pub fn futex_wait(word: &AtomicU32, val: u32) -> isize {
+    sys_futex(word as *const _ as usize, FUTEX_WAIT, val as usize)
+}
+
+/// Wake up to `n` tasks waiting on `word`, returning how many were woken.
+pub fn futex_wake(word: &AtomicU32, n: usize) -> isize {
+    sys_futex(word as *const _ as usize, FUTEX_WAKE, n)
+}
+
+/// Send signal `signum` to task `pid`, or to process group `-pid`.
+pub fn kill(pid: isize, signum: usize) -> isize {
+    sys_kill(pid, signum)
+}
+
+pub fn sigaction(
+    signum: usize,
+    action: &SignalAction,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    sys_sigaction(signum, action, old_action)
+}
+
+/// Replace the signal mask, returning the old one.
+pub fn sigprocmask(mask: u32) -> isize {
+    sys_sigprocmask(mask)
+}
+
+/// Return from a signal handler to where the signal came.
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
+}
+
+pub fn setitimer(which: usize, new: &ITimerVal, old: Option<&mut ITimerVal>) -> isize {
+    sys_setitimer(which, new, old)
+}
+
+/// Move task `pid`, 0 for the caller, to process group `pgid`, 0 for a new
+/// group led by the task.
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+
+/// Create a message queue of `capacity` messages, returning its id.
+pub fn mq_create(capacity: usize, mode: usize) -> isize {
+    sys_mq_create(capacity, mode)
+}
+
+pub fn mq_send(id: usize, msg: &[u8]) -> isize {
+    sys_mq_send(id, msg)
+}
+
+/// Receive a message into `buf`, waiting for one, and return its length.
+pub fn mq_receive(id: usize, buf: &mut [u8]) -> isize {
+    sys_mq_receive(id, buf)
+}
+
 pub fn get_time() -> isize {
     let time = TimeVal::new();
     match sys_get_time(&time, 0) {
@@ -232,6 +592,95 @@ pub fn get_time() -> isize {
     }
 }
 
+pub fn clock_gettime(clock_id: usize, ts: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, ts)
+}
+
+/// Sleep for `req`, or until `req` with [`TIMER_ABSTIME`]. When a signal
+/// cuts a relative sleep short, the time left is stored in `rem`.
+pub fn clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: &TimeSpec,
+    rem: Option<&mut TimeSpec>,
+) -> isize {
+    sys_clock_nanosleep(clock_id, flags, req, rem)
+}
+
+/// Sleep for `ms` milliseconds, blocked rather than yielding like [`sleep`].
+pub fn sleep_ms(ms: usize) -> isize {
+    let req = TimeSpec {
+        sec: ms / 1000,
+        nsec: ms % 1000 * 1_000_000,
+    };
+    clock_nanosleep(CLOCK_MONOTONIC, 0, &req, None)
+}
+
+pub const PTRACE_PEEKDATA: usize = 2;
+pub const PTRACE_SINGLESTEP: usize = 9;
+pub const PTRACE_ATTACH: usize = 16;
+pub const PTRACE_DETACH: usize = 17;
+
+pub fn ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    sys_ptrace(request, pid, addr, data)
+}
+
+/// Read kernel tunable `name` into `old`, then set it to `new`; either may
+/// be left out.
+pub fn sysctl(name: &str, old: Option<&mut usize>, new: Option<&usize>) -> isize {
+    sys_sysctl(name, old, new)
+}
+
+/// Register `ring` for [`ring_enter`]. The kernel keeps using it until
+/// another one is registered, so it has to stay put until then.
+pub fn ring_setup<const N: usize>(ring: &mut Ring<N>) -> isize {
+    sys_ring_setup(ring as *mut _ as usize, N)
+}
+
+/// Have up to `to_submit` queued requests processed, returning how many
+/// were.
+pub fn ring_enter(to_submit: usize) -> isize {
+    sys_ring_enter(to_submit)
+}
+
+/// Add `increment` to the nice value of the calling task. Only privileged
+/// tasks may lower it.
+pub fn nice(increment: isize) -> isize {
+    sys_nice(increment)
+}
+
+/// Restrict task `pid`, 0 for the caller, to the harts in `mask`, bit `i`
+/// for hart `i`.
+pub fn sched_setaffinity(pid: usize, mask: usize) -> isize {
+    sys_sched_setaffinity(pid, &mask)
+}
+
+/// Get the harts task `pid`, 0 for the caller, may run on.
+pub fn sched_getaffinity(pid: usize, mask: &mut usize) -> isize {
+    sys_sched_getaffinity(pid, mask)
+}
+
+/// Move task `pid`, 0 for the caller, into task group `group`.
+pub fn set_task_group(pid: usize, group: usize) -> isize {
+    sys_set_task_group(pid, group)
+}
+
+/// Let the tasks of `group` run `quota` percent of the time at most.
+pub fn set_group_quota(group: usize, quota: usize) -> isize {
+    sys_set_group_quota(group, quota)
+}
+
+const PRIO_PROCESS: usize = 0;
+
+/// Get the nice value of task `pid`, 0 for the caller.
+pub fn get_nice(pid: usize) -> Option<isize> {
+    // the kernel returns `20 - nice`, never negative
+    match sys_getpriority(PRIO_PROCESS, pid) {
+        prio if prio > 0 => Some(20 - prio),
+        _ => None,
+    }
+}
+
 pub fn getpid() -> isize {
     sys_getpid()
 }
@@ -292,6 +741,14 @@ pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
 }
 
+pub const MINCORE_RESIDENT: u8 = 1;
+
+/// Get a byte of `MINCORE_*` bits into `vec` for each page of the `len`
+/// bytes at `addr`; `vec` must have room for all of them.
+pub fn mincore(addr: usize, len: usize, vec: &mut [u8]) -> isize {
+    sys_mincore(addr, len, vec)
+}
+
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path)
 }
@@ -307,6 +764,126 @@ pub fn task_info(info: &TaskInfo) -> isize {
     sys_task_info(info)
 }
 
+/// Like [`task_info`], but also gets where the time went. Only fills `times`
+/// on kernels that support it.
+pub fn task_times(info: &TaskInfo, times: &mut TaskTimes) -> isize {
+    sys_task_info_times(info, times)
+}
+
+/// Like [`task_info`], but also gets the traps taken. Only fills `traps` on
+/// kernels that support it.
+pub fn task_trap_stats(info: &TaskInfo, traps: &mut TrapStats) -> isize {
+    sys_task_info_traps(info, traps)
+}
+
+/// Fill `buf` with random bytes, returning how many were filled.
+pub fn getrandom(buf: &mut [u8], flags: u32) -> isize {
+    sys_getrandom(buf, flags)
+}
+
+pub fn perf_read(counters: &mut PerfCounters) -> isize {
+    sys_perf_read(counters)
+}
+
+/// Count SBI PMU event `event_idx` while the task runs, returning a handle
+/// for [`perf_event_read`].
+pub fn perf_event_open(event_idx: usize, event_data: usize) -> isize {
+    sys_perf_event_open(event_idx, event_data)
+}
+
+pub fn perf_event_read(handle: usize, count: &mut usize) -> isize {
+    sys_perf_event_read(handle, count)
+}
+
+/// Get the scheduling latencies of task `pid`, 0 for the caller, or of all
+/// tasks if -1.
+pub fn sched_latency(pid: isize, hist: &mut LatencyHistogram) -> isize {
+    sys_sched_latency(pid, hist)
+}
+
+/// Get the CPU time of the caller, returning the clock ticks since boot.
+pub fn times(tms: &mut Tms) -> isize {
+    sys_times(tms)
+}
+
+pub fn getrusage(who: isize, usage: &mut Rusage) -> isize {
+    sys_getrusage(who, usage)
+}
+
+pub fn sched_getstat(stat: &mut SchedStat) -> isize {
+    sys_sched_getstat(stat)
+}
+
+pub fn sysinfo(info: &mut SysInfo) -> isize {
+    sys_sysinfo(info)
+}
+
+/// Fill `buf` with the mappings of the caller, returning how many there are,
+/// which may be more than fit.
+pub fn get_mappings(buf: &mut [Mapping]) -> isize {
+    sys_get_mappings(buf)
+}
+
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_getrlimit(resource, rlim)
+}
+
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_setrlimit(resource, rlim)
+}
+
+pub fn settimeofday(tv: &TimeVal) -> isize {
+    sys_settimeofday(tv)
+}
+
+/// Slew the realtime clock by `delta_us`, getting what was left of the last
+/// slew into `old`.
+pub fn adjtime(delta_us: isize, old: Option<&mut isize>) -> isize {
+    sys_adjtime(delta_us, old)
+}
+
+/// Get the time page, on kernels that have one.
+pub fn time_page() -> Option<&'static TimePage> {
+    match sys_get_time_page() {
+        addr if addr > 0 => Some(unsafe { &*(addr as *const TimePage) }),
+        _ => None,
+    }
+}
+
+/// Get the time in microseconds the kernel last put in `page`, without a
+/// syscall.
+///
+/// The kernel makes `seq` odd before it updates the page and even again
+/// after, so a read is only whole if `seq` was even before it and the same
+/// after it; otherwise the kernel was writing meanwhile, and it is retried.
+pub fn time_page_us(page: &TimePage) -> usize {
+    loop {
+        let seq = unsafe { core::ptr::read_volatile(&page.seq) };
+        if seq % 2 == 1 {
+            core::hint::spin_loop();
+            continue;
+        }
+        // `time_us` is read after the first `seq`...
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        let time_us = unsafe { core::ptr::read_volatile(&page.time_us) };
+        // ...and before the second
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        if unsafe { core::ptr::read_volatile(&page.seq) } == seq {
+            return time_us;
+        }
+    }
+}
+
+/// Stop task `pid`, 0 for the caller, until [`task_resume`]; a blocked task
+/// stops once it wakes.
+pub fn task_suspend(pid: usize) -> isize {
+    sys_task_suspend(pid)
+}
+
+pub fn task_resume(pid: usize) -> isize {
+    sys_task_resume(pid)
+}
+
 pub fn thread_create(entry: usize, arg: usize) -> isize {
     sys_thread_create(entry, arg)
 }
@@ -0,0 +1,103 @@
+//! App metadata for the kernel, embedded as an ELF note with [`app_meta!`]
+
+/// owner of the note
+const NOTE_NAME: [u8; 8] = *b"rCore\0\0\0";
+/// note type of the app metadata
+const NT_APP_META: u32 = 1;
+
+pub const MAX_REQUIRED_SYSCALLS: usize = 16;
+pub const MAX_DESCRIPTION_LEN: usize = 64;
+/// `nice` of metadata that doesn't ask for one
+pub const NICE_DEFAULT: i32 = i32::MIN;
+
+/// What the app asks of the kernel when it is loaded
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AppMeta {
+    /// nice value to start with
+    pub nice: i32,
+    /// bytes of stack needed, 0 for whatever the kernel gives
+    pub stack_size: u32,
+    /// ids of the syscalls the app can't do without, 0 past the last
+    pub syscalls: [u16; MAX_REQUIRED_SYSCALLS],
+    /// what the app does, NUL padded
+    pub description: [u8; MAX_DESCRIPTION_LEN],
+}
+
+impl AppMeta {
+    pub const fn new() -> Self {
+        Self {
+            nice: NICE_DEFAULT,
+            stack_size: 0,
+            syscalls: [0; MAX_REQUIRED_SYSCALLS],
+            description: [0; MAX_DESCRIPTION_LEN],
+        }
+    }
+
+    pub const fn nice(mut self, nice: i32) -> Self {
+        self.nice = nice;
+        self
+    }
+
+    pub const fn stack_size(mut self, bytes: u32) -> Self {
+        self.stack_size = bytes;
+        self
+    }
+
+    pub const fn syscalls(mut self, ids: &[u16]) -> Self {
+        assert!(ids.len() <= MAX_REQUIRED_SYSCALLS, "too many syscalls");
+        let mut i = 0;
+        while i < ids.len() {
+            self.syscalls[i] = ids[i];
+            i += 1;
+        }
+        self
+    }
+
+    pub const fn description(mut self, text: &str) -> Self {
+        let bytes = text.as_bytes();
+        assert!(bytes.len() <= MAX_DESCRIPTION_LEN, "description too long");
+        let mut i = 0;
+        while i < bytes.len() {
+            self.description[i] = bytes[i];
+            i += 1;
+        }
+        self
+    }
+}
+
+/// An ELF note holding [`AppMeta`]
+#[repr(C, align(4))]
+pub struct AppNote {
+    namesz: u32,
+    descsz: u32,
+    ntype: u32,
+    name: [u8; 8],
+    desc: AppMeta,
+}
+
+impl AppNote {
+    pub const fn new(desc: AppMeta) -> Self {
+        Self {
+            namesz: 6,
+            descsz: core::mem::size_of::<AppMeta>() as u32,
+            ntype: NT_APP_META,
+            name: NOTE_NAME,
+            desc,
+        }
+    }
+}
+
+/// Embed `meta`, an [`AppMeta`], for the kernel to apply when loading the app:
+///
+/// ```ignore
+/// app_meta!(AppMeta::new().nice(-5).description("prints A"));
+/// ```
+#[macro_export]
+macro_rules! app_meta {
+    ($meta:expr) => {
+        #[link_section = ".note.app"]
+        #[used]
+        static APP_NOTE: $crate::meta::AppNote = $crate::meta::AppNote::new($meta);
+    };
+}
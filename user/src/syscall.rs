@@ -1,4 +1,7 @@
-use crate::TaskInfo;
+use crate::{
+    ITimerVal, IoVec, LatencyHistogram, Mapping, PerfCounters, PollFd, RLimit, Rusage, SchedStat,
+    SignalAction, SysInfo, TaskInfo, TaskTimes, TimeSpec, Tms, TrapStats,
+};
 
 use super::{Stat, TimeVal};
 
@@ -6,18 +9,42 @@ pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_WRITEV: usize = 66;
+pub const SYSCALL_PPOLL: usize = 73;
 pub const SYSCALL_UNLINKAT: usize = 35;
 pub const SYSCALL_LINKAT: usize = 37;
 pub const SYSCALL_FSTAT: usize = 80;
 pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_FUTEX: usize = 98;
 pub const SYSCALL_SLEEP: usize = 101;
+pub const SYSCALL_SETITIMER: usize = 103;
+pub const SYSCALL_CLOCK_GETTIME: usize = 113;
+pub const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+pub const SYSCALL_PTRACE: usize = 117;
+pub const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+pub const SYSCALL_SCHED_GETAFFINITY: usize = 123;
 pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_KILL: usize = 129;
+pub const SYSCALL_SIGACTION: usize = 134;
+pub const SYSCALL_SIGPROCMASK: usize = 135;
+pub const SYSCALL_SIGRETURN: usize = 139;
+pub const SYSCALL_GETPRIORITY: usize = 141;
+pub const SYSCALL_TIMES: usize = 153;
+pub const SYSCALL_SETPGID: usize = 154;
+pub const SYSCALL_GETPGID: usize = 155;
+pub const SYSCALL_GETRLIMIT: usize = 163;
+pub const SYSCALL_SETRLIMIT: usize = 164;
+pub const SYSCALL_GETRUSAGE: usize = 165;
 pub const SYSCALL_GETTIMEOFDAY: usize = 169;
+pub const SYSCALL_SETTIMEOFDAY: usize = 170;
 pub const SYSCALL_GETPID: usize = 172;
 pub const SYSCALL_GETTID: usize = 178;
+pub const SYSCALL_SYSINFO: usize = 179;
 pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_MINCORE: usize = 232;
 pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_GETRANDOM: usize = 278;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
@@ -27,6 +54,26 @@ pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
 pub const SYSCALL_PIPE: usize = 59;
 pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_GET_TIME_PAGE: usize = 411;
+pub const SYSCALL_RING_SETUP: usize = 412;
+pub const SYSCALL_RING_ENTER: usize = 413;
+pub const SYSCALL_SYSCTL: usize = 414;
+pub const SYSCALL_PERF_READ: usize = 415;
+pub const SYSCALL_PERF_EVENT_OPEN: usize = 416;
+pub const SYSCALL_PERF_EVENT_READ: usize = 417;
+pub const SYSCALL_NICE: usize = 418;
+pub const SYSCALL_SET_TASK_GROUP: usize = 419;
+pub const SYSCALL_SET_GROUP_QUOTA: usize = 420;
+pub const SYSCALL_SCHED_LATENCY: usize = 421;
+pub const SYSCALL_ADJTIME: usize = 422;
+pub const SYSCALL_YIELD_TO: usize = 423;
+pub const SYSCALL_MQ_CREATE: usize = 424;
+pub const SYSCALL_MQ_SEND: usize = 425;
+pub const SYSCALL_MQ_RECEIVE: usize = 426;
+pub const SYSCALL_SCHED_GETSTAT: usize = 427;
+pub const SYSCALL_GET_MAPPINGS: usize = 428;
+pub const SYSCALL_TASK_SUSPEND: usize = 432;
+pub const SYSCALL_TASK_RESUME: usize = 433;
 pub const SYSCALL_THREAD_CREATE: usize = 460;
 pub const SYSCALL_WAITTID: usize = 462;
 pub const SYSCALL_MUTEX_CREATE: usize = 463;
@@ -48,7 +95,9 @@ pub fn syscall(id: usize, args: [usize; 3]) -> isize {
             inlateout("x10") args[0] => ret,
             in("x11") args[1],
             in("x12") args[2],
-            in("x17") id
+            in("x17") id,
+            // the kernel only keeps what the C calling convention keeps
+            clobber_abi("C")
         );
     }
     ret
@@ -64,7 +113,8 @@ pub fn syscall6(id: usize, args: [usize; 6]) -> isize {
             in("x13") args[3],
             in("x14") args[4],
             in("x15") args[5],
-            in("x17") id
+            in("x17") id,
+            clobber_abi("C")
         );
     }
     ret
@@ -99,6 +149,18 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    let timeout = timeout.map_or(0, |timeout| timeout as *const _ as usize);
+    syscall(
+        SYSCALL_PPOLL,
+        [fds.as_mut_ptr() as usize, fds.len(), timeout],
+    )
+}
+
 pub fn sys_linkat(
     old_dirfd: usize,
     old_path: &str,
@@ -154,10 +216,90 @@ pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
+pub fn sys_futex(addr: usize, op: usize, val: usize) -> isize {
+    syscall(SYSCALL_FUTEX, [addr, op, val])
+}
+
+pub fn sys_setitimer(which: usize, new: &ITimerVal, old: Option<&mut ITimerVal>) -> isize {
+    let old = old.map_or(0, |old| old as *mut _ as usize);
+    syscall(SYSCALL_SETITIMER, [which, new as *const _ as usize, old])
+}
+
+pub fn sys_sched_setaffinity(pid: usize, mask: &usize) -> isize {
+    syscall(
+        SYSCALL_SCHED_SETAFFINITY,
+        [
+            pid,
+            core::mem::size_of::<usize>(),
+            mask as *const _ as usize,
+        ],
+    )
+}
+
+pub fn sys_sched_getaffinity(pid: usize, mask: &mut usize) -> isize {
+    syscall(
+        SYSCALL_SCHED_GETAFFINITY,
+        [pid, core::mem::size_of::<usize>(), mask as *mut _ as usize],
+    )
+}
+
+pub fn sys_kill(pid: isize, signum: usize) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, signum, 0])
+}
+
+pub fn sys_sigaction(
+    signum: usize,
+    action: &SignalAction,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    let old_action = old_action.map_or(0, |old_action| old_action as *mut _ as usize);
+    syscall(
+        SYSCALL_SIGACTION,
+        [signum, action as *const _ as usize, old_action],
+    )
+}
+
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0])
+}
+
+pub fn sys_sigreturn() -> isize {
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+}
+
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
 pub fn sys_get_time(time: &TimeVal, tz: usize) -> isize {
     syscall(SYSCALL_GETTIMEOFDAY, [time as *const _ as usize, tz, 0])
 }
 
+pub fn sys_clock_gettime(clock_id: usize, ts: &mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clock_id, ts as *mut _ as usize, 0])
+}
+
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: &TimeSpec,
+    rem: Option<&mut TimeSpec>,
+) -> isize {
+    let rem = rem.map_or(0, |rem| rem as *mut _ as usize);
+    syscall6(
+        SYSCALL_CLOCK_NANOSLEEP,
+        [clock_id, flags, req as *const _ as usize, rem, 0, 0],
+    )
+}
+
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    syscall6(SYSCALL_PTRACE, [request, pid, addr, data, 0, 0])
+}
+
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
@@ -189,6 +331,10 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
 
+pub fn sys_mincore(addr: usize, len: usize, vec: &mut [u8]) -> isize {
+    syscall(SYSCALL_MINCORE, [addr, len, vec.as_mut_ptr() as usize])
+}
+
 pub fn sys_spawn(path: &str) -> isize {
     syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
 }
@@ -205,6 +351,156 @@ pub fn sys_task_info(info: &TaskInfo) -> isize {
     syscall(SYSCALL_TASK_INFO, [info as *const _ as usize, 0, 0])
 }
 
+pub fn sys_task_info_times(info: &TaskInfo, times: &mut TaskTimes) -> isize {
+    syscall(
+        SYSCALL_TASK_INFO,
+        [info as *const _ as usize, times as *mut _ as usize, 0],
+    )
+}
+
+pub fn sys_task_info_traps(info: &TaskInfo, traps: &mut TrapStats) -> isize {
+    syscall(
+        SYSCALL_TASK_INFO,
+        [info as *const _ as usize, 0, traps as *mut _ as usize],
+    )
+}
+
+pub fn sys_get_time_page() -> isize {
+    syscall(SYSCALL_GET_TIME_PAGE, [0, 0, 0])
+}
+
+pub fn sys_ring_setup(addr: usize, entries: usize) -> isize {
+    syscall(SYSCALL_RING_SETUP, [addr, entries, 0])
+}
+
+pub fn sys_ring_enter(to_submit: usize) -> isize {
+    syscall(SYSCALL_RING_ENTER, [to_submit, 0, 0])
+}
+
+pub fn sys_sysctl(name: &str, old: Option<&mut usize>, new: Option<&usize>) -> isize {
+    let old = old.map_or(0, |old| old as *mut _ as usize);
+    let new = new.map_or(0, |new| new as *const _ as usize);
+    syscall6(
+        SYSCALL_SYSCTL,
+        [name.as_ptr() as usize, name.len(), old, new, 0, 0],
+    )
+}
+
+pub fn sys_nice(increment: isize) -> isize {
+    syscall(SYSCALL_NICE, [increment as usize, 0, 0])
+}
+
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    syscall(SYSCALL_GETPRIORITY, [which, who, 0])
+}
+
+pub fn sys_set_task_group(pid: usize, group: usize) -> isize {
+    syscall(SYSCALL_SET_TASK_GROUP, [pid, group, 0])
+}
+
+pub fn sys_set_group_quota(group: usize, quota: usize) -> isize {
+    syscall(SYSCALL_SET_GROUP_QUOTA, [group, quota, 0])
+}
+
+pub fn sys_yield_to(pid: usize) -> isize {
+    syscall(SYSCALL_YIELD_TO, [pid, 0, 0])
+}
+
+pub fn sys_mq_create(capacity: usize, mode: usize) -> isize {
+    syscall(SYSCALL_MQ_CREATE, [capacity, mode, 0])
+}
+
+pub fn sys_mq_send(id: usize, msg: &[u8]) -> isize {
+    syscall(SYSCALL_MQ_SEND, [id, msg.as_ptr() as usize, msg.len()])
+}
+
+pub fn sys_mq_receive(id: usize, buf: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_MQ_RECEIVE,
+        [id, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+
+pub fn sys_task_suspend(pid: usize) -> isize {
+    syscall(SYSCALL_TASK_SUSPEND, [pid, 0, 0])
+}
+
+pub fn sys_task_resume(pid: usize) -> isize {
+    syscall(SYSCALL_TASK_RESUME, [pid, 0, 0])
+}
+
+pub fn sys_getrandom(buf: &mut [u8], flags: u32) -> isize {
+    syscall(
+        SYSCALL_GETRANDOM,
+        [buf.as_mut_ptr() as usize, buf.len(), flags as usize],
+    )
+}
+
+pub fn sys_perf_read(counters: &mut PerfCounters) -> isize {
+    syscall(SYSCALL_PERF_READ, [counters as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_perf_event_open(event_idx: usize, event_data: usize) -> isize {
+    syscall(SYSCALL_PERF_EVENT_OPEN, [event_idx, event_data, 0])
+}
+
+pub fn sys_perf_event_read(handle: usize, count: &mut usize) -> isize {
+    syscall(
+        SYSCALL_PERF_EVENT_READ,
+        [handle, count as *mut _ as usize, 0],
+    )
+}
+
+pub fn sys_sched_latency(pid: isize, hist: &mut LatencyHistogram) -> isize {
+    syscall(
+        SYSCALL_SCHED_LATENCY,
+        [pid as usize, hist as *mut _ as usize, 0],
+    )
+}
+
+pub fn sys_times(tms: &mut Tms) -> isize {
+    syscall(SYSCALL_TIMES, [tms as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_getrusage(who: isize, usage: &mut Rusage) -> isize {
+    syscall(
+        SYSCALL_GETRUSAGE,
+        [who as usize, usage as *mut _ as usize, 0],
+    )
+}
+
+pub fn sys_sched_getstat(stat: &mut SchedStat) -> isize {
+    syscall(SYSCALL_SCHED_GETSTAT, [stat as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_sysinfo(info: &mut SysInfo) -> isize {
+    syscall(SYSCALL_SYSINFO, [info as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_get_mappings(buf: &mut [Mapping]) -> isize {
+    syscall(
+        SYSCALL_GET_MAPPINGS,
+        [buf.as_mut_ptr() as usize, buf.len(), 0],
+    )
+}
+
+pub fn sys_getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlim as *mut _ as usize, 0])
+}
+
+pub fn sys_setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlim as *const _ as usize, 0])
+}
+
+pub fn sys_settimeofday(tv: &TimeVal) -> isize {
+    syscall(SYSCALL_SETTIMEOFDAY, [tv as *const _ as usize, 0, 0])
+}
+
+pub fn sys_adjtime(delta_us: isize, old: Option<&mut isize>) -> isize {
+    let old = old.map_or(0, |old| old as *mut _ as usize);
+    syscall(SYSCALL_ADJTIME, [delta_us as usize, old, 0])
+}
+
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
 }